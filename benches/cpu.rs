@@ -0,0 +1,50 @@
+//! Runs Klaus Dormann's functional test to completion and reports the emulated
+//! clock rate. Run with `cargo bench`, with `NES_6502_FUNCTIONAL_TEST` pointing at
+//! the test image (see tests/roms/README.md).
+
+use nes::bus::FlatBus;
+use nes::cpu::CPU;
+
+use std::time::Instant;
+
+const START_ADDR: u16 = 0x0400;
+const SUCCESS_ADDR: u16 = 0x3469;
+const RUNS: u32 = 5;
+
+fn main() {
+    let Ok(path) = std::env::var("NES_6502_FUNCTIONAL_TEST") else {
+        println!("NES_6502_FUNCTIONAL_TEST is not set, skipping the CPU benchmark");
+        return;
+    };
+    let program = std::fs::read(path).unwrap();
+    let mut best = f64::MAX;
+    let mut cycles = 0;
+
+    for _ in 0..RUNS {
+        let mut bus = FlatBus::new();
+        bus.load(0, &program);
+        let mut cpu = CPU::new(bus);
+        cpu.reset_with_start_addr(START_ADDR);
+        cpu.set_decimal_mode(true);
+
+        let start = Instant::now();
+        loop {
+            let pc = cpu.r_pc;
+            cpu.step_instruction().unwrap();
+            if cpu.r_pc == pc {
+                assert_eq!(pc, SUCCESS_ADDR, "trapped at ${:04X}", pc);
+                break;
+            }
+        }
+        best = best.min(start.elapsed().as_secs_f64());
+        cycles = cpu.m_cycles;
+    }
+
+    println!(
+        "6502 functional test: {} cycles in {:.3}s (best of {}), {:.1} MHz",
+        cycles,
+        best,
+        RUNS,
+        cycles as f64 / best / 1e6
+    );
+}
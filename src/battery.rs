@@ -0,0 +1,45 @@
+use std::fs;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Where the battery save of `rom_path` lives: `<rom>.sav` next to the ROM, or in
+/// `save_dir` if one is configured.
+pub fn save_path(rom_path: &Path, save_dir: Option<&Path>) -> PathBuf {
+    let file_name = rom_path.with_extension("sav");
+    match save_dir {
+        Some(dir) => dir.join(file_name.file_name().unwrap_or_default()),
+        None => file_name,
+    }
+}
+
+/// Reads a battery save, `None` if there is none yet.
+pub fn load(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(format!("Reading save file {} failed: {}", path.display(), error)),
+    }
+}
+
+/// Writes a battery save. The data goes to a temporary file first which then replaces
+/// the old save, so a crash mid-write never leaves a truncated save behind.
+pub fn store(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Creating save directory {} failed: {}", dir.display(), e))?;
+    }
+
+    let temp_path = path.with_extension("sav.tmp");
+    let write_temp = || -> std::io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()
+    };
+    write_temp().map_err(|e| format!("Writing save file {} failed: {}", temp_path.display(), e))?;
+
+    fs::rename(&temp_path, path)
+        .map_err(|e| format!("Replacing save file {} failed: {}", path.display(), e))
+}
@@ -0,0 +1,57 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+/// The CPU's view of its 16-bit address space.
+pub trait Bus {
+    fn read(&mut self, addr: Address) -> Byte;
+    /// Reads an opcode. Only differs from `read` for buses that watch execution.
+    fn fetch(&mut self, addr: Address) -> Byte {
+        self.read(addr)
+    }
+    fn write(&mut self, addr: Address, val: Byte);
+    /// Reads without side effects, for tracers, debuggers and the disassembler.
+    fn peek(&self, addr: Address) -> Byte;
+    /// Called once per CPU cycle so the rest of the system can keep pace with the CPU.
+    fn tick(&mut self) {}
+    /// Returns true once after a write started an OAM DMA, so the CPU can stall for it.
+    fn take_dma_request(&mut self) -> bool {
+        false
+    }
+}
+
+/// 64KB of RAM with nothing mapped, enough for CPU-only programs and tests.
+pub struct FlatBus {
+    pub memory: Vec<Byte>,
+}
+
+impl Default for FlatBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlatBus {
+    pub fn new() -> Self {
+        FlatBus { memory: vec![0; 0x10000] }
+    }
+
+    pub fn load(&mut self, addr: Address, data: &[Byte]) {
+        let start = addr as usize;
+        self.memory[start..start + data.len()].copy_from_slice(data);
+    }
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, addr: Address) -> Byte {
+        self.memory[addr as usize]
+    }
+
+    fn write(&mut self, addr: Address, val: Byte) {
+        self.memory[addr as usize] = val;
+    }
+
+    fn peek(&self, addr: Address) -> Byte {
+        self.memory[addr as usize]
+    }
+}
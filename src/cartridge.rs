@@ -7,12 +7,25 @@
 use std::fs::File;
 use std::io::Read;
 
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+use cartridge_header::CartridgeHeader;
+use cartridge_header::HeaderFormat;
+use cartridge_header::Timing;
+use cartridge_header::HEADER_SIZE;
+use crate::cartridge_header;
+
 pub struct Cartridge {
     prg_rom: Vec<u8>,
     chr_rom: Vec<u8>,
-    name_table_mirroring: u8,
-    mapper_number: u8,
-    extended_ram: bool
+    header: CartridgeHeader,
+}
+
+impl Default for Cartridge {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cartridge {
@@ -20,9 +33,7 @@ impl Cartridge {
         Cartridge {
             prg_rom: Vec::new(),
             chr_rom: Vec::new(),
-            name_table_mirroring: 0,
-            mapper_number: 0,
-            extended_ram: false
+            header: CartridgeHeader::default(),
         }
     }
 
@@ -34,53 +45,73 @@ impl Cartridge {
         &self.chr_rom
     }
 
+    pub fn get_header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    pub fn get_mapper(&self) -> u16 {
+        self.header.mapper
+    }
+
+    /// NES 2.0 submapper, 0 (unspecified) for iNES headers.
+    pub fn get_submapper(&self) -> u8 {
+        self.header.submapper
+    }
+
+    pub fn get_name_table_mirroring(&self) -> NameTableMirroring {
+        self.header.mirroring
+    }
+
+    /// iNES can't tell, so there only battery-backed PRG-RAM (bit 6.1) gets mapped.
     pub fn has_extended_ram(&self) -> bool {
-        self.extended_ram
+        match self.header.format {
+            HeaderFormat::INes => self.header.battery,
+            HeaderFormat::Nes2 => self.prg_ram_size() > 0,
+        }
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.header.battery
+    }
+
+    /// PRG-RAM and PRG-NVRAM together, in bytes; mappers bank them as one.
+    pub fn prg_ram_size(&self) -> usize {
+        self.header.prg_ram_size + self.header.prg_nvram_size
     }
 
     pub fn load_from_file(&mut self, path: &str) -> Result<(), String> {
         let mut rom_file = File::open(path).map_err(|e| format!("Could not open ROM file: {}", e))?;
-        let mut header = [0; 0x10];
+        let mut header = [0; HEADER_SIZE];
 
         rom_file.read_exact(&mut header).map_err(|e| format!("Reading iNES header failed: {}", e))?;
 
-        if &header[0..4] != b"NES\x1A" {
-            return Err("Not a valid iNES image.".to_string());
-        }
-
-        let prg_rom_banks = header[4] as u16;
-        println!("16KB PRG-ROM Banks: {}", prg_rom_banks);
-        if prg_rom_banks == 0 {
+        let header = CartridgeHeader::parse(&header)?;
+        println!("Header format: {:?}", header.format);
+        println!("PRG-ROM: {} bytes", header.prg_rom_size);
+        if header.prg_rom_size == 0 {
             return Err("ROM has no PRG-ROM banks. Loading ROM failed.".to_string());
         }
+        println!("CHR-ROM: {} bytes", header.chr_rom_size);
+        println!("Mapper number {}, submapper {}", header.mapper, header.submapper);
+        println!("Battery: {}", header.battery);
 
-        let chr_rom_banks = header[5] as u16;
-        println!("8KB PRG-ROM Banks: {}", chr_rom_banks);
-
-        self.name_table_mirroring = header[6] & 0xB;
-        self.mapper_number = ((header[6] >> 4) & 0xf) | (header[7] & 0xf0);
-        println!("Mapper number {}", self.mapper_number);
-        self.extended_ram = header[6] & 0x2 != 0;
-        println!("Extended (CPU) RAM: {}", self.extended_ram);
-        
-        if header[6] & 0x4 != 0 {
+        if header.trainer {
             return Err("Trainer is not supported.".to_string());
         }
 
-        if (header[0xA] & 0x3) == 0x2 || (header[0xA] & 0x1) != 0 {
-            return Err("PAL ROM not supported.".to_string());
+        match header.timing {
+            Timing::Pal => return Err("PAL ROM not supported.".to_string()),
+            Timing::Dendy => return Err("Dendy ROM not supported.".to_string()),
+            Timing::Ntsc | Timing::MultiRegion => println!("ROM is NTSC compatible."),
         }
 
-        println!("ROM is NTSC compatible.");
-
-        self.prg_rom.resize((0x4000 * prg_rom_banks) as usize, 0);
+        self.prg_rom.resize(header.prg_rom_size, 0);
         rom_file
             .read_exact(&mut self.prg_rom)
             .map_err(|e| format!("Reading PRG-ROM from image file failed: {}", e))?;
 
-
-        if chr_rom_banks > 0 {
-            self.chr_rom.resize((0x2000 * chr_rom_banks) as usize, 0);
+        if header.chr_rom_size > 0 {
+            self.chr_rom.resize(header.chr_rom_size, 0);
             rom_file
                 .read_exact(&mut self.chr_rom)
                 .map_err(|e| format!("Reading CHR-ROM from image file failed: {}", e))?;
@@ -88,6 +119,7 @@ impl Cartridge {
             println!("Cartridge with CHR-RAM.");
         }
 
+        self.header = header;
         Ok(())
     }
-}
\ No newline at end of file
+}
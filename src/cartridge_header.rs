@@ -0,0 +1,171 @@
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+pub const HEADER_SIZE: usize = 0x10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HeaderFormat {
+    INes,
+    Nes2,
+}
+
+/// The CPU/PPU timing the game was made for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    /// Runs on either NTSC or PAL consoles.
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConsoleType {
+    Nes,
+    /// Arcade board; the PPU and hardware types come from NES 2.0 byte 13, 0 for iNES.
+    VsSystem { ppu: u8, hardware: u8 },
+    PlayChoice10,
+    /// One of the NES 2.0 extended console types (byte 13), e.g. 3 for a Famiclone
+    /// with decimal mode.
+    Extended(u8),
+}
+
+/// The 16-byte header in front of an iNES or NES 2.0 image. Sizes are in bytes. iNES
+/// headers leave most of this out, so RAM sizes are the usual guesses: 8KB of PRG-RAM
+/// (battery-backed if bit 6.1 is set) and 8KB of CHR-RAM when there is no CHR-ROM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CartridgeHeader {
+    pub format: HeaderFormat,
+    /// 12 bits with NES 2.0, 8 with iNES.
+    pub mapper: u16,
+    /// 0 (unspecified) for iNES.
+    pub submapper: u8,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub mirroring: NameTableMirroring,
+    pub battery: bool,
+    pub trainer: bool,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    /// NES 2.0 default expansion device (byte 15), e.g. 1 for standard controllers; 0
+    /// (unspecified) for iNES.
+    pub expansion_device: u8,
+    /// ROMs stored after CHR-ROM, such as PlayChoice-10 INST-ROM.
+    pub misc_roms: u8,
+}
+
+impl Default for CartridgeHeader {
+    /// What an iNES header with every field 0 means.
+    fn default() -> Self {
+        Self::parse_ines(&[0; HEADER_SIZE], false, NameTableMirroring::Horizontal, false, false)
+    }
+}
+
+impl CartridgeHeader {
+    pub fn parse(header: &[u8; HEADER_SIZE]) -> Result<CartridgeHeader, String> {
+        if &header[0..4] != b"NES\x1A" {
+            return Err("Not a valid iNES image.".to_string());
+        }
+
+        let mirroring = if header[6] & 0x8 != 0 {
+            NameTableMirroring::FourScreen
+        } else if header[6] & 0x1 != 0 {
+            NameTableMirroring::Vertical
+        } else {
+            NameTableMirroring::Horizontal
+        };
+        let battery = header[6] & 0x2 != 0;
+        let trainer = header[6] & 0x4 != 0;
+
+        match header[7] & 0xc {
+            0x8 => Self::parse_nes2(header, mirroring, battery, trainer),
+            // Bits 2-3 set to 1 mean byte 7 onward holds garbage such as "DiskDude!"
+            0x4 => Ok(Self::parse_ines(header, true, mirroring, battery, trainer)),
+            _ => Ok(Self::parse_ines(header, false, mirroring, battery, trainer)),
+        }
+    }
+
+    fn parse_ines(header: &[u8; HEADER_SIZE], archaic: bool, mirroring: NameTableMirroring, battery: bool, trainer: bool) -> CartridgeHeader {
+        let (flags7, prg_ram_banks, flags9) = if archaic { (0, 0, 0) } else { (header[7], header[8], header[9]) };
+        let chr_rom_size = header[5] as usize * 0x2000;
+        let prg_ram_size = prg_ram_banks.max(1) as usize * 0x2000;
+        let console_type = match flags7 & 0x3 {
+            0x1 => ConsoleType::VsSystem { ppu: 0, hardware: 0 },
+            0x2 => ConsoleType::PlayChoice10,
+            _ => ConsoleType::Nes,
+        };
+        CartridgeHeader {
+            format: HeaderFormat::INes,
+            mapper: ((header[6] >> 4) | (flags7 & 0xf0)) as u16,
+            submapper: 0,
+            prg_rom_size: header[4] as usize * 0x4000,
+            chr_rom_size,
+            prg_ram_size: if battery { 0 } else { prg_ram_size },
+            prg_nvram_size: if battery { prg_ram_size } else { 0 },
+            chr_ram_size: if chr_rom_size == 0 { 0x2000 } else { 0 },
+            chr_nvram_size: 0,
+            mirroring,
+            battery,
+            trainer,
+            timing: if flags9 & 0x1 != 0 { Timing::Pal } else { Timing::Ntsc },
+            console_type,
+            expansion_device: 0,
+            misc_roms: 0,
+        }
+    }
+
+    fn parse_nes2(header: &[u8; HEADER_SIZE], mirroring: NameTableMirroring, battery: bool, trainer: bool) -> Result<CartridgeHeader, String> {
+        let console_type = match header[7] & 0x3 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem { ppu: header[13] & 0xf, hardware: header[13] >> 4 },
+            2 => ConsoleType::PlayChoice10,
+            _ => ConsoleType::Extended(header[13] & 0xf),
+        };
+        let timing = match header[12] & 0x3 {
+            0 => Timing::Ntsc,
+            1 => Timing::Pal,
+            2 => Timing::MultiRegion,
+            _ => Timing::Dendy,
+        };
+        Ok(CartridgeHeader {
+            format: HeaderFormat::Nes2,
+            mapper: ((header[6] >> 4) | (header[7] & 0xf0)) as u16 | ((header[8] & 0xf) as u16) << 8,
+            submapper: header[8] >> 4,
+            prg_rom_size: rom_size(header[4], header[9] & 0xf, 0x4000)?,
+            chr_rom_size: rom_size(header[5], header[9] >> 4, 0x2000)?,
+            prg_ram_size: ram_size(header[10] & 0xf),
+            prg_nvram_size: ram_size(header[10] >> 4),
+            chr_ram_size: ram_size(header[11] & 0xf),
+            chr_nvram_size: ram_size(header[11] >> 4),
+            mirroring,
+            battery,
+            trainer,
+            timing,
+            console_type,
+            expansion_device: header[15] & 0x3f,
+            misc_roms: header[14] & 0x3,
+        })
+    }
+}
+
+/// A NES 2.0 ROM size. An MSB nibble of $F switches the LSB byte to exponent-multiplier
+/// notation: 2^E * (MM * 2 + 1) bytes, with E in bits 2-7 and MM in bits 0-1.
+fn rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, String> {
+    if msb != 0xf {
+        return Ok(((msb as usize) << 8 | lsb as usize) * unit);
+    }
+    1usize
+        .checked_shl((lsb >> 2) as u32)
+        .and_then(|size| size.checked_mul((lsb & 0x3) as usize * 2 + 1))
+        .filter(|&size| size <= isize::MAX as usize)
+        .ok_or_else(|| "ROM size in the NES 2.0 header is too large.".to_string())
+}
+
+/// A NES 2.0 RAM size from its shift count: 64 << shift bytes, 0 for none.
+fn ram_size(shift: u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+}
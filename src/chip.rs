@@ -4,5 +4,5 @@
  * @LastEditors: mental1104 mental1104@gmail.com
  * @LastEditTime: 2023-10-29 11:46:05
  */
-pub(crate) type Byte = u8;
-pub(crate) type Address = u16;
+pub type Byte = u8;
+pub type Address = u16;
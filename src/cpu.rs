@@ -8,47 +8,102 @@ use chip::Byte;
 use chip::Address;
 use crate::chip;
 
+use cpu_opcodes::NMI_VECTOR;
 use cpu_opcodes::RESET_VECTOR;
-use cpu_opcodes::BranchOnFlag;
-use cpu_opcodes::BRANCH_INSTRUCTION_MASK;
-use cpu_opcodes::BRANCH_INSTRUCTION_MASK_RESULT;
-use cpu_opcodes::BRANCH_ON_FLAG_SHIFT;
-use cpu_opcodes::INSTRUCTION_MODE_MASK;
-use cpu_opcodes::OPERATION_MASK;
-use cpu_opcodes::OPERATION_SHIFT;
-use cpu_opcodes::ADDR_MODE_MASK;
-use cpu_opcodes::ADDR_MODE_SHIFT;
-use cpu_opcodes::OperationImplied;
-use cpu_opcodes::Operation1;
-use cpu_opcodes::Operation2;
-use cpu_opcodes::AddressingMode1;
-use cpu_opcodes::AddressingMode2;
+use cpu_opcodes::IRQ_VECTOR;
+use cpu_opcodes::INSTRUCTIONS;
+use cpu_opcodes::AddressingMode;
+use cpu_opcodes::MemoryAccess;
+use cpu_opcodes::Mnemonic;
+use cpu_opcodes::OpcodeInfo;
 use crate::cpu_opcodes;
 
 use main_bus::MainBus;
 use crate::main_bus;
- 
-pub struct CPU<'a> {
-    bus: &'a mut MainBus,
+
+use bus::Bus;
+use crate::bus;
+
+use status_flags::StatusFlags;
+use crate::status_flags;
+
+use crate::trace;
+
+use std::io::Write;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum InterruptType {
+    IRQ,
+    NMI,
+    BRK,
+}
+
+/// Cycles the CPU is halted for by an OAM DMA started by a write on `write_cycle`: a
+/// wait cycle, another one to align with the APU on odd cycles, then 256 reads and writes.
+fn dma_stall_cycles(write_cycle: u64) -> u32 {
+    513 + (write_cycle & 1) as u32
+}
+
+/// Devices that can pull the shared (wired-AND) IRQ line low.
+#[derive(Clone, Copy)]
+pub enum IrqSource {
+    FrameCounter = 1 << 0,
+    Dmc = 1 << 1,
+    Mapper = 1 << 2,
+    External = 1 << 3,
+}
+
+/// What the CPU does when it meets a KIL/JAM or an unstable unofficial opcode.
+/// Stable unofficial opcodes are always emulated.
+#[derive(Clone, Copy, PartialEq)]
+pub enum IllegalOpcodePolicy {
+    /// Stop the CPU until the next reset
+    Halt,
+    /// Leave PC on the opcode and make `step` report it
+    Error,
+    /// Jam on KIL like the real chip, run unstable opcodes with their common behaviour
+    Emulate,
+}
+
+pub struct CPU<B: Bus = MainBus> {
+    bus: B,
     pub r_a: u8,
     pub r_x: u8,
     pub r_y: u8,
     pub r_sp: u8,
     pub r_pc: Address,
     pub m_skip_cycles: u32,
-    pub m_cycles: u32,
+    pub m_cycles: u64,
     pub f_i: bool,
     pub f_c: bool,
     pub f_d: bool,
     pub f_n: bool,
     pub f_v: bool,
     pub f_z: bool,
+    m_pending_nmi: bool,
+    m_irq_lines: u32,
+    // I flag as seen by the interrupt poll at the end of the last instruction
+    m_irq_poll_inhibit: bool,
+    // interrupt sequence currently being burned through, for NMI hijacking
+    m_interrupt_in_progress: Option<InterruptType>,
+    m_illegal_opcode_policy: IllegalOpcodePolicy,
+    m_halted: bool,
+    m_tracer: Option<Box<dyn Write>>,
+    m_decimal_mode: bool,
+    m_cycle_accurate: bool,
+    // cycle-accurate mode: current opcode, the cycle of it to run next (0 fetches the
+    // next opcode) and the latches its micro-ops work with
+    m_opcode: Byte,
+    m_tstate: u8,
+    m_address: Address,
+    m_base: Address,
+    m_data: Byte,
 }
 
-impl<'a> CPU<'a> {
-    pub fn new(mem: &'a mut MainBus) -> Self {
+impl<B: Bus> CPU<B> {
+    pub fn new(bus: B) -> Self {
         CPU {
-            bus: mem,
+            bus,
             r_a: 0,
             r_x: 0,
             r_y: 0,
@@ -62,54 +117,310 @@ impl<'a> CPU<'a> {
             f_n: false,
             f_v: false,
             f_z: false,
+            m_pending_nmi: false,
+            m_irq_lines: 0,
+            m_irq_poll_inhibit: true,
+            m_interrupt_in_progress: None,
+            m_illegal_opcode_policy: IllegalOpcodePolicy::Emulate,
+            m_halted: false,
+            m_tracer: None,
+            m_decimal_mode: false,
+            m_cycle_accurate: false,
+            m_opcode: 0,
+            m_tstate: 0,
+            m_address: 0,
+            m_base: 0,
+            m_data: 0,
         }
     }
 
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// The status register as it is inside the CPU, without B and bit 5.
+    pub fn status(&self) -> StatusFlags {
+        let mut status = StatusFlags::empty();
+        status.set(StatusFlags::CARRY, self.f_c);
+        status.set(StatusFlags::ZERO, self.f_z);
+        status.set(StatusFlags::INTERRUPT_DISABLE, self.f_i);
+        status.set(StatusFlags::DECIMAL, self.f_d);
+        status.set(StatusFlags::OVERFLOW, self.f_v);
+        status.set(StatusFlags::NEGATIVE, self.f_n);
+        status
+    }
+
+    /// Loads the status register. B and bit 5 are ignored, like PLP does.
+    pub fn set_status(&mut self, status: StatusFlags) {
+        self.f_c = status.contains(StatusFlags::CARRY);
+        self.f_z = status.contains(StatusFlags::ZERO);
+        self.f_i = status.contains(StatusFlags::INTERRUPT_DISABLE);
+        self.f_d = status.contains(StatusFlags::DECIMAL);
+        self.f_v = status.contains(StatusFlags::OVERFLOW);
+        self.f_n = status.contains(StatusFlags::NEGATIVE);
+    }
+
     pub fn reset(&mut self) {
         let addr = self.read_address(RESET_VECTOR);
         self.reset_with_start_addr(addr);
     }
 
-    pub fn step(&mut self) {
+    pub fn set_illegal_opcode_policy(&mut self, policy: IllegalOpcodePolicy) {
+        self.m_illegal_opcode_policy = policy;
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.m_halted
+    }
+
+    /// The 2A03 has the BCD logic of the 6502 disconnected, so the D flag has no effect on
+    /// ADC/SBC. Enable this to run generic 6502 code such as Klaus Dormann's functional test.
+    pub fn set_decimal_mode(&mut self, enabled: bool) {
+        self.m_decimal_mode = enabled;
+    }
+
+    /// By default `step` runs a whole instruction on its first cycle and idles for the rest.
+    /// In cycle-accurate mode every cycle does its own bus access, including the dummy
+    /// reads of indexed addressing and the double write of read-modify-write instructions.
+    /// Interrupts are still polled between instructions.
+    pub fn set_cycle_accurate(&mut self, enabled: bool) {
+        self.m_cycle_accurate = enabled;
+    }
+
+    /// Logs every instruction in nestest.log format to `tracer` before it is executed.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Write>>) {
+        self.m_tracer = tracer;
+    }
+
+    /// Runs one CPU cycle, then lets the bus catch up with it.
+    pub fn step(&mut self) -> Result<(), String> {
+        let result = if self.m_cycle_accurate {
+            self.step_cycle()
+        } else {
+            self.step_instant()
+        };
+        self.bus.tick();
+        result
+    }
+
+    fn step_instant(&mut self) -> Result<(), String> {
         self.m_cycles += 1;
     
         if self.m_skip_cycles > 1 {
             self.m_skip_cycles -= 1;
-            return;
+            return Ok(());
         }
     
         self.m_skip_cycles = 0;
-        /* 生成程序状态字 */
-        /*
-        let psw = (self.f_N as u8) << 7 |
-                  (self.f_V as u8) << 6 |
-                  1 << 5 |
-                  (self.f_D as u8) << 3 |
-                  (self.f_I as u8) << 2 |
-                  (self.f_Z as u8) << 1 |
-                  (self.f_C as u8);
-        */
-        let opcode = self.bus.read(self.r_pc);
-        self.r_pc += 1;
-        let cycle_length = cpu_opcodes::OPERATION_CYCLES[opcode as usize];
-        if cycle_length != 0 && (self.execute_implied(opcode) || self.execute_branch(opcode)
-            || self.execute_type1(opcode) || self.execute_type2(opcode))
+        if self.m_halted {
+            return Ok(());
+        }
+        self.m_interrupt_in_progress = None;
+
+        if self.m_pending_nmi {
+            self.m_pending_nmi = false;
+            self.interrupt_sequence(InterruptType::NMI);
+            self.m_skip_cycles += 7;
+            return Ok(());
+        } else if self.m_irq_lines != 0 && !self.m_irq_poll_inhibit {
+            self.interrupt_sequence(InterruptType::IRQ);
+            self.m_skip_cycles += 7;
+            return Ok(());
+        }
+
+        let info = match self.fetch_opcode()? {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+
+        let prev_i = self.f_i;
+        let location = self.operand_address(info);
+        self.execute(info, location);
+        self.m_skip_cycles += info.cycles as u32;
+        if self.bus.take_dma_request() {
+            // The $4014 write is the last cycle of the instruction
+            let write_cycle = self.m_cycles + self.m_skip_cycles as u64 - 1;
+            self.m_skip_cycles += dma_stall_cycles(write_cycle);
+        }
+
+        // CLI, SEI and PLP change the I flag after the interrupt poll of their last cycle,
+        // so the instruction following them still sees the old value.
+        self.m_irq_poll_inhibit = match info.mnemonic {
+            Mnemonic::CLI | Mnemonic::SEI | Mnemonic::PLP => prev_i,
+            _ => self.f_i,
+        };
+        Ok(())
+    }
+
+    fn step_cycle(&mut self) -> Result<(), String> {
+        self.m_cycles += 1;
+
+        // Halted for OAM DMA
+        if self.m_skip_cycles > 0 {
+            self.m_skip_cycles -= 1;
+            return Ok(());
+        }
+        if self.m_tstate != 0 {
+            self.run_micro_op();
+            if self.bus.take_dma_request() {
+                self.m_skip_cycles = dma_stall_cycles(self.m_cycles);
+            }
+            return Ok(());
+        }
+        if self.m_halted {
+            return Ok(());
+        }
+        self.m_interrupt_in_progress = None;
+
+        let interrupt = if self.m_pending_nmi {
+            self.m_pending_nmi = false;
+            Some(InterruptType::NMI)
+        } else if self.m_irq_lines != 0 && !self.m_irq_poll_inhibit {
+            Some(InterruptType::IRQ)
+        } else {
+            None
+        };
+        if interrupt.is_some() {
+            // The opcode is fetched but replaced by BRK
+            self.bus.read(self.r_pc);
+            self.m_opcode = 0x00;
+            self.m_interrupt_in_progress = interrupt;
+            self.m_tstate = 1;
+            return Ok(());
+        }
+
+        let info = match self.fetch_opcode()? {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+        if info.mnemonic == Mnemonic::BRK {
+            self.m_interrupt_in_progress = Some(InterruptType::BRK);
+        }
+        // Kept by CLI, SEI and PLP, see `step_instant`
+        self.m_irq_poll_inhibit = self.f_i;
+        self.m_tstate = 1;
+        Ok(())
+    }
+
+    /// Traces and fetches the next opcode, applying the illegal opcode policy.
+    /// Returns `None` if the CPU halted on it.
+    fn fetch_opcode(&mut self) -> Result<Option<&'static OpcodeInfo>, String> {
+        if self.m_tracer.is_some() {
+            let line = trace::format_instruction(self, &self.bus);
+            if let Some(tracer) = self.m_tracer.as_mut() {
+                writeln!(tracer, "{}", line).map_err(|e| format!("Writing trace failed: {}", e))?;
+            }
+        }
+
+        let opcode = self.bus.fetch(self.r_pc);
+        self.r_pc = self.r_pc.wrapping_add(1);
+        self.m_opcode = opcode;
+        let info = &INSTRUCTIONS[opcode as usize];
+
+        let jam = info.mnemonic == Mnemonic::KIL;
+        if jam || info.mnemonic.is_unstable() {
+            match self.m_illegal_opcode_policy {
+                IllegalOpcodePolicy::Error => {
+                    self.r_pc = self.r_pc.wrapping_sub(1);
+                    return Err(format!("Illegal opcode 0x{:02X} at 0x{:04X}", opcode, self.r_pc));
+                }
+                IllegalOpcodePolicy::Emulate if !jam => (),
+                _ => {
+                    self.r_pc = self.r_pc.wrapping_sub(1);
+                    self.m_halted = true;
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(info))
+    }
+
+    /// Latches an NMI edge; it is serviced before the next instruction.
+    pub fn trigger_nmi(&mut self) {
+        if self.m_cycle_accurate {
+            // Hijacking happens on its own when the vector is fetched
+            self.m_pending_nmi = true;
+            return;
+        }
+        match self.m_interrupt_in_progress {
+            // An NMI arriving before the vector fetch of an IRQ/BRK sequence hijacks it:
+            // the stacked state is kept but execution continues at the NMI handler.
+            Some(InterruptType::IRQ) | Some(InterruptType::BRK) if self.m_skip_cycles >= 4 => {
+                self.m_interrupt_in_progress = Some(InterruptType::NMI);
+                self.r_pc = self.read_address(NMI_VECTOR);
+            }
+            _ => self.m_pending_nmi = true,
+        }
+    }
+
+    /// Asserts or releases the IRQ line on behalf of `source`. The line stays low
+    /// for as long as at least one source holds it.
+    pub fn set_irq(&mut self, source: IrqSource, asserted: bool) {
+        if asserted {
+            self.m_irq_lines |= source as u32;
+        } else {
+            self.m_irq_lines &= !(source as u32);
+        }
+    }
+
+    pub fn irq_line(&self) -> bool {
+        self.m_irq_lines != 0
+    }
+
+    fn interrupt_sequence(&mut self, interrupt_type: InterruptType) {
+        // BRK skips its padding byte, hardware interrupts return to the interrupted instruction
+        let return_addr = if interrupt_type == InterruptType::BRK {
+            self.r_pc.wrapping_add(1)
+        } else {
+            self.r_pc
+        };
+        self.push_stack((return_addr >> 8) as u8);
+        self.push_stack(return_addr as u8);
+
+        self.push_stack(self.status().to_stack_byte(interrupt_type == InterruptType::BRK));
+        self.f_i = true;
+
+        // A pending NMI hijacks a BRK that is being executed at the same time
+        let vector = if interrupt_type == InterruptType::NMI
+            || (interrupt_type == InterruptType::BRK && self.m_pending_nmi)
         {
-            self.m_skip_cycles += cycle_length;
+            self.m_pending_nmi = false;
+            NMI_VECTOR
+        } else {
+            IRQ_VECTOR
+        };
+        self.r_pc = self.read_address(vector);
+        self.m_interrupt_in_progress = Some(interrupt_type);
+    }
+
+    /// Steps until the current instruction (or interrupt sequence) has used up all of its cycles.
+    pub fn step_instruction(&mut self) -> Result<(), String> {
+        self.step()?;
+        if self.m_cycle_accurate {
+            while self.m_tstate != 0 || self.m_skip_cycles > 0 {
+                self.step()?;
+            }
         } else {
-            eprintln!("Unrecognized opcode: 0x{:02X}", opcode);
+            while self.m_skip_cycles > 1 {
+                self.step()?;
+            }
         }
+        Ok(())
     }
 
     pub fn read_address(&mut self, addr: Address) -> Address {
         let low_byte = self.bus.read(addr) as u16;
-        let high_byte = (self.bus.read(addr + 1) as u16) << 8;
+        let high_byte = (self.bus.read(addr.wrapping_add(1)) as u16) << 8;
         low_byte | high_byte
     }
 
     pub fn reset_with_start_addr(&mut self, start_addr: Address) {
         self.m_skip_cycles = 0;
-        self.m_cycles = 0;
+        self.m_cycles = 7; // the reset sequence takes 7 cycles
         self.r_a = 0;
         self.r_x = 0;
         self.r_y = 0;
@@ -121,6 +432,11 @@ impl<'a> CPU<'a> {
         self.f_z = false;
         self.r_pc = start_addr;
         self.r_sp = 0xfd; // documented startup state
+        self.m_pending_nmi = false;
+        self.m_irq_poll_inhibit = true;
+        self.m_interrupt_in_progress = None;
+        self.m_halted = false;
+        self.m_tstate = 0;
     }
 
     pub fn push_stack(&mut self, val: Byte) {
@@ -145,382 +461,754 @@ impl<'a> CPU<'a> {
         }
     }
 
-    pub fn execute_implied(&mut self, opcode: Byte) -> bool {
-        match OperationImplied::from(opcode) {
-            OperationImplied::NOP => (),
-            OperationImplied::JSR => {
+    fn add_with_carry(&mut self, operand: Byte) {
+        let sum = self.r_a as u16 + operand as u16 + (self.f_c as u16);
+        self.f_v = (self.r_a ^ sum as u8) & (operand ^ sum as u8) & 0x80 != 0;
+        if self.f_d && self.m_decimal_mode {
+            // NMOS behaviour: Z comes from the binary sum, N and V from the half-adjusted one
+            let mut low = (self.r_a & 0xf) as u16 + (operand & 0xf) as u16 + self.f_c as u16;
+            if low >= 0xa {
+                low = ((low + 0x6) & 0xf) + 0x10;
+            }
+            let mut result = (self.r_a & 0xf0) as u16 + (operand & 0xf0) as u16 + low;
+            self.f_z = sum as u8 == 0;
+            self.f_n = (result & 0x80) != 0;
+            self.f_v = (self.r_a ^ result as u8) & (operand ^ result as u8) & 0x80 != 0;
+            if result >= 0xa0 {
+                result += 0x60;
+            }
+            self.f_c = result >= 0x100;
+            self.r_a = result as u8;
+            return;
+        }
+        self.f_c = (sum & 0x100) != 0;
+        self.r_a = sum as u8;
+        self.set_zn(self.r_a);
+    }
+
+    fn subtract_with_borrow(&mut self, operand: Byte) {
+        let a = self.r_a;
+        let borrow = !self.f_c as i16;
+        // A - M - !C is A + !M + C in two's complement, the flags are always the binary ones
+        let diff = a as u16 + !operand as u16 + (self.f_c as u16);
+        self.f_c = (diff & 0x100) != 0;
+        self.f_v = (a ^ diff as u8) & (!operand ^ diff as u8) & 0x80 != 0;
+        self.r_a = diff as u8;
+        self.set_zn(self.r_a);
+        if self.f_d && self.m_decimal_mode {
+            let mut low = (a & 0xf) as i16 - (operand & 0xf) as i16 - borrow;
+            if low < 0 {
+                low = ((low - 0x6) & 0xf) - 0x10;
+            }
+            let mut result = (a & 0xf0) as i16 - (operand & 0xf0) as i16 + low;
+            if result < 0 {
+                result -= 0x60;
+            }
+            self.r_a = result as u8;
+        }
+    }
+
+    fn compare(&mut self, register: Byte, operand: Byte) {
+        self.f_c = register >= operand;
+        self.set_zn(register.wrapping_sub(operand));
+    }
+
+    /// Resolves the effective address of the operand and moves PC past it. For branches
+    /// this is the branch target, for implied and accumulator instructions it is unused.
+    fn operand_address(&mut self, info: &OpcodeInfo) -> Address {
+        match info.mode {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Immediate => {
+                let location = self.r_pc;
+                self.r_pc = self.r_pc.wrapping_add(1);
+                location
+            }
+            AddressingMode::ZeroPage => {
+                let location = self.bus.read(self.r_pc) as Address;
+                self.r_pc = self.r_pc.wrapping_add(1);
+                location
+            }
+            AddressingMode::ZeroPageX => {
+                // Address wraps around in the zero page
+                let location = self.bus.read(self.r_pc).wrapping_add(self.r_x) as Address;
+                self.r_pc = self.r_pc.wrapping_add(1);
+                location
+            }
+            AddressingMode::ZeroPageY => {
+                let location = self.bus.read(self.r_pc).wrapping_add(self.r_y) as Address;
+                self.r_pc = self.r_pc.wrapping_add(1);
+                location
+            }
+            AddressingMode::Absolute => {
+                let location = self.read_address(self.r_pc);
+                self.r_pc = self.r_pc.wrapping_add(2);
+                location
+            }
+            AddressingMode::AbsoluteX => {
+                let base = self.read_address(self.r_pc);
+                self.r_pc = self.r_pc.wrapping_add(2);
+                self.index(base, self.r_x, info.page_penalty)
+            }
+            AddressingMode::AbsoluteY => {
+                let base = self.read_address(self.r_pc);
+                self.r_pc = self.r_pc.wrapping_add(2);
+                self.index(base, self.r_y, info.page_penalty)
+            }
+            AddressingMode::Indirect => {
+                let location = self.read_address(self.r_pc);
+                self.r_pc = self.r_pc.wrapping_add(2);
+                // 6502 has a bug such that when the vector of an indirect address begins at the last byte of a page,
+                // the second byte is fetched from the beginning of that page rather than the beginning of the next
+                // Recreating here:
+                let page = location & 0xff00;
+                self.bus.read(location) as Address |
+                    (self.bus.read(page | (location.wrapping_add(1) & 0xff)) as Address) << 8
+            }
+            AddressingMode::IndirectX => {
+                // Pointer lookups wrap around in the zero page
+                let zero_addr = self.r_x.wrapping_add(self.bus.read(self.r_pc));
+                self.r_pc = self.r_pc.wrapping_add(1);
+                self.read_address_zero_page(zero_addr)
+            }
+            AddressingMode::IndirectY => {
+                let zero_addr = self.bus.read(self.r_pc);
+                self.r_pc = self.r_pc.wrapping_add(1);
+                let base = self.read_address_zero_page(zero_addr);
+                self.index(base, self.r_y, info.page_penalty)
+            }
+            AddressingMode::Relative => {
+                let offset = self.bus.read(self.r_pc) as i8;
+                self.r_pc = self.r_pc.wrapping_add(1);
+                self.r_pc.wrapping_add(offset as Address)
+            }
+        }
+    }
+
+    fn index(&mut self, base: Address, index: Byte, page_penalty: bool) -> Address {
+        let location = base.wrapping_add(index.into());
+        if page_penalty {
+            self.set_page_crossed(base, location, 1);
+        }
+        location
+    }
+
+    fn read_address_zero_page(&mut self, zero_addr: Byte) -> Address {
+        self.bus.read(zero_addr as Address) as Address
+            | (self.bus.read(zero_addr.wrapping_add(1) as Address) as Address) << 8
+    }
+
+    fn execute(&mut self, info: &OpcodeInfo, location: Address) {
+        match info.mnemonic {
+            Mnemonic::BRK => {
+                self.interrupt_sequence(InterruptType::BRK);
+            }
+            Mnemonic::JSR => {
                 // Jump to new location, saving Return Address
-                // Push address of next instruction - 1, thus r_PC + 1 instead of r_PC + 2
-                // since r_PC and r_PC + 1 are the address of the subroutine
-                self.push_stack(((self.r_pc + 1) >> 8) as u8);
-                self.push_stack((self.r_pc + 1) as u8);
-                self.r_pc = self.read_address(self.r_pc);
-            },
-            OperationImplied::RTS => {
+                // Push address of next instruction - 1, which is the last byte of the JSR
+                let return_addr = self.r_pc.wrapping_sub(1);
+                self.push_stack((return_addr >> 8) as u8);
+                self.push_stack(return_addr as u8);
+                self.r_pc = location;
+            }
+            Mnemonic::RTS => {
                 // Return from Subroutine
                 self.r_pc = self.pull_stack() as Address;
                 self.r_pc |= (self.pull_stack() as Address) << 8;
-                self.r_pc += 1;
-            },
-            OperationImplied::RTI => {
-                let flags = self.pull_stack() as u8;
-                self.f_n = (flags & 0x80) != 0;
-                self.f_v = (flags & 0x40) != 0;
-                self.f_d = (flags & 0x8) != 0;
-                self.f_i = (flags & 0x4) != 0;
-                self.f_z = (flags & 0x2) != 0;
-                self.f_c = (flags & 0x1) != 0;
+                self.r_pc = self.r_pc.wrapping_add(1);
+            }
+            Mnemonic::RTI => {
+                let flags = self.pull_stack();
+                self.set_status(StatusFlags::from_stack_byte(flags));
                 self.r_pc = self.pull_stack() as Address;
                 self.r_pc |= (self.pull_stack() as Address) << 8;
-            },
-            OperationImplied::JMP => {
-                self.r_pc = self.read_address(self.r_pc);
-            },
-            OperationImplied::JMPI => {
-                let location = self.read_address(self.r_pc);
-                // 6502 has a bug such that when the vector of an indirect address begins at the last byte of a page,
-                // the second byte is fetched from the beginning of that page rather than the beginning of the next
-                // Recreating here:
-                let page = location & 0xff00;
-                self.r_pc = self.bus.read(location) as Address | 
-                            (self.bus.read(page | ((location + 1) & 0xff)) as Address) << 8;
-            },
-            OperationImplied::PHP => {
-                let flags = (self.f_n as u8) << 7 |
-                            (self.f_v as u8) << 6 |
-                            1 << 5 | // supposed to always be 1
-                            1 << 4 | // PHP pushes with the B flag as 1, no matter what
-                            (self.f_d as u8) << 3 |
-                            (self.f_i as u8) << 2 |
-                            (self.f_z as u8) << 1 |
-                            (self.f_c as u8);
-                self.push_stack(flags);
-            },
-            OperationImplied::PLP => {
-                let flags = self.pull_stack() as u8;
-                self.f_n = (flags & 0x80) != 0;
-                self.f_v = (flags & 0x40) != 0;
-                self.f_d = (flags & 0x8) != 0;
-                self.f_i = (flags & 0x4) != 0;
-                self.f_z = (flags & 0x2) != 0;
-                self.f_c = (flags & 0x1) != 0;
-            },
-            OperationImplied::PHA => {
+            }
+            Mnemonic::JMP => {
+                self.r_pc = location;
+            }
+            Mnemonic::PHP => {
+                self.push_stack(self.status().to_stack_byte(true));
+            }
+            Mnemonic::PLP => {
+                let flags = self.pull_stack();
+                self.set_status(StatusFlags::from_stack_byte(flags));
+            }
+            Mnemonic::PHA => {
                 self.push_stack(self.r_a);
-            },
-            OperationImplied::PLA => {
-                self.r_a = self.pull_stack() as Byte;
+            }
+            Mnemonic::PLA => {
+                self.r_a = self.pull_stack();
                 self.set_zn(self.r_a);
+            }
+            Mnemonic::BPL | Mnemonic::BMI | Mnemonic::BVC | Mnemonic::BVS
+                | Mnemonic::BCC | Mnemonic::BCS | Mnemonic::BNE | Mnemonic::BEQ => {
+                if self.branch_taken(info.mnemonic) {
+                    self.m_skip_cycles += 1;
+                    self.set_page_crossed(self.r_pc, location, 1);
+                    self.r_pc = location;
+                }
+            }
+            _ => match info.access {
+                MemoryAccess::None => self.implied_operation(info.mnemonic),
+                MemoryAccess::Read => {
+                    let operand = self.bus.read(location);
+                    self.read_operation(info.mnemonic, operand);
+                }
+                MemoryAccess::Write => self.write_operation(info.mnemonic, location),
+                MemoryAccess::ReadModifyWrite => {
+                    let operand = self.bus.read(location);
+                    let result = self.modify_operation(info.mnemonic, operand);
+                    self.bus.write(location, result);
+                }
             },
-            OperationImplied::DEY => {
+        }
+    }
+
+    /// Instructions without a memory operand, including the accumulator forms of the shifts.
+    fn implied_operation(&mut self, mnemonic: Mnemonic) {
+        match mnemonic {
+            Mnemonic::DEY => {
                 self.r_y = self.r_y.wrapping_sub(1);
                 self.set_zn(self.r_y);
-            },
-            OperationImplied::DEX => {
+            }
+            Mnemonic::DEX => {
                 self.r_x = self.r_x.wrapping_sub(1);
                 self.set_zn(self.r_x);
-            },
-            OperationImplied::TAY => {
+            }
+            Mnemonic::TAY => {
                 self.r_y = self.r_a;
                 self.set_zn(self.r_y);
-            },
-            OperationImplied::INY => {
+            }
+            Mnemonic::INY => {
                 self.r_y = self.r_y.wrapping_add(1);
                 self.set_zn(self.r_y);
-            },
-            OperationImplied::INX => {
+            }
+            Mnemonic::INX => {
                 self.r_x = self.r_x.wrapping_add(1);
                 self.set_zn(self.r_x);
-            },
-            OperationImplied::CLC => {
-                self.f_c = false;
-            },
-            OperationImplied::SEC => {
-                self.f_c = true;
-            },
-            OperationImplied::CLI => {
-                self.f_i = false;
-            },
-            OperationImplied::SEI => {
-                self.f_i = true;
-            },
-            OperationImplied::CLD => {
-                self.f_d = false;
-            },
-            OperationImplied::SED => {
-                self.f_d = true;
-            },
-            OperationImplied::TYA => {
+            }
+            Mnemonic::CLC => self.f_c = false,
+            Mnemonic::SEC => self.f_c = true,
+            Mnemonic::CLI => self.f_i = false,
+            Mnemonic::SEI => self.f_i = true,
+            Mnemonic::CLD => self.f_d = false,
+            Mnemonic::SED => self.f_d = true,
+            Mnemonic::CLV => self.f_v = false,
+            Mnemonic::TYA => {
                 self.r_a = self.r_y;
                 self.set_zn(self.r_a);
-            },
-            OperationImplied::CLV => {
-                self.f_v = false;
-            },
-            OperationImplied::TXA => {
+            }
+            Mnemonic::TXA => {
                 self.r_a = self.r_x;
                 self.set_zn(self.r_a);
-            },
-            OperationImplied::TXS => {
+            }
+            Mnemonic::TXS => {
                 self.r_sp = self.r_x;
-            },
-            OperationImplied::TAX => {
+            }
+            Mnemonic::TAX => {
                 self.r_x = self.r_a;
                 self.set_zn(self.r_x);
-            },
-            OperationImplied::TSX => {
+            }
+            Mnemonic::TSX => {
                 self.r_x = self.r_sp;
                 self.set_zn(self.r_x);
-            },
-            OperationImplied::Unknown => {
-                return false;
             }
+            Mnemonic::ASL | Mnemonic::ROL | Mnemonic::LSR | Mnemonic::ROR => {
+                self.r_a = self.modify_operation(mnemonic, self.r_a);
+            }
+            Mnemonic::NOP => (),
+            _ => unreachable!("{:?} has a memory operand", mnemonic),
         }
-        true
     }
-    
-    pub(crate) fn execute_branch(&mut self, opcode: u8) -> bool {
-        if (opcode & BRANCH_INSTRUCTION_MASK) == BRANCH_INSTRUCTION_MASK_RESULT {
-            // branch is initialized to the condition required (for the flag specified later)
-            let branch = opcode & BRANCH_INSTRUCTION_MASK;
-            let branch_flag: bool;
-            // set branch to true if the given condition is met by the given flag
-            // We use xnor here, it is true if either both operands are true or false
-            match BranchOnFlag::from(opcode >> BRANCH_ON_FLAG_SHIFT) {
-                //BranchOnFlag::Negative => branch = !(branch ^ self.f_n)
-                BranchOnFlag::Negative => {
-                    let flag_n = self.f_n as u8;
-                    branch_flag = !(branch ^ flag_n != 0);
-                }
-                BranchOnFlag::Overflow => {
-                    let flag_v = self.f_v as u8;
-                    branch_flag = !(branch ^ flag_v != 0);
-                }
-                BranchOnFlag::Carry => {
-                    let flag_c = self.f_c as u8;
-                    branch_flag = !(branch ^ flag_c != 0);
-                }
-                BranchOnFlag::Zero => {
-                    let flag_z = self.f_z as u8;
-                    branch_flag = !(branch ^ flag_z != 0);
-                }
+
+    fn read_operation(&mut self, mnemonic: Mnemonic, operand: Byte) {
+        match mnemonic {
+            Mnemonic::ORA => {
+                self.r_a |= operand;
+                self.set_zn(self.r_a);
             }
-    
-            if branch_flag {
-                let offset = self.bus.read(self.r_pc as Address) as i8;
-                self.m_skip_cycles += 1;
-                let new_pc: u16 = (self.r_pc as i32 + offset as i32) as Address;
-                self.set_page_crossed(self.r_pc, new_pc, 2);
-                self.r_pc = new_pc;
-            } else {
-                self.r_pc += 1;
-            }
-            return true;
+            Mnemonic::AND => {
+                self.r_a &= operand;
+                self.set_zn(self.r_a);
+            }
+            Mnemonic::EOR => {
+                self.r_a ^= operand;
+                self.set_zn(self.r_a);
+            }
+            Mnemonic::ADC => self.add_with_carry(operand),
+            Mnemonic::SBC => self.subtract_with_borrow(operand),
+            Mnemonic::CMP => self.compare(self.r_a, operand),
+            Mnemonic::CPX => self.compare(self.r_x, operand),
+            Mnemonic::CPY => self.compare(self.r_y, operand),
+            Mnemonic::BIT => {
+                self.f_z = (self.r_a & operand) == 0;
+                self.f_v = (operand & 0x40) != 0;
+                self.f_n = (operand & 0x80) != 0;
+            }
+            Mnemonic::LDA => {
+                self.r_a = operand;
+                self.set_zn(self.r_a);
+            }
+            Mnemonic::LDX => {
+                self.r_x = operand;
+                self.set_zn(self.r_x);
+            }
+            Mnemonic::LDY => {
+                self.r_y = operand;
+                self.set_zn(self.r_y);
+            }
+            Mnemonic::LAX => {
+                self.r_a = operand;
+                self.r_x = operand;
+                self.set_zn(self.r_a);
+            }
+            Mnemonic::ANC => {
+                self.r_a &= operand;
+                self.set_zn(self.r_a);
+                self.f_c = self.f_n;
+            }
+            Mnemonic::ALR => {
+                self.r_a &= operand;
+                self.r_a = self.shift_right(self.r_a);
+            }
+            Mnemonic::ARR => {
+                self.r_a &= operand;
+                self.r_a = self.r_a >> 1 | (self.f_c as Byte) << 7;
+                self.set_zn(self.r_a);
+                self.f_c = (self.r_a & 0x40) != 0;
+                self.f_v = ((self.r_a >> 6) ^ (self.r_a >> 5)) & 1 != 0;
+            }
+            Mnemonic::XAA => {
+                // The magic constant depends on the chip, 0xee is the most common value
+                self.r_a = (self.r_a | 0xee) & self.r_x & operand;
+                self.set_zn(self.r_a);
+            }
+            Mnemonic::LXA => {
+                self.r_a = (self.r_a | 0xee) & operand;
+                self.r_x = self.r_a;
+                self.set_zn(self.r_a);
+            }
+            Mnemonic::AXS => {
+                let masked = self.r_a & self.r_x;
+                self.f_c = masked >= operand;
+                self.r_x = masked.wrapping_sub(operand);
+                self.set_zn(self.r_x);
+            }
+            Mnemonic::LAS => {
+                let value = operand & self.r_sp;
+                self.r_a = value;
+                self.r_x = value;
+                self.r_sp = value;
+                self.set_zn(value);
+            }
+            // The unofficial forms with an operand still read it
+            Mnemonic::NOP => (),
+            _ => unreachable!("{:?} doesn't read memory", mnemonic),
         }
-        false
     }
 
-    fn execute_type1(&mut self, opcode: u8) -> bool {
-        if (opcode & INSTRUCTION_MODE_MASK) == 0x1 {
-            let mut location: Address;
-            let op = Operation1::from(((opcode & OPERATION_MASK) >> OPERATION_SHIFT) as u8);
-            let addressing_mode = AddressingMode1::from(((opcode & ADDR_MODE_MASK) >> ADDR_MODE_SHIFT) as u8);
-    
-            match addressing_mode {
-                AddressingMode1::IndexedIndirectX => {
-                    let zero_addr = self.r_x.wrapping_add(self.bus.read(self.r_pc));
-                    let read_addr1: Address = (zero_addr & 0xFF) as Address;
-                    let read_addr2: Address = ((zero_addr + 1) & 0xFF) as Address;
-                    location = (self.bus.read(read_addr1) as Address)
-                        | ((self.bus.read(read_addr2) as Address) << 8);
-                    self.r_pc += 1;
-                }
-                AddressingMode1::ZeroPage => {
-                    location = self.bus.read(self.r_pc) as Address;
-                    self.r_pc += 1;
-                }
-                AddressingMode1::Immediate => {
-                    location = self.r_pc;
-                    self.r_pc += 1;
-                }
-                AddressingMode1::Absolute => {
-                    location = self.read_address(self.r_pc);
-                    self.r_pc += 2;
-                }
-                AddressingMode1::IndirectY => {
-                    let zero_addr = self.bus.read(self.r_pc);
-                    let read_addr1: Address = (zero_addr & 0xFF) as Address;
-                    let mut read_addr2: Address = ((zero_addr + 1) & 0xFF) as Address;
-                    read_addr2 = read_addr2 << 8;
-                    location = (self.bus.read(read_addr1) as Address)
-                        | ((self.bus.read(read_addr2) as Address) << 8);
-                    if op != Operation1::STA {
-                        self.set_page_crossed(location, location.wrapping_add(self.r_y.into()), 1);
-                    }
-                    location = location.wrapping_add(self.r_y.into());
-                    self.r_pc += 1;
-                }
-                AddressingMode1::IndexedX => {
-                    let zero_addr = (self.bus.read(self.r_pc) as u16)
-                        .wrapping_add(self.r_x as u16);
-                    location = zero_addr as Address & 0xFF;
-                    self.r_pc += 1;
-                }
-                AddressingMode1::AbsoluteY => {
-                    location = self.read_address(self.r_pc);
-                    self.r_pc += 2;
-                    if op != Operation1::STA {
-                        self.set_page_crossed(location, location.wrapping_add(self.r_y.into()), 1);
-                    }
-                    location = location.wrapping_add(self.r_y.into());
-                }
-                AddressingMode1::AbsoluteX => {
-                    location = self.read_address(self.r_pc);
-                    self.r_pc += 2;
-                    if op != Operation1::STA {
-                        self.set_page_crossed(location, location.wrapping_add(self.r_x.into()), 1);
-                    }
-                    location = location.wrapping_add(self.r_x.into());
-                }
+    fn write_operation(&mut self, mnemonic: Mnemonic, location: Address) {
+        match mnemonic {
+            Mnemonic::STA => self.bus.write(location, self.r_a),
+            Mnemonic::STX => self.bus.write(location, self.r_x),
+            Mnemonic::STY => self.bus.write(location, self.r_y),
+            Mnemonic::SAX => self.bus.write(location, self.r_a & self.r_x),
+            Mnemonic::AHX => self.store_and_high(location, self.r_y, self.r_a & self.r_x),
+            Mnemonic::TAS => {
+                self.r_sp = self.r_a & self.r_x;
+                self.store_and_high(location, self.r_y, self.r_sp);
             }
-    
-            match op {
-                Operation1::ORA => {
-                    self.r_a |= self.bus.read(location);
-                    self.set_zn(self.r_a);
-                }
-                Operation1::AND => {
-                    self.r_a &= self.bus.read(location);
-                    self.set_zn(self.r_a);
-                }
-                Operation1::EOR => {
-                    self.r_a ^= self.bus.read(location);
-                    self.set_zn(self.r_a);
-                }
-                Operation1::ADC => {
-                    let operand = self.bus.read(location);
-                    let sum = self.r_a as u16 + operand as u16 + (self.f_c as u16);
-                    self.f_c = (sum & 0x100) != 0;
-                    self.f_v = (self.r_a ^ sum as u8) & (operand ^ sum as u8) & 0x80 != 0;
-                    self.r_a = sum as u8;
-                    self.set_zn(self.r_a);
-                }
-                Operation1::STA => {
-                    self.bus.write(location, self.r_a);
-                }
-                Operation1::LDA => {
-                    self.r_a = self.bus.read(location);
-                    self.set_zn(self.r_a);
-                }
-                Operation1::SBC => {
-                    let subtrahend = self.bus.read(location);
-                    let diff = self.r_a as i16 - subtrahend as i16 - !(self.f_c as i16);
-                    self.f_c = !(diff & 0x100 != 0);
-                    self.f_v = (self.r_a ^ diff as u8) & (!subtrahend ^ diff as u8) & 0x80 != 0;
-                    self.r_a = diff as u8;
-                    self.set_zn(diff as u8);
-                }
-                Operation1::CMP => {
-                    let diff = self.r_a as i16 - self.bus.read(location) as i16;
-                    self.f_c = !(diff & 0x100 != 0);
-                    self.set_zn(diff as u8);
-                }
+            Mnemonic::SHY => self.store_and_high(location, self.r_x, self.r_y),
+            Mnemonic::SHX => self.store_and_high(location, self.r_y, self.r_x),
+            _ => unreachable!("{:?} doesn't write memory", mnemonic),
+        }
+    }
+
+    /// Computes the value a read-modify-write instruction writes back. The combined
+    /// unofficial ones also update A here.
+    fn modify_operation(&mut self, mnemonic: Mnemonic, operand: Byte) -> Byte {
+        match mnemonic {
+            Mnemonic::ASL => self.shift_left(operand),
+            Mnemonic::ROL => self.rotate_left(operand),
+            Mnemonic::LSR => self.shift_right(operand),
+            Mnemonic::ROR => self.rotate_right(operand),
+            Mnemonic::INC => self.increment(operand),
+            Mnemonic::DEC => self.decrement(operand),
+            Mnemonic::SLO => {
+                let result = self.shift_left(operand);
+                self.r_a |= result;
+                self.set_zn(self.r_a);
+                result
+            }
+            Mnemonic::RLA => {
+                let result = self.rotate_left(operand);
+                self.r_a &= result;
+                self.set_zn(self.r_a);
+                result
             }
-            return true;
+            Mnemonic::SRE => {
+                let result = self.shift_right(operand);
+                self.r_a ^= result;
+                self.set_zn(self.r_a);
+                result
+            }
+            Mnemonic::RRA => {
+                let result = self.rotate_right(operand);
+                self.add_with_carry(result);
+                result
+            }
+            Mnemonic::DCP => {
+                let result = self.decrement(operand);
+                self.compare(self.r_a, result);
+                result
+            }
+            Mnemonic::ISC => {
+                let result = self.increment(operand);
+                self.subtract_with_borrow(result);
+                result
+            }
+            _ => unreachable!("{:?} isn't a read-modify-write instruction", mnemonic),
         }
-        false
     }
 
-    pub fn execute_type2(&mut self, opcode: u8) -> bool {
-        if (opcode & INSTRUCTION_MODE_MASK) == 2 {
-            let mut location: Address = 0;
-            let op = Operation2::from((opcode & OPERATION_MASK) >> OPERATION_SHIFT);
-            let addr_mode =
-                AddressingMode2::from((opcode & ADDR_MODE_MASK) >> ADDR_MODE_SHIFT);
-            match addr_mode {
-                AddressingMode2::Immediate_ => location = self.r_pc,
-                AddressingMode2::ZeroPage_ => location = self.bus.read(self.r_pc) as Address,
-                AddressingMode2::Accumulator => {}
-                AddressingMode2::Absolute_ => {
-                    location = self.read_address(self.r_pc);
-                }
-                AddressingMode2::Indexed => {
-                    location = self.bus.read(self.r_pc) as Address;
-                    let index: Byte = if op == Operation2::LDX || op == Operation2::STX {
-                        self.r_y
-                    } else {
-                        self.r_x
-                    };
-                    location = (location.wrapping_add(index.into()) & 0xFF) as Address;
-                }
-                AddressingMode2::AbsoluteIndexed => {
-                    location = self.read_address(self.r_pc);
-                    let index: Byte = if op == Operation2::LDX || op == Operation2::STX {
-                        self.r_y
-                    } else {
-                        self.r_x
-                    };
-                    self.set_page_crossed(location, location.wrapping_add(index.into()), 1);
-                    location = location.wrapping_add(index.into());
-                }
+    fn branch_taken(&self, mnemonic: Mnemonic) -> bool {
+        match mnemonic {
+            Mnemonic::BPL => !self.f_n,
+            Mnemonic::BMI => self.f_n,
+            Mnemonic::BVC => !self.f_v,
+            Mnemonic::BVS => self.f_v,
+            Mnemonic::BCC => !self.f_c,
+            Mnemonic::BCS => self.f_c,
+            Mnemonic::BNE => !self.f_z,
+            Mnemonic::BEQ => self.f_z,
+            _ => unreachable!("{:?} isn't a branch", mnemonic),
+        }
+    }
+
+    fn shift_left(&mut self, operand: Byte) -> Byte {
+        self.f_c = (operand & 0x80) != 0;
+        let result = operand << 1;
+        self.set_zn(result);
+        result
+    }
+
+    fn rotate_left(&mut self, operand: Byte) -> Byte {
+        let result = operand << 1 | self.f_c as Byte;
+        self.f_c = (operand & 0x80) != 0;
+        self.set_zn(result);
+        result
+    }
+
+    fn shift_right(&mut self, operand: Byte) -> Byte {
+        self.f_c = (operand & 1) != 0;
+        let result = operand >> 1;
+        self.set_zn(result);
+        result
+    }
+
+    fn rotate_right(&mut self, operand: Byte) -> Byte {
+        let result = operand >> 1 | (self.f_c as Byte) << 7;
+        self.f_c = (operand & 1) != 0;
+        self.set_zn(result);
+        result
+    }
+
+    fn increment(&mut self, operand: Byte) -> Byte {
+        let result = operand.wrapping_add(1);
+        self.set_zn(result);
+        result
+    }
+
+    fn decrement(&mut self, operand: Byte) -> Byte {
+        let result = operand.wrapping_sub(1);
+        self.set_zn(result);
+        result
+    }
+
+    /// The SHA/SHX/SHY/TAS family stores `value & (high byte of the base address + 1)`.
+    /// When indexing crosses a page the high byte of the target is replaced by that value too.
+    fn store_and_high(&mut self, location: Address, index: Byte, value: Byte) {
+        let base = location.wrapping_sub(index.into());
+        let mut location = location;
+        let value = value & ((base >> 8) as Byte).wrapping_add(1);
+        if (base & 0xff00) != (location & 0xff00) {
+            location = (value as Address) << 8 | (location & 0xff);
+        }
+        self.bus.write(location, value);
+    }
+
+    fn fetch(&mut self) -> Byte {
+        let value = self.bus.read(self.r_pc);
+        self.r_pc = self.r_pc.wrapping_add(1);
+        value
+    }
+
+    /// Runs one cycle of the current instruction in cycle-accurate mode.
+    fn run_micro_op(&mut self) {
+        let info = &INSTRUCTIONS[self.m_opcode as usize];
+        let (addressing, operation) = micro_ops(info);
+        let index = self.m_tstate as usize - 1;
+        let op = if index < addressing.len() {
+            addressing[index]
+        } else {
+            operation[index - addressing.len()]
+        };
+
+        if self.micro_op(info, op) || index + 1 == addressing.len() + operation.len() {
+            self.m_tstate = 0;
+            if !matches!(info.mnemonic, Mnemonic::CLI | Mnemonic::SEI | Mnemonic::PLP) {
+                self.m_irq_poll_inhibit = self.f_i;
             }
-    
-            let mut operand: u16;
-            match op {
-                Operation2::ASL | Operation2::ROL => {
-                    if addr_mode == AddressingMode2::Accumulator {
-                        let prev_c = self.f_c;
-                        self.f_c = (self.r_a & 0x80) != 0;
-                        self.r_a <<= 1;
-                        self.r_a |= (prev_c && (op == Operation2::ROL)) as Byte;
-                        self.set_zn(self.r_a);
-                    } else {
-                        let prev_c = self.f_c;
-                        operand = self.bus.read(location) as u16;
-                        self.f_c = (operand & 0x80) != 0;
-                        operand = (operand << 1 | (prev_c && (op == Operation2::ROL)) as u16) & 0xFF;
-                        self.set_zn(self.r_a);
-                        self.bus.write(location, operand as Byte);
-                    }
-                }
-                Operation2::LSR | Operation2::ROR => {
-                    if addr_mode == AddressingMode2::Accumulator {
-                        let prev_c = self.f_c;
-                        self.f_c = (self.r_a & 1) != 0;
-                        self.r_a >>= 1;
-                        self.r_a |= ((prev_c && (op == Operation2::ROR)) as Byte) << 7;
-                        self.set_zn(self.r_a);
-                    } else {
-                        let prev_c = self.f_c;
-                        operand = self.bus.read(location) as u16;
-                        self.f_c = (operand & 1) != 0;
-                        operand = (operand >> 1 | ((prev_c && (op == Operation2::ROR)) as u16) << 7) & 0xFF;
-                        self.set_zn(self.r_a);
-                        self.bus.write(location, operand as Byte);
-                    }
-                }
-                Operation2::STX => {
-                    self.bus.write(location, self.r_x);
+        } else {
+            self.m_tstate += 1;
+        }
+    }
+
+    /// Performs the bus access of one cycle. Returns true if the instruction ends early,
+    /// i.e. a branch isn't taken or an indexed read didn't cross a page.
+    fn micro_op(&mut self, info: &OpcodeInfo, op: MicroOp) -> bool {
+        match op {
+            MicroOp::DummyReadPc => {
+                self.bus.read(self.r_pc);
+            }
+            MicroOp::DummyReadStack => {
+                self.bus.read(0x100 | self.r_sp as Address);
+            }
+            MicroOp::IncrementPc => {
+                self.bus.read(self.r_pc);
+                self.r_pc = self.r_pc.wrapping_add(1);
+            }
+            MicroOp::FetchAddressLow => {
+                self.m_address = self.fetch() as Address;
+            }
+            MicroOp::FetchAddressHigh => {
+                self.m_address |= (self.fetch() as Address) << 8;
+            }
+            MicroOp::FetchAddressHighX => {
+                self.m_base = self.m_address | (self.fetch() as Address) << 8;
+                self.m_address = self.m_base.wrapping_add(self.r_x as Address);
+            }
+            MicroOp::FetchAddressHighY => {
+                self.m_base = self.m_address | (self.fetch() as Address) << 8;
+                self.m_address = self.m_base.wrapping_add(self.r_y as Address);
+            }
+            MicroOp::ZeroPageIndexX => {
+                self.bus.read(self.m_address);
+                self.m_address = (self.m_address as Byte).wrapping_add(self.r_x) as Address;
+            }
+            MicroOp::ZeroPageIndexY => {
+                self.bus.read(self.m_address);
+                self.m_address = (self.m_address as Byte).wrapping_add(self.r_y) as Address;
+            }
+            MicroOp::FetchPointer => {
+                self.m_data = self.fetch();
+            }
+            MicroOp::PointerIndexX => {
+                self.bus.read(self.m_data as Address);
+                self.m_data = self.m_data.wrapping_add(self.r_x);
+            }
+            MicroOp::ReadPointerLow => {
+                self.m_address = self.bus.read(self.m_data as Address) as Address;
+            }
+            MicroOp::ReadPointerHigh => {
+                self.m_address |= (self.bus.read(self.m_data.wrapping_add(1) as Address) as Address) << 8;
+            }
+            MicroOp::ReadPointerHighY => {
+                self.m_base = self.m_address
+                    | (self.bus.read(self.m_data.wrapping_add(1) as Address) as Address) << 8;
+                self.m_address = self.m_base.wrapping_add(self.r_y as Address);
+            }
+            MicroOp::ReadUnfixed => {
+                // The high byte isn't fixed up yet; reads are done here unless that was needed
+                let unfixed = (self.m_base & 0xff00) | (self.m_address & 0xff);
+                let operand = self.bus.read(unfixed);
+                if info.page_penalty && unfixed == self.m_address {
+                    self.read_operation(info.mnemonic, operand);
+                    return true;
                 }
-                Operation2::LDX => {
-                    self.r_x = self.bus.read(location);
-                    self.set_zn(self.r_x);
+            }
+            MicroOp::Read => {
+                let operand = self.bus.read(self.m_address);
+                self.read_operation(info.mnemonic, operand);
+            }
+            MicroOp::Write => {
+                self.write_operation(info.mnemonic, self.m_address);
+            }
+            MicroOp::ReadModify => {
+                self.m_data = self.bus.read(self.m_address);
+            }
+            MicroOp::WriteUnmodified => {
+                // The unmodified value is written back while the ALU works on it
+                self.bus.write(self.m_address, self.m_data);
+                self.m_data = self.modify_operation(info.mnemonic, self.m_data);
+            }
+            MicroOp::WriteModified => {
+                self.bus.write(self.m_address, self.m_data);
+            }
+            MicroOp::Implied => {
+                self.bus.read(self.r_pc);
+                self.implied_operation(info.mnemonic);
+            }
+            MicroOp::Immediate => {
+                let operand = self.fetch();
+                self.read_operation(info.mnemonic, operand);
+            }
+            MicroOp::FetchOffset => {
+                self.m_data = self.fetch();
+                if !self.branch_taken(info.mnemonic) {
+                    return true;
                 }
-                Operation2::DEC => {
-                    let mut tmp = self.bus.read(location);
-                    tmp = tmp.wrapping_sub(1);
-                    self.set_zn(tmp);
-                    self.bus.write(location, tmp as Byte);
+            }
+            MicroOp::BranchTaken => {
+                self.bus.read(self.r_pc);
+                self.m_address = self.r_pc.wrapping_add(self.m_data as i8 as Address);
+                if (self.m_address & 0xff00) == (self.r_pc & 0xff00) {
+                    self.r_pc = self.m_address;
+                    return true;
                 }
-                Operation2::INC => {
-                    let mut tmp = self.bus.read(location);
-                    tmp = tmp.wrapping_add(1);
-                    self.set_zn(tmp);
-                    self.bus.write(location, tmp as Byte);
+                self.r_pc = (self.r_pc & 0xff00) | (self.m_address & 0xff);
+            }
+            MicroOp::BranchFixup => {
+                self.bus.read(self.r_pc);
+                self.r_pc = self.m_address;
+            }
+            MicroOp::JumpHigh => {
+                let high = self.bus.read(self.r_pc);
+                self.r_pc = self.m_address | (high as Address) << 8;
+            }
+            MicroOp::ReadIndirectLow => {
+                self.m_data = self.bus.read(self.m_address);
+            }
+            MicroOp::JumpIndirectHigh => {
+                // Same page wrap bug as in `operand_address`
+                let high = self.bus.read((self.m_address & 0xff00) | (self.m_address.wrapping_add(1) & 0xff));
+                self.r_pc = self.m_data as Address | (high as Address) << 8;
+            }
+            MicroOp::PushA => self.push_stack(self.r_a),
+            MicroOp::PushFlags => self.push_stack(self.status().to_stack_byte(true)),
+            MicroOp::PullA => {
+                self.r_a = self.pull_stack();
+                self.set_zn(self.r_a);
+            }
+            MicroOp::PullFlags => {
+                let flags = self.pull_stack();
+                self.set_status(StatusFlags::from_stack_byte(flags));
+            }
+            MicroOp::PushPch => self.push_stack((self.r_pc >> 8) as Byte),
+            MicroOp::PushPcl => self.push_stack(self.r_pc as Byte),
+            MicroOp::PullPcl => {
+                self.r_pc = self.pull_stack() as Address;
+            }
+            MicroOp::PullPch => {
+                self.r_pc |= (self.pull_stack() as Address) << 8;
+            }
+            MicroOp::BreakPadding => {
+                // BRK skips its padding byte, hardware interrupts only read it
+                self.bus.read(self.r_pc);
+                if self.m_interrupt_in_progress == Some(InterruptType::BRK) {
+                    self.r_pc = self.r_pc.wrapping_add(1);
                 }
             }
-            return true;
+            MicroOp::PushStatus => {
+                let break_flag = self.m_interrupt_in_progress == Some(InterruptType::BRK);
+                self.push_stack(self.status().to_stack_byte(break_flag));
+                self.f_i = true;
+            }
+            MicroOp::VectorLow => {
+                // An NMI that arrived by now hijacks a BRK or IRQ sequence
+                self.m_base = if self.m_interrupt_in_progress == Some(InterruptType::NMI) || self.m_pending_nmi {
+                    self.m_pending_nmi = false;
+                    NMI_VECTOR
+                } else {
+                    IRQ_VECTOR
+                };
+                self.m_address = self.bus.read(self.m_base) as Address;
+            }
+            MicroOp::VectorHigh => {
+                self.r_pc = self.m_address | (self.bus.read(self.m_base + 1) as Address) << 8;
+            }
         }
         false
     }
-}
\ No newline at end of file
+}
+
+/// One cycle of an instruction in cycle-accurate mode. Each performs exactly one bus access.
+#[derive(Clone, Copy)]
+enum MicroOp {
+    DummyReadPc,
+    DummyReadStack,
+    IncrementPc,
+    FetchAddressLow,
+    FetchAddressHigh,
+    FetchAddressHighX,
+    FetchAddressHighY,
+    ZeroPageIndexX,
+    ZeroPageIndexY,
+    FetchPointer,
+    PointerIndexX,
+    ReadPointerLow,
+    ReadPointerHigh,
+    ReadPointerHighY,
+    ReadUnfixed,
+    Read,
+    Write,
+    ReadModify,
+    WriteUnmodified,
+    WriteModified,
+    Implied,
+    Immediate,
+    FetchOffset,
+    BranchTaken,
+    BranchFixup,
+    JumpHigh,
+    ReadIndirectLow,
+    JumpIndirectHigh,
+    PushA,
+    PushFlags,
+    PullA,
+    PullFlags,
+    PushPch,
+    PushPcl,
+    PullPcl,
+    PullPch,
+    BreakPadding,
+    PushStatus,
+    VectorLow,
+    VectorHigh,
+}
+
+/// The cycles following the opcode fetch, split into the addressing cycles and the
+/// cycles that access the operand.
+fn micro_ops(info: &OpcodeInfo) -> (&'static [MicroOp], &'static [MicroOp]) {
+    use MicroOp::*;
+    let addressing: &'static [MicroOp] = match (info.mnemonic, info.mode) {
+        (Mnemonic::BRK, _) => return (&[BreakPadding, PushPch, PushPcl, PushStatus, VectorLow, VectorHigh], &[]),
+        (Mnemonic::JSR, _) => return (&[FetchAddressLow, DummyReadStack, PushPch, PushPcl, JumpHigh], &[]),
+        (Mnemonic::RTS, _) => return (&[DummyReadPc, DummyReadStack, PullPcl, PullPch, IncrementPc], &[]),
+        (Mnemonic::RTI, _) => return (&[DummyReadPc, DummyReadStack, PullFlags, PullPcl, PullPch], &[]),
+        (Mnemonic::JMP, AddressingMode::Absolute) => return (&[FetchAddressLow, JumpHigh], &[]),
+        (Mnemonic::JMP, _) => return (&[FetchAddressLow, FetchAddressHigh, ReadIndirectLow, JumpIndirectHigh], &[]),
+        (Mnemonic::PHA, _) => return (&[DummyReadPc, PushA], &[]),
+        (Mnemonic::PHP, _) => return (&[DummyReadPc, PushFlags], &[]),
+        (Mnemonic::PLA, _) => return (&[DummyReadPc, DummyReadStack, PullA], &[]),
+        (Mnemonic::PLP, _) => return (&[DummyReadPc, DummyReadStack, PullFlags], &[]),
+        (_, AddressingMode::Implied) | (_, AddressingMode::Accumulator) => return (&[Implied], &[]),
+        (_, AddressingMode::Immediate) => return (&[Immediate], &[]),
+        (_, AddressingMode::Relative) => return (&[FetchOffset, BranchTaken, BranchFixup], &[]),
+        (_, AddressingMode::ZeroPage) => &[FetchAddressLow],
+        (_, AddressingMode::ZeroPageX) => &[FetchAddressLow, ZeroPageIndexX],
+        (_, AddressingMode::ZeroPageY) => &[FetchAddressLow, ZeroPageIndexY],
+        (_, AddressingMode::Absolute) => &[FetchAddressLow, FetchAddressHigh],
+        (_, AddressingMode::AbsoluteX) => &[FetchAddressLow, FetchAddressHighX, ReadUnfixed],
+        (_, AddressingMode::AbsoluteY) => &[FetchAddressLow, FetchAddressHighY, ReadUnfixed],
+        (_, AddressingMode::IndirectX) => &[FetchPointer, PointerIndexX, ReadPointerLow, ReadPointerHigh],
+        (_, AddressingMode::IndirectY) => &[FetchPointer, ReadPointerLow, ReadPointerHighY, ReadUnfixed],
+        (_, AddressingMode::Indirect) => unreachable!("only JMP is indirect"),
+    };
+    let operation: &'static [MicroOp] = match info.access {
+        MemoryAccess::Read => &[Read],
+        MemoryAccess::Write => &[Write],
+        MemoryAccess::ReadModifyWrite => &[ReadModify, WriteUnmodified, WriteModified],
+        MemoryAccess::None => unreachable!("{:?} has no memory operand", info.mnemonic),
+    };
+    (addressing, operation)
+}
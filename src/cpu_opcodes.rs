@@ -6,283 +6,539 @@
  */
 
 
-pub(crate) const INSTRUCTION_MODE_MASK: u8 = 0x3;
-
-pub(crate) const OPERATION_MASK: u8 = 0xe0;
-pub(crate) const OPERATION_SHIFT: u8 = 5;
-
-pub(crate) const ADDR_MODE_MASK: u8 = 0x1c;
-pub(crate) const ADDR_MODE_SHIFT: u8 = 2;
-
-pub(crate) const BRANCH_INSTRUCTION_MASK: u8 = 0x1f;
-pub(crate) const BRANCH_INSTRUCTION_MASK_RESULT: u8 = 0x10;
-//pub(crate) const BRANCH_CONDITION_MASK: u8 = 0x20;
-pub(crate) const BRANCH_ON_FLAG_SHIFT: u8 = 6;
-
+pub(crate) const NMI_VECTOR: u16 = 0xfffa;
 pub(crate) const RESET_VECTOR: u16 = 0xfffc;
+pub(crate) const IRQ_VECTOR: u16 = 0xfffe;
 
-pub(crate) enum BranchOnFlag {
-    Negative,
-    Overflow,
-    Carry,
-    Zero,
-}
-
-impl From<u8> for BranchOnFlag {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => BranchOnFlag::Negative,
-            1 => BranchOnFlag::Overflow,
-            2 => BranchOnFlag::Carry,
-            3 => BranchOnFlag::Zero,
-            _ => panic!("Unknown integer value for BranchOnFlag enum"),
-        }
-    }
-}
-
-pub(crate) enum Operation1 {
-    ORA, // 'OR' memory with ACC
-    AND,
-    EOR, // 'Exclusive OR' with ACC
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mnemonic {
     ADC,
-    STA,
-    LDA, // Load ACC with Mem
+    AHX, // unstable, also called SHA
+    ALR, // AND #imm + LSR A
+    ANC, // AND #imm, C = N
+    AND,
+    ARR, // AND #imm + ROR A with odd C/V
+    ASL,
+    AXS, // X = (A & X) - #imm
+    BCC,
+    BCS,
+    BEQ,
+    BIT,
+    BMI,
+    BNE,
+    BPL,
+    BRK,
+    BVC,
+    BVS,
+    CLC,
+    CLD,
+    CLI,
+    CLV,
     CMP,
+    CPX,
+    CPY,
+    DCP, // DEC + CMP
+    DEC,
+    DEX,
+    DEY,
+    EOR,
+    INC,
+    INX,
+    INY,
+    ISC, // INC + SBC
+    JMP,
+    JSR,
+    KIL, // jams the CPU
+    LAS, // A = X = SP = M & SP
+    LAX, // LDA + LDX
+    LDA,
+    LDX,
+    LDY,
+    LSR,
+    LXA, // unstable
+    NOP,
+    ORA,
+    PHA,
+    PHP,
+    PLA,
+    PLP,
+    RLA, // ROL + AND
+    ROL,
+    ROR,
+    RRA, // ROR + ADC
+    RTI,
+    RTS,
+    SAX, // store A & X
     SBC,
+    SEC,
+    SED,
+    SEI,
+    SHX, // unstable
+    SHY, // unstable
+    SLO, // ASL + ORA
+    SRE, // LSR + EOR
+    STA,
+    STX,
+    STY,
+    TAS, // unstable
+    TAX,
+    TAY,
+    TSX,
+    TXA,
+    TXS,
+    TYA,
+    XAA, // unstable
 }
 
-impl PartialEq for Operation1 {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Operation1::ORA, Operation1::ORA) => true,
-            (Operation1::AND, Operation1::AND) => true,
-            (Operation1::EOR, Operation1::EOR) => true,
-            (Operation1::ADC, Operation1::ADC) => true,
-            (Operation1::STA, Operation1::STA) => true,
-            (Operation1::LDA, Operation1::LDA) => true,
-            (Operation1::CMP, Operation1::CMP) => true,
-            (Operation1::SBC, Operation1::SBC) => true,
-            _ => false,
+impl Mnemonic {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mnemonic::ADC => "ADC",
+            Mnemonic::AHX => "AHX",
+            Mnemonic::ALR => "ALR",
+            Mnemonic::ANC => "ANC",
+            Mnemonic::AND => "AND",
+            Mnemonic::ARR => "ARR",
+            Mnemonic::ASL => "ASL",
+            Mnemonic::AXS => "AXS",
+            Mnemonic::BCC => "BCC",
+            Mnemonic::BCS => "BCS",
+            Mnemonic::BEQ => "BEQ",
+            Mnemonic::BIT => "BIT",
+            Mnemonic::BMI => "BMI",
+            Mnemonic::BNE => "BNE",
+            Mnemonic::BPL => "BPL",
+            Mnemonic::BRK => "BRK",
+            Mnemonic::BVC => "BVC",
+            Mnemonic::BVS => "BVS",
+            Mnemonic::CLC => "CLC",
+            Mnemonic::CLD => "CLD",
+            Mnemonic::CLI => "CLI",
+            Mnemonic::CLV => "CLV",
+            Mnemonic::CMP => "CMP",
+            Mnemonic::CPX => "CPX",
+            Mnemonic::CPY => "CPY",
+            Mnemonic::DCP => "DCP",
+            Mnemonic::DEC => "DEC",
+            Mnemonic::DEX => "DEX",
+            Mnemonic::DEY => "DEY",
+            Mnemonic::EOR => "EOR",
+            Mnemonic::INC => "INC",
+            Mnemonic::INX => "INX",
+            Mnemonic::INY => "INY",
+            Mnemonic::ISC => "ISB",
+            Mnemonic::JMP => "JMP",
+            Mnemonic::JSR => "JSR",
+            Mnemonic::KIL => "KIL",
+            Mnemonic::LAS => "LAS",
+            Mnemonic::LAX => "LAX",
+            Mnemonic::LDA => "LDA",
+            Mnemonic::LDX => "LDX",
+            Mnemonic::LDY => "LDY",
+            Mnemonic::LSR => "LSR",
+            Mnemonic::LXA => "LXA",
+            Mnemonic::NOP => "NOP",
+            Mnemonic::ORA => "ORA",
+            Mnemonic::PHA => "PHA",
+            Mnemonic::PHP => "PHP",
+            Mnemonic::PLA => "PLA",
+            Mnemonic::PLP => "PLP",
+            Mnemonic::RLA => "RLA",
+            Mnemonic::ROL => "ROL",
+            Mnemonic::ROR => "ROR",
+            Mnemonic::RRA => "RRA",
+            Mnemonic::RTI => "RTI",
+            Mnemonic::RTS => "RTS",
+            Mnemonic::SAX => "SAX",
+            Mnemonic::SBC => "SBC",
+            Mnemonic::SEC => "SEC",
+            Mnemonic::SED => "SED",
+            Mnemonic::SEI => "SEI",
+            Mnemonic::SHX => "SHX",
+            Mnemonic::SHY => "SHY",
+            Mnemonic::SLO => "SLO",
+            Mnemonic::SRE => "SRE",
+            Mnemonic::STA => "STA",
+            Mnemonic::STX => "STX",
+            Mnemonic::STY => "STY",
+            Mnemonic::TAS => "TAS",
+            Mnemonic::TAX => "TAX",
+            Mnemonic::TAY => "TAY",
+            Mnemonic::TSX => "TSX",
+            Mnemonic::TXA => "TXA",
+            Mnemonic::TXS => "TXS",
+            Mnemonic::TYA => "TYA",
+            Mnemonic::XAA => "XAA",
         }
     }
-}
 
-
-impl From<u8> for Operation1 {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => Operation1::ORA,
-            1 => Operation1::AND,
-            2 => Operation1::EOR,
-            3 => Operation1::ADC,
-            4 => Operation1::STA,
-            5 => Operation1::LDA,
-            6 => Operation1::CMP,
-            7 => Operation1::SBC,
-            _ => panic!("Unknown opcode for Operation1"),
-        }
+    /// Opcodes whose behaviour depends on analog effects and varies between chips.
+    pub fn is_unstable(self) -> bool {
+        matches!(
+            self,
+            Mnemonic::XAA | Mnemonic::LXA | Mnemonic::AHX | Mnemonic::TAS | Mnemonic::SHY | Mnemonic::SHX
+        )
     }
 }
 
-pub(crate) enum OperationImplied {
-    NOP = 0xea,
-    JSR = 0x20,
-    RTI = 0x40,
-    RTS = 0x60,
-    JMP = 0x4c,
-    JMPI = 0x6c, // JMP Indirect
-    PHP = 0x08,
-    PLP = 0x28,
-    PHA = 0x48,
-    PLA = 0x68,
-    DEY = 0x88,
-    DEX = 0xca,
-    TAY = 0xa8,
-    INY = 0xc8,
-    INX = 0xe8,
-    CLC = 0x18,
-    SEC = 0x38,
-    CLI = 0x58,
-    SEI = 0x78,
-    TYA = 0x98,
-    CLV = 0xb8,
-    CLD = 0xd8,
-    SED = 0xf8,
-    TXA = 0x8a,
-    TXS = 0x9a,
-    TAX = 0xaa,
-    TSX = 0xba,
-    Unknown
-}
-
-impl From<u8> for OperationImplied {
-    fn from(value: u8) -> Self {
-        match value {
-            0xea => OperationImplied::NOP,
-            0x20 => OperationImplied::JSR,
-            0x40 => OperationImplied::RTI,
-            0x60 => OperationImplied::RTS,
-            0x4c => OperationImplied::JMP,
-            0x6c => OperationImplied::JMPI,
-            0x08 => OperationImplied::PHP,
-            0x28 => OperationImplied::PLP,
-            0x48 => OperationImplied::PHA,
-            0x68 => OperationImplied::PLA,
-            0x88 => OperationImplied::DEY,
-            0xca => OperationImplied::DEX,
-            0xa8 => OperationImplied::TAY,
-            0xc8 => OperationImplied::INY,
-            0xe8 => OperationImplied::INX,
-            0x18 => OperationImplied::CLC,
-            0x38 => OperationImplied::SEC,
-            0x58 => OperationImplied::CLI,
-            0x78 => OperationImplied::SEI,
-            0x98 => OperationImplied::TYA,
-            0xb8 => OperationImplied::CLV,
-            0xd8 => OperationImplied::CLD,
-            0xf8 => OperationImplied::SED,
-            0x8a => OperationImplied::TXA,
-            0x9a => OperationImplied::TXS,
-            0xaa => OperationImplied::TAX,
-            0xba => OperationImplied::TSX,
-            _ => OperationImplied::Unknown
-        }
-    }
-}
-
-
-pub(crate) enum AddressingMode1 {
-    IndexedIndirectX,
-    ZeroPage,
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
     Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
     Absolute,
-    IndirectY,
-    IndexedX,
-    AbsoluteY,
     AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX, // ($zp,X)
+    IndirectY, // ($zp),Y
+    Relative,
 }
 
-impl From<u8> for AddressingMode1 {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => AddressingMode1::IndexedIndirectX,
-            1 => AddressingMode1::ZeroPage,
-            2 => AddressingMode1::Immediate,
-            3 => AddressingMode1::Absolute,
-            4 => AddressingMode1::IndirectY,
-            5 => AddressingMode1::IndexedX,
-            6 => AddressingMode1::AbsoluteY,
-            7 => AddressingMode1::AbsoluteX,
-            _ => unimplemented!(), // Handle the case for other u8 values if needed
+impl AddressingMode {
+    /// Number of operand bytes following the opcode.
+    pub const fn operand_length(self) -> u16 {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY
+                | AddressingMode::Indirect => 2,
+            _ => 1,
         }
     }
 }
 
-
-
-pub(crate) enum Operation2 {
-    ASL,
-    ROL,
-    LSR,
-    ROR,
-    STX,
-    LDX,
-    DEC,
-    INC,
+/// How an instruction uses the memory its operand points at.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MemoryAccess {
+    /// No data access: implied, accumulator, branches and jumps
+    None,
+    Read,
+    Write,
+    ReadModifyWrite,
 }
 
-impl From<u8> for Operation2 {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => Operation2::ASL,
-            1 => Operation2::ROL,
-            2 => Operation2::LSR,
-            3 => Operation2::ROR,
-            4 => Operation2::STX,
-            5 => Operation2::LDX,
-            6 => Operation2::DEC,
-            7 => Operation2::INC,
-            _ => panic!("Unknown opcode for Operation2"),
-        }
-    }
+#[derive(Clone, Copy, Debug)]
+pub struct OpcodeInfo {
+    pub mnemonic: Mnemonic,
+    pub mode: AddressingMode,
+    /// Cycles taken without page crossings or taken branches
+    pub cycles: u8,
+    /// Whether crossing a page while indexing (or branching) costs an extra cycle
+    pub page_penalty: bool,
+    /// Length in bytes, opcode included
+    pub length: u8,
+    pub official: bool,
+    pub access: MemoryAccess,
 }
 
-impl PartialEq for Operation2 {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Operation2::ASL, Operation2::ASL) => true,
-            (Operation2::ROL, Operation2::ROL) => true,
-            (Operation2::LSR, Operation2::LSR) => true,
-            (Operation2::ROR, Operation2::ROR) => true,
-            (Operation2::STX, Operation2::STX) => true,
-            (Operation2::LDX, Operation2::LDX) => true,
-            (Operation2::DEC, Operation2::DEC) => true,
-            (Operation2::INC, Operation2::INC) => true,
-            _ => false,
+const fn access_of(mnemonic: Mnemonic, mode: AddressingMode) -> MemoryAccess {
+    match (mnemonic, mode) {
+        (_, AddressingMode::Implied) | (_, AddressingMode::Accumulator) | (_, AddressingMode::Relative) => {
+            MemoryAccess::None
         }
+        (Mnemonic::JMP, _) | (Mnemonic::JSR, _) => MemoryAccess::None,
+        (Mnemonic::STA, _) | (Mnemonic::STX, _) | (Mnemonic::STY, _) | (Mnemonic::SAX, _) | (Mnemonic::AHX, _)
+            | (Mnemonic::TAS, _) | (Mnemonic::SHX, _) | (Mnemonic::SHY, _) => MemoryAccess::Write,
+        (Mnemonic::ASL, _) | (Mnemonic::LSR, _) | (Mnemonic::ROL, _) | (Mnemonic::ROR, _) | (Mnemonic::INC, _)
+            | (Mnemonic::DEC, _) | (Mnemonic::SLO, _) | (Mnemonic::RLA, _) | (Mnemonic::SRE, _) | (Mnemonic::RRA, _)
+            | (Mnemonic::DCP, _) | (Mnemonic::ISC, _) => MemoryAccess::ReadModifyWrite,
+        _ => MemoryAccess::Read,
     }
 }
 
-pub(crate) enum AddressingMode2 {
-    Immediate_,
-    ZeroPage_,
-    Accumulator,
-    Absolute_,
-    Indexed = 5,
-    AbsoluteIndexed = 7,
-}
-
-impl From<u8> for AddressingMode2 {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => AddressingMode2::Immediate_,
-            1 => AddressingMode2::ZeroPage_,
-            2 => AddressingMode2::Accumulator,
-            3 => AddressingMode2::Absolute_,
-            5 => AddressingMode2::Indexed,
-            7 => AddressingMode2::AbsoluteIndexed,
-            _ => unimplemented!(), // Handle the case for other u8 values if needed
+const fn opcode(mnemonic: Mnemonic, mode: AddressingMode, cycles: u8, official: bool) -> OpcodeInfo {
+    let access = access_of(mnemonic, mode);
+    // Reads take the extra cycle only when indexing carries into the high byte,
+    // writes and read-modify-writes always spend it
+    let page_penalty = match mode {
+        AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::IndirectY => {
+            matches!(access, MemoryAccess::Read)
         }
+        AddressingMode::Relative => true,
+        _ => false,
+    };
+    OpcodeInfo {
+        mnemonic,
+        mode,
+        cycles,
+        page_penalty,
+        length: 1 + mode.operand_length() as u8,
+        official,
+        access,
     }
 }
 
-impl PartialEq for AddressingMode2 {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (AddressingMode2::Immediate_, AddressingMode2::Immediate_) => true,
-            (AddressingMode2::ZeroPage_, AddressingMode2::ZeroPage_) => true,
-            (AddressingMode2::Accumulator, AddressingMode2::Accumulator) => true,
-            (AddressingMode2::Absolute_, AddressingMode2::Absolute_) => true,
-            (AddressingMode2::Indexed, AddressingMode2::Indexed) => true,
-            (AddressingMode2::AbsoluteIndexed, AddressingMode2::AbsoluteIndexed) => true,
-            _ => false,
-        }
-    }
+const fn official(mnemonic: Mnemonic, mode: AddressingMode, cycles: u8) -> OpcodeInfo {
+    opcode(mnemonic, mode, cycles, true)
 }
 
-// pub(crate) enum Operation0 {
-//     BIT = 1,
-//     STY = 4,
-//     LDY,
-//     CPY,
-//     CPX,
-// }
+const fn unofficial(mnemonic: Mnemonic, mode: AddressingMode, cycles: u8) -> OpcodeInfo {
+    opcode(mnemonic, mode, cycles, false)
+}
 
-pub(crate) const OPERATION_CYCLES: [u32; 0x100] = [
-    7, 6, 0, 0, 0, 3, 5, 0, 3, 2, 2, 0, 0, 4, 6, 0,
-    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
-    6, 6, 0, 0, 3, 3, 5, 0, 4, 2, 2, 0, 4, 4, 6, 0,
-    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
-    6, 6, 0, 0, 0, 3, 5, 0, 3, 2, 2, 0, 3, 4, 6, 0,
-    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
-    6, 6, 0, 0, 0, 3, 5, 0, 4, 2, 2, 0, 5, 4, 6, 0,
-    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
-    0, 6, 0, 0, 3, 3, 3, 0, 2, 0, 2, 0, 4, 4, 4, 0,
-    2, 6, 0, 0, 4, 4, 4, 0, 2, 5, 2, 0, 0, 5, 0, 0,
-    2, 6, 2, 0, 3, 3, 3, 0, 2, 2, 2, 0, 4, 4, 4, 0,
-    2, 5, 0, 0, 4, 4, 4, 0, 2, 4, 2, 0, 4, 4, 4, 0,
-    2, 6, 0, 0, 3, 3, 5, 0, 2, 2, 2, 0, 4, 4, 6, 0,
-    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
-    2, 6, 0, 0, 3, 3, 5, 0, 2, 2, 2, 2, 4, 4, 6, 0,
-    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
-];
+/// Decoding information for every opcode, built at compile time.
+pub static INSTRUCTIONS: [OpcodeInfo; 0x100] = {
+    use Mnemonic::*;
+    use AddressingMode::*;
+    [
+        official(BRK, Implied, 7), // 0x00
+        official(ORA, IndirectX, 6), // 0x01
+        unofficial(KIL, Implied, 0), // 0x02
+        unofficial(SLO, IndirectX, 8), // 0x03
+        unofficial(NOP, ZeroPage, 3), // 0x04
+        official(ORA, ZeroPage, 3), // 0x05
+        official(ASL, ZeroPage, 5), // 0x06
+        unofficial(SLO, ZeroPage, 5), // 0x07
+        official(PHP, Implied, 3), // 0x08
+        official(ORA, Immediate, 2), // 0x09
+        official(ASL, Accumulator, 2), // 0x0a
+        unofficial(ANC, Immediate, 2), // 0x0b
+        unofficial(NOP, Absolute, 4), // 0x0c
+        official(ORA, Absolute, 4), // 0x0d
+        official(ASL, Absolute, 6), // 0x0e
+        unofficial(SLO, Absolute, 6), // 0x0f
+        official(BPL, Relative, 2), // 0x10
+        official(ORA, IndirectY, 5), // 0x11
+        unofficial(KIL, Implied, 0), // 0x12
+        unofficial(SLO, IndirectY, 8), // 0x13
+        unofficial(NOP, ZeroPageX, 4), // 0x14
+        official(ORA, ZeroPageX, 4), // 0x15
+        official(ASL, ZeroPageX, 6), // 0x16
+        unofficial(SLO, ZeroPageX, 6), // 0x17
+        official(CLC, Implied, 2), // 0x18
+        official(ORA, AbsoluteY, 4), // 0x19
+        unofficial(NOP, Implied, 2), // 0x1a
+        unofficial(SLO, AbsoluteY, 7), // 0x1b
+        unofficial(NOP, AbsoluteX, 4), // 0x1c
+        official(ORA, AbsoluteX, 4), // 0x1d
+        official(ASL, AbsoluteX, 7), // 0x1e
+        unofficial(SLO, AbsoluteX, 7), // 0x1f
+        official(JSR, Absolute, 6), // 0x20
+        official(AND, IndirectX, 6), // 0x21
+        unofficial(KIL, Implied, 0), // 0x22
+        unofficial(RLA, IndirectX, 8), // 0x23
+        official(BIT, ZeroPage, 3), // 0x24
+        official(AND, ZeroPage, 3), // 0x25
+        official(ROL, ZeroPage, 5), // 0x26
+        unofficial(RLA, ZeroPage, 5), // 0x27
+        official(PLP, Implied, 4), // 0x28
+        official(AND, Immediate, 2), // 0x29
+        official(ROL, Accumulator, 2), // 0x2a
+        unofficial(ANC, Immediate, 2), // 0x2b
+        official(BIT, Absolute, 4), // 0x2c
+        official(AND, Absolute, 4), // 0x2d
+        official(ROL, Absolute, 6), // 0x2e
+        unofficial(RLA, Absolute, 6), // 0x2f
+        official(BMI, Relative, 2), // 0x30
+        official(AND, IndirectY, 5), // 0x31
+        unofficial(KIL, Implied, 0), // 0x32
+        unofficial(RLA, IndirectY, 8), // 0x33
+        unofficial(NOP, ZeroPageX, 4), // 0x34
+        official(AND, ZeroPageX, 4), // 0x35
+        official(ROL, ZeroPageX, 6), // 0x36
+        unofficial(RLA, ZeroPageX, 6), // 0x37
+        official(SEC, Implied, 2), // 0x38
+        official(AND, AbsoluteY, 4), // 0x39
+        unofficial(NOP, Implied, 2), // 0x3a
+        unofficial(RLA, AbsoluteY, 7), // 0x3b
+        unofficial(NOP, AbsoluteX, 4), // 0x3c
+        official(AND, AbsoluteX, 4), // 0x3d
+        official(ROL, AbsoluteX, 7), // 0x3e
+        unofficial(RLA, AbsoluteX, 7), // 0x3f
+        official(RTI, Implied, 6), // 0x40
+        official(EOR, IndirectX, 6), // 0x41
+        unofficial(KIL, Implied, 0), // 0x42
+        unofficial(SRE, IndirectX, 8), // 0x43
+        unofficial(NOP, ZeroPage, 3), // 0x44
+        official(EOR, ZeroPage, 3), // 0x45
+        official(LSR, ZeroPage, 5), // 0x46
+        unofficial(SRE, ZeroPage, 5), // 0x47
+        official(PHA, Implied, 3), // 0x48
+        official(EOR, Immediate, 2), // 0x49
+        official(LSR, Accumulator, 2), // 0x4a
+        unofficial(ALR, Immediate, 2), // 0x4b
+        official(JMP, Absolute, 3), // 0x4c
+        official(EOR, Absolute, 4), // 0x4d
+        official(LSR, Absolute, 6), // 0x4e
+        unofficial(SRE, Absolute, 6), // 0x4f
+        official(BVC, Relative, 2), // 0x50
+        official(EOR, IndirectY, 5), // 0x51
+        unofficial(KIL, Implied, 0), // 0x52
+        unofficial(SRE, IndirectY, 8), // 0x53
+        unofficial(NOP, ZeroPageX, 4), // 0x54
+        official(EOR, ZeroPageX, 4), // 0x55
+        official(LSR, ZeroPageX, 6), // 0x56
+        unofficial(SRE, ZeroPageX, 6), // 0x57
+        official(CLI, Implied, 2), // 0x58
+        official(EOR, AbsoluteY, 4), // 0x59
+        unofficial(NOP, Implied, 2), // 0x5a
+        unofficial(SRE, AbsoluteY, 7), // 0x5b
+        unofficial(NOP, AbsoluteX, 4), // 0x5c
+        official(EOR, AbsoluteX, 4), // 0x5d
+        official(LSR, AbsoluteX, 7), // 0x5e
+        unofficial(SRE, AbsoluteX, 7), // 0x5f
+        official(RTS, Implied, 6), // 0x60
+        official(ADC, IndirectX, 6), // 0x61
+        unofficial(KIL, Implied, 0), // 0x62
+        unofficial(RRA, IndirectX, 8), // 0x63
+        unofficial(NOP, ZeroPage, 3), // 0x64
+        official(ADC, ZeroPage, 3), // 0x65
+        official(ROR, ZeroPage, 5), // 0x66
+        unofficial(RRA, ZeroPage, 5), // 0x67
+        official(PLA, Implied, 4), // 0x68
+        official(ADC, Immediate, 2), // 0x69
+        official(ROR, Accumulator, 2), // 0x6a
+        unofficial(ARR, Immediate, 2), // 0x6b
+        official(JMP, Indirect, 5), // 0x6c
+        official(ADC, Absolute, 4), // 0x6d
+        official(ROR, Absolute, 6), // 0x6e
+        unofficial(RRA, Absolute, 6), // 0x6f
+        official(BVS, Relative, 2), // 0x70
+        official(ADC, IndirectY, 5), // 0x71
+        unofficial(KIL, Implied, 0), // 0x72
+        unofficial(RRA, IndirectY, 8), // 0x73
+        unofficial(NOP, ZeroPageX, 4), // 0x74
+        official(ADC, ZeroPageX, 4), // 0x75
+        official(ROR, ZeroPageX, 6), // 0x76
+        unofficial(RRA, ZeroPageX, 6), // 0x77
+        official(SEI, Implied, 2), // 0x78
+        official(ADC, AbsoluteY, 4), // 0x79
+        unofficial(NOP, Implied, 2), // 0x7a
+        unofficial(RRA, AbsoluteY, 7), // 0x7b
+        unofficial(NOP, AbsoluteX, 4), // 0x7c
+        official(ADC, AbsoluteX, 4), // 0x7d
+        official(ROR, AbsoluteX, 7), // 0x7e
+        unofficial(RRA, AbsoluteX, 7), // 0x7f
+        unofficial(NOP, Immediate, 2), // 0x80
+        official(STA, IndirectX, 6), // 0x81
+        unofficial(NOP, Immediate, 2), // 0x82
+        unofficial(SAX, IndirectX, 6), // 0x83
+        official(STY, ZeroPage, 3), // 0x84
+        official(STA, ZeroPage, 3), // 0x85
+        official(STX, ZeroPage, 3), // 0x86
+        unofficial(SAX, ZeroPage, 3), // 0x87
+        official(DEY, Implied, 2), // 0x88
+        unofficial(NOP, Immediate, 2), // 0x89
+        official(TXA, Implied, 2), // 0x8a
+        unofficial(XAA, Immediate, 2), // 0x8b
+        official(STY, Absolute, 4), // 0x8c
+        official(STA, Absolute, 4), // 0x8d
+        official(STX, Absolute, 4), // 0x8e
+        unofficial(SAX, Absolute, 4), // 0x8f
+        official(BCC, Relative, 2), // 0x90
+        official(STA, IndirectY, 6), // 0x91
+        unofficial(KIL, Implied, 0), // 0x92
+        unofficial(AHX, IndirectY, 6), // 0x93
+        official(STY, ZeroPageX, 4), // 0x94
+        official(STA, ZeroPageX, 4), // 0x95
+        official(STX, ZeroPageY, 4), // 0x96
+        unofficial(SAX, ZeroPageY, 4), // 0x97
+        official(TYA, Implied, 2), // 0x98
+        official(STA, AbsoluteY, 5), // 0x99
+        official(TXS, Implied, 2), // 0x9a
+        unofficial(TAS, AbsoluteY, 5), // 0x9b
+        unofficial(SHY, AbsoluteX, 5), // 0x9c
+        official(STA, AbsoluteX, 5), // 0x9d
+        unofficial(SHX, AbsoluteY, 5), // 0x9e
+        unofficial(AHX, AbsoluteY, 5), // 0x9f
+        official(LDY, Immediate, 2), // 0xa0
+        official(LDA, IndirectX, 6), // 0xa1
+        official(LDX, Immediate, 2), // 0xa2
+        unofficial(LAX, IndirectX, 6), // 0xa3
+        official(LDY, ZeroPage, 3), // 0xa4
+        official(LDA, ZeroPage, 3), // 0xa5
+        official(LDX, ZeroPage, 3), // 0xa6
+        unofficial(LAX, ZeroPage, 3), // 0xa7
+        official(TAY, Implied, 2), // 0xa8
+        official(LDA, Immediate, 2), // 0xa9
+        official(TAX, Implied, 2), // 0xaa
+        unofficial(LXA, Immediate, 2), // 0xab
+        official(LDY, Absolute, 4), // 0xac
+        official(LDA, Absolute, 4), // 0xad
+        official(LDX, Absolute, 4), // 0xae
+        unofficial(LAX, Absolute, 4), // 0xaf
+        official(BCS, Relative, 2), // 0xb0
+        official(LDA, IndirectY, 5), // 0xb1
+        unofficial(KIL, Implied, 0), // 0xb2
+        unofficial(LAX, IndirectY, 5), // 0xb3
+        official(LDY, ZeroPageX, 4), // 0xb4
+        official(LDA, ZeroPageX, 4), // 0xb5
+        official(LDX, ZeroPageY, 4), // 0xb6
+        unofficial(LAX, ZeroPageY, 4), // 0xb7
+        official(CLV, Implied, 2), // 0xb8
+        official(LDA, AbsoluteY, 4), // 0xb9
+        official(TSX, Implied, 2), // 0xba
+        unofficial(LAS, AbsoluteY, 4), // 0xbb
+        official(LDY, AbsoluteX, 4), // 0xbc
+        official(LDA, AbsoluteX, 4), // 0xbd
+        official(LDX, AbsoluteY, 4), // 0xbe
+        unofficial(LAX, AbsoluteY, 4), // 0xbf
+        official(CPY, Immediate, 2), // 0xc0
+        official(CMP, IndirectX, 6), // 0xc1
+        unofficial(NOP, Immediate, 2), // 0xc2
+        unofficial(DCP, IndirectX, 8), // 0xc3
+        official(CPY, ZeroPage, 3), // 0xc4
+        official(CMP, ZeroPage, 3), // 0xc5
+        official(DEC, ZeroPage, 5), // 0xc6
+        unofficial(DCP, ZeroPage, 5), // 0xc7
+        official(INY, Implied, 2), // 0xc8
+        official(CMP, Immediate, 2), // 0xc9
+        official(DEX, Implied, 2), // 0xca
+        unofficial(AXS, Immediate, 2), // 0xcb
+        official(CPY, Absolute, 4), // 0xcc
+        official(CMP, Absolute, 4), // 0xcd
+        official(DEC, Absolute, 6), // 0xce
+        unofficial(DCP, Absolute, 6), // 0xcf
+        official(BNE, Relative, 2), // 0xd0
+        official(CMP, IndirectY, 5), // 0xd1
+        unofficial(KIL, Implied, 0), // 0xd2
+        unofficial(DCP, IndirectY, 8), // 0xd3
+        unofficial(NOP, ZeroPageX, 4), // 0xd4
+        official(CMP, ZeroPageX, 4), // 0xd5
+        official(DEC, ZeroPageX, 6), // 0xd6
+        unofficial(DCP, ZeroPageX, 6), // 0xd7
+        official(CLD, Implied, 2), // 0xd8
+        official(CMP, AbsoluteY, 4), // 0xd9
+        unofficial(NOP, Implied, 2), // 0xda
+        unofficial(DCP, AbsoluteY, 7), // 0xdb
+        unofficial(NOP, AbsoluteX, 4), // 0xdc
+        official(CMP, AbsoluteX, 4), // 0xdd
+        official(DEC, AbsoluteX, 7), // 0xde
+        unofficial(DCP, AbsoluteX, 7), // 0xdf
+        official(CPX, Immediate, 2), // 0xe0
+        official(SBC, IndirectX, 6), // 0xe1
+        unofficial(NOP, Immediate, 2), // 0xe2
+        unofficial(ISC, IndirectX, 8), // 0xe3
+        official(CPX, ZeroPage, 3), // 0xe4
+        official(SBC, ZeroPage, 3), // 0xe5
+        official(INC, ZeroPage, 5), // 0xe6
+        unofficial(ISC, ZeroPage, 5), // 0xe7
+        official(INX, Implied, 2), // 0xe8
+        official(SBC, Immediate, 2), // 0xe9
+        official(NOP, Implied, 2), // 0xea
+        unofficial(SBC, Immediate, 2), // 0xeb
+        official(CPX, Absolute, 4), // 0xec
+        official(SBC, Absolute, 4), // 0xed
+        official(INC, Absolute, 6), // 0xee
+        unofficial(ISC, Absolute, 6), // 0xef
+        official(BEQ, Relative, 2), // 0xf0
+        official(SBC, IndirectY, 5), // 0xf1
+        unofficial(KIL, Implied, 0), // 0xf2
+        unofficial(ISC, IndirectY, 8), // 0xf3
+        unofficial(NOP, ZeroPageX, 4), // 0xf4
+        official(SBC, ZeroPageX, 4), // 0xf5
+        official(INC, ZeroPageX, 6), // 0xf6
+        unofficial(ISC, ZeroPageX, 6), // 0xf7
+        official(SED, Implied, 2), // 0xf8
+        official(SBC, AbsoluteY, 4), // 0xf9
+        unofficial(NOP, Implied, 2), // 0xfa
+        unofficial(ISC, AbsoluteY, 7), // 0xfb
+        unofficial(NOP, AbsoluteX, 4), // 0xfc
+        official(SBC, AbsoluteX, 4), // 0xfd
+        official(INC, AbsoluteX, 7), // 0xfe
+        unofficial(ISC, AbsoluteX, 7), // 0xff
+    ]
+};
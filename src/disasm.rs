@@ -0,0 +1,137 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cpu_opcodes::INSTRUCTIONS;
+use cpu_opcodes::AddressingMode;
+use crate::cpu_opcodes;
+
+use bus::Bus;
+use crate::bus;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use std::fmt;
+
+/// Memory that can be inspected without side effects, so disassembling never
+/// disturbs the state of the machine.
+pub trait MemorySource {
+    fn peek(&self, addr: Address) -> Byte;
+}
+
+impl<B: Bus> MemorySource for B {
+    fn peek(&self, addr: Address) -> Byte {
+        Bus::peek(self, addr)
+    }
+}
+
+/// PRG-ROM as NROM maps it: $8000-$FFFF, with 16KB images mirrored into both halves.
+impl MemorySource for Cartridge {
+    fn peek(&self, addr: Address) -> Byte {
+        let rom = self.get_rom();
+        if addr < 0x8000 || rom.is_empty() {
+            return 0;
+        }
+        rom[(addr - 0x8000) as usize % rom.len()]
+    }
+}
+
+/// A raw image loaded at address 0; reads past its end return 0.
+impl MemorySource for [Byte] {
+    fn peek(&self, addr: Address) -> Byte {
+        self.get(addr as usize).copied().unwrap_or(0)
+    }
+}
+
+/// A decoded instruction.
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub address: Address,
+    pub opcode: Byte,
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    pub official: bool,
+    /// The operand bytes, little-endian for 16 bit operands.
+    pub operand: Address,
+}
+
+impl Instruction {
+    pub fn length(&self) -> u16 {
+        1 + self.mode.operand_length()
+    }
+
+    /// The raw bytes as hex, e.g. `4C F5 C5`.
+    pub fn bytes(&self) -> String {
+        let mut bytes = format!("{:02X}", self.opcode);
+        for i in 0..self.mode.operand_length() {
+            bytes += &format!(" {:02X}", (self.operand >> (8 * i)) as Byte);
+        }
+        bytes
+    }
+
+    /// The operand in assembler syntax, with branch targets resolved.
+    pub fn operand_text(&self) -> String {
+        let operand = self.operand;
+        match self.mode {
+            AddressingMode::Implied => String::new(),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::Immediate => format!("#${:02X}", operand),
+            AddressingMode::ZeroPage => format!("${:02X}", operand),
+            AddressingMode::ZeroPageX => format!("${:02X},X", operand),
+            AddressingMode::ZeroPageY => format!("${:02X},Y", operand),
+            AddressingMode::Absolute => format!("${:04X}", operand),
+            AddressingMode::AbsoluteX => format!("${:04X},X", operand),
+            AddressingMode::AbsoluteY => format!("${:04X},Y", operand),
+            AddressingMode::Indirect => format!("(${:04X})", operand),
+            AddressingMode::IndirectX => format!("(${:02X},X)", operand),
+            AddressingMode::IndirectY => format!("(${:02X}),Y", operand),
+            AddressingMode::Relative => format!("${:04X}", self.branch_target()),
+        }
+    }
+
+    fn branch_target(&self) -> Address {
+        self.address.wrapping_add(2).wrapping_add(self.operand as Byte as i8 as Address)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand = self.operand_text();
+        if operand.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{} {}", self.mnemonic, operand)
+        }
+    }
+}
+
+/// Decodes the instruction at `addr`.
+pub fn disassemble<M: MemorySource + ?Sized>(memory: &M, addr: Address) -> Instruction {
+    let opcode = memory.peek(addr);
+    let info = &INSTRUCTIONS[opcode as usize];
+    let mut operand = 0;
+    for i in 0..info.mode.operand_length() {
+        operand |= (memory.peek(addr.wrapping_add(1 + i)) as Address) << (8 * i);
+    }
+    Instruction {
+        address: addr,
+        opcode,
+        mnemonic: info.mnemonic.name(),
+        mode: info.mode,
+        official: info.official,
+        operand,
+    }
+}
+
+/// Decodes `count` consecutive instructions starting at `addr`.
+pub fn disassemble_range<M: MemorySource + ?Sized>(memory: &M, addr: Address, count: usize) -> Vec<Instruction> {
+    let mut instructions = Vec::with_capacity(count);
+    let mut addr = addr;
+    for _ in 0..count {
+        let instruction = disassemble(memory, addr);
+        addr = addr.wrapping_add(instruction.length());
+        instructions.push(instruction);
+    }
+    instructions
+}
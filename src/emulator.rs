@@ -1,6 +1,12 @@
+use std::path::Path;
+use std::path::PathBuf;
 use std::string::String;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use cpu::CPU;
+use cpu::IrqSource;
 use crate::cpu;
 
 use cartridge::Cartridge;
@@ -9,18 +15,50 @@ use crate::cartridge;
 use main_bus::MainBus;
 use crate::main_bus;
 
-use mapper::Mapper;
+use mapper::create_mapper;
 use crate::mapper;
 
-// Assume that the CPU, Cartridge, MainBus, and Mapper types are defined in other modules.
+use crate::battery;
+use crate::signal;
 
-pub struct Emulator<'a> {
-    pub m_cpu: CPU<'a>,
-    pub m_bus: MainBus
+/// How often dirty battery-backed PRG-RAM is flushed, about 5 seconds of CPU time.
+const SAVE_FLUSH_CYCLES: u32 = 5 * 1_789_773;
+
+/// The whole console: the CPU owns the main bus, which owns everything mapped on it.
+pub struct Emulator {
+    pub m_cpu: CPU<MainBus>,
+    m_save_dir: Option<PathBuf>,
+    m_stop: Arc<AtomicBool>,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<'a> Emulator<'a> {
+impl Emulator {
+    pub fn new() -> Self {
+        Emulator {
+            m_cpu: CPU::new(MainBus::new()),
+            m_save_dir: None,
+            m_stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Keeps battery saves in `dir` instead of next to the ROM.
+    pub fn set_save_directory(&mut self, dir: Option<PathBuf>) {
+        self.m_save_dir = dir;
+    }
 
+    /// A flag that makes `run` return, from another thread or a signal handler.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.m_stop.clone()
+    }
+
+    /// Runs the ROM until the stop handle is set, SIGINT/SIGTERM arrives (once
+    /// `signal::install_stop_handler` was called), the CPU jams or it reports an error.
+    /// Battery saves are flushed on the way out.
     pub fn run(&mut self, rom_path: String) {
         let mut cartridge: Cartridge = Cartridge::new();
         if let Err(_error) = cartridge.load_from_file(&rom_path) {
@@ -28,13 +66,60 @@ impl<'a> Emulator<'a> {
             return;
         } 
 
-        let mut mapper = Mapper::new();
-        mapper.load(cartridge);
+        let save_path = if cartridge.has_battery() {
+            Some(battery::save_path(Path::new(&rom_path), self.m_save_dir.as_deref()))
+        } else {
+            None
+        };
+
+        let mapper = match create_mapper(cartridge) {
+            Ok(mapper) => mapper,
+            Err(error) => {
+                eprintln!("{}", error);
+                return;
+            }
+        };
         // Add code for PPU bus mapper setup here if necessary.
 
-        self.m_bus.set_mapper(mapper);
+        self.m_cpu.bus_mut().set_mapper(mapper);
+
+        if let Some(path) = &save_path {
+            match battery::load(path) {
+                Ok(Some(data)) => self.m_cpu.bus_mut().load_battery_data(&data),
+                Ok(None) => (),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
 
         self.m_cpu.reset();
+
+        let mut cycles_since_flush = 0;
+        while !self.m_cpu.is_halted() && !self.m_stop.load(Ordering::Relaxed) && !signal::stop_requested() {
+            if let Err(error) = self.m_cpu.step() {
+                eprintln!("{}", error);
+                break;
+            }
+            let mapper_irq = self.m_cpu.bus().mapper().is_some_and(|mapper| mapper.irq_line());
+            self.m_cpu.set_irq(IrqSource::Mapper, mapper_irq);
+            cycles_since_flush += 1;
+            if cycles_since_flush == SAVE_FLUSH_CYCLES {
+                cycles_since_flush = 0;
+                self.flush_save(save_path.as_deref());
+            }
+        }
+        self.flush_save(save_path.as_deref());
+        // Flush the tracer, if any
+        self.m_cpu.set_tracer(None);
     }
-}
 
+    /// Writes battery-backed RAM to the save if it changed since the last flush.
+    fn flush_save(&mut self, path: Option<&Path>) {
+        if let Some(path) = path {
+            if self.m_cpu.bus_mut().take_battery_dirty() {
+                if let Err(error) = battery::store(path, &self.m_cpu.bus().battery_data()) {
+                    eprintln!("{}", error);
+                }
+            }
+        }
+    }
+}
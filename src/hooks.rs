@@ -0,0 +1,111 @@
+use std::ops::RangeInclusive;
+
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccessKind {
+    Read,
+    Write,
+    /// Opcode fetch
+    Execute,
+}
+
+/// A bus access as seen by a hook.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub addr: Address,
+    pub value: Byte,
+    /// Address of the instruction that made the access
+    pub pc: Address,
+}
+
+/// What a hook wants emulation to do after it ran.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HookAction {
+    Continue,
+    /// Stop once the current step is done, see `Hooks::take_break`
+    Break,
+}
+
+pub type HookId = usize;
+
+struct Hook {
+    id: HookId,
+    kind: AccessKind,
+    range: RangeInclusive<Address>,
+    callback: Box<dyn FnMut(&Access) -> HookAction>,
+}
+
+/// Watchpoints and callbacks on address ranges of a bus. Costs a single bit test per
+/// access as long as no hook watches that kind of access.
+pub struct Hooks {
+    m_hooks: Vec<Hook>,
+    m_next_id: HookId,
+    // bit per AccessKind that at least one hook watches
+    m_watched: u8,
+    m_break: bool,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Hooks {
+            m_hooks: Vec::new(),
+            m_next_id: 0,
+            m_watched: 0,
+            m_break: false,
+        }
+    }
+
+    /// Calls `callback` on every `kind` access to `range`.
+    pub fn add(
+        &mut self,
+        kind: AccessKind,
+        range: RangeInclusive<Address>,
+        callback: Box<dyn FnMut(&Access) -> HookAction>,
+    ) -> HookId {
+        let id = self.m_next_id;
+        self.m_next_id += 1;
+        self.m_hooks.push(Hook { id, kind, range, callback });
+        self.m_watched |= 1 << kind as u8;
+        id
+    }
+
+    /// Removes a hook, returns false if there was no hook `id`.
+    pub fn remove(&mut self, id: HookId) -> bool {
+        let count = self.m_hooks.len();
+        self.m_hooks.retain(|hook| hook.id != id);
+        self.m_watched = self.m_hooks.iter().fold(0, |watched, hook| watched | 1 << hook.kind as u8);
+        self.m_hooks.len() != count
+    }
+
+    #[inline]
+    pub fn watches(&self, kind: AccessKind) -> bool {
+        self.m_watched & (1 << kind as u8) != 0
+    }
+
+    /// Runs the hooks matching `access`.
+    pub fn fire(&mut self, access: Access) {
+        for hook in self.m_hooks.iter_mut() {
+            if hook.kind == access.kind
+                && hook.range.contains(&access.addr)
+                && (hook.callback)(&access) == HookAction::Break
+            {
+                self.m_break = true;
+            }
+        }
+    }
+
+    /// Returns true once after a hook asked to break.
+    pub fn take_break(&mut self) -> bool {
+        std::mem::take(&mut self.m_break)
+    }
+}
@@ -0,0 +1,49 @@
+use chip::Byte;
+use crate::chip;
+
+/// About 600ms of CPU cycles, how long a bit of the PPU's I/O latch holds its charge.
+const DECAY_CYCLES: u64 = 1_073_864;
+
+/// The PPU keeps its own data bus latch, separate from the CPU's open bus. Any write to a
+/// PPU register refreshes all of it, reads only refresh the bits the register drives.
+/// Bits that haven't been refreshed for a while decay to 0.
+pub struct IoLatch {
+    m_value: Byte,
+    m_refreshed_at: [u64; 8],
+}
+
+impl Default for IoLatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoLatch {
+    pub fn new() -> Self {
+        IoLatch {
+            m_value: 0,
+            m_refreshed_at: [0; 8],
+        }
+    }
+
+    /// Drives the bits in `mask` with `value` at CPU cycle `cycle`.
+    pub fn refresh(&mut self, value: Byte, mask: Byte, cycle: u64) {
+        self.m_value = (self.m_value & !mask) | (value & mask);
+        for bit in 0..8 {
+            if mask & (1 << bit) != 0 {
+                self.m_refreshed_at[bit] = cycle;
+            }
+        }
+    }
+
+    /// The latch as seen at CPU cycle `cycle`.
+    pub fn value(&self, cycle: u64) -> Byte {
+        let mut value = self.m_value;
+        for bit in 0..8 {
+            if cycle.saturating_sub(self.m_refreshed_at[bit]) >= DECAY_CYCLES {
+                value &= !(1 << bit);
+            }
+        }
+        value
+    }
+}
@@ -0,0 +1,40 @@
+// 6502 mnemonics are acronyms by nature.
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod bus;
+pub mod main_bus;
+pub mod io_latch;
+pub mod hooks;
+pub mod chip;
+pub mod cpu;
+pub mod status_flags;
+pub mod cpu_opcodes;
+pub mod cartridge;
+pub mod cartridge_header;
+pub mod battery;
+pub mod signal;
+pub mod emulator;
+pub mod mapper;
+pub mod mapper_nrom;
+pub mod mapper_sxrom;
+pub mod mapper_txrom;
+pub mod mapper_uxrom;
+pub mod mapper_cnrom;
+pub mod mapper_axrom;
+pub mod mapper_gxrom;
+pub mod mapper_color_dreams;
+pub mod mapper_bnrom;
+pub mod mapper_pxrom;
+pub mod mapper_exrom;
+pub mod mapper_vrc4;
+pub mod mapper_vrc6;
+pub mod mapper_vrc7;
+pub mod mapper_fme7;
+pub mod mapper_namco163;
+pub mod vrc_irq;
+pub mod vrc6_audio;
+pub mod vrc7_audio;
+pub mod sunsoft5b_audio;
+pub mod namco163_audio;
+pub mod disasm;
+pub mod trace;
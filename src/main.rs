@@ -4,34 +4,118 @@
  * @LastEditors: mental1104 mental1104@gmail.com
  * @LastEditTime: 2023-10-29 23:21:47
  */
-mod main_bus;
-mod chip;
-mod cpu;
-mod cpu_opcodes;
-mod cartridge;
-mod emulator;
-mod mapper;
-use emulator::Emulator;
-use main_bus::MainBus;
-use cpu::CPU;
-use cartridge::Cartridge;
+use nes::emulator::Emulator;
+use nes::cartridge::Cartridge;
+use nes::disasm;
+use nes::disasm::MemorySource;
+use nes::signal;
 
 use std::env;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::process;
+
+fn usage(program_name: &str) -> ! {
+    eprintln!("Usage: {} [--trace <file|->] [--save-dir <dir>] <rom>", program_name);
+    eprintln!("       {} disasm <rom> [--from $C000] [--count N]", program_name);
+    process::exit(1);
+}
+
+/// Parses `$C000`, `0xC000` or `C000` as a hexadecimal address.
+fn parse_address(text: &str) -> Option<u16> {
+    let digits = text.strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn disasm_command(program_name: &str, args: &[String]) {
+    let mut rom_path = None;
+    let mut from = None;
+    let mut count = 16;
+    let mut remaining = args.iter();
+    while let Some(arg) = remaining.next() {
+        match arg.as_str() {
+            "--from" => {
+                let value = remaining.next().unwrap_or_else(|| usage(program_name));
+                from = Some(parse_address(value).unwrap_or_else(|| usage(program_name)));
+            }
+            "--count" => {
+                let value = remaining.next().unwrap_or_else(|| usage(program_name));
+                count = value.parse().unwrap_or_else(|_| usage(program_name));
+            }
+            _ if rom_path.is_none() => rom_path = Some(arg.as_str()),
+            _ => usage(program_name),
+        }
+    }
+    let rom_path = rom_path.unwrap_or_else(|| usage(program_name));
+
+    let mut cartridge = Cartridge::new();
+    if let Err(error) = cartridge.load_from_file(rom_path) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+    // Start at the reset vector unless told otherwise
+    let from = from.unwrap_or_else(|| cartridge.peek(0xfffc) as u16 | (cartridge.peek(0xfffd) as u16) << 8);
+
+    for instruction in disasm::disassemble_range(&cartridge, from, count) {
+        println!(
+            "{:04X}  {:<8} {}{}",
+            instruction.address,
+            instruction.bytes(),
+            if instruction.official { ' ' } else { '*' },
+            instruction
+        );
+    }
+}
 
 fn main() {
-    let mut main_bus = MainBus::new();
-    let mut tmp_bus = MainBus::new();
-    let mut emulator = Emulator {
-        m_cpu: CPU::new(&mut main_bus),
-        m_bus: tmp_bus
-    };
     let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("disasm") {
+        disasm_command(&args[0], &args[2..]);
+        return;
+    }
+
+    let mut emulator = Emulator::new();
 
     // 第一个参数是程序的名称
     let program_name = &args[0];
     println!("Program name: {}", program_name);
 
-    let argv = args[1].to_string();
-    println!("rom name: {}", argv);
-    emulator.run(argv);
+    let mut rom_path = None;
+    let mut trace_path = None;
+    let mut save_dir = None;
+    let mut remaining = args[1..].iter();
+    while let Some(arg) = remaining.next() {
+        match arg.as_str() {
+            "--trace" => trace_path = Some(remaining.next().unwrap_or_else(|| usage(program_name))),
+            "--save-dir" => save_dir = Some(remaining.next().unwrap_or_else(|| usage(program_name)).into()),
+            _ if rom_path.is_none() => rom_path = Some(arg.to_string()),
+            _ => usage(program_name),
+        }
+    }
+    let rom_path = rom_path.unwrap_or_else(|| usage(program_name));
+
+    if let Some(path) = trace_path {
+        let tracer: Box<dyn Write> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            match File::create(path) {
+                Ok(file) => Box::new(BufWriter::new(file)),
+                Err(error) => {
+                    eprintln!("Unable to create trace file {}: {}", path, error);
+                    process::exit(1);
+                }
+            }
+        };
+        emulator.m_cpu.set_tracer(Some(tracer));
+    }
+
+    emulator.set_save_directory(save_dir);
+
+    println!("rom name: {}", rom_path);
+    signal::install_stop_handler();
+    emulator.run(rom_path);
 }
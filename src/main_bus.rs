@@ -6,18 +6,153 @@
  */
 
 
+use std::collections::HashMap;
+
 use chip::Byte;
 use chip::Address;
 use mapper::Mapper;
 use crate::mapper;
 use crate::cartridge::Cartridge;
 use crate::chip;
+use crate::bus::Bus;
+use crate::io_latch::IoLatch;
+
+use hooks::Access;
+use hooks::AccessKind;
+use hooks::HookAction;
+use hooks::HookId;
+use hooks::Hooks;
+use crate::hooks;
+
+use std::ops::RangeInclusive;
+
+/// Memory-mapped registers of the PPU ($2000-$2007, mirrored up to $3FFF),
+/// the APU and the controller ports.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum IORegister {
+    PPUCTRL = 0x2000,
+    PPUMASK,
+    PPUSTATUS,
+    OAMADDR,
+    OAMDATA,
+    PPUSCROLL,
+    PPUADDR,
+    PPUDATA,
+    SQ1_VOL = 0x4000,
+    SQ1_SWEEP,
+    SQ1_LO,
+    SQ1_HI,
+    SQ2_VOL,
+    SQ2_SWEEP,
+    SQ2_LO,
+    SQ2_HI,
+    TRI_LINEAR,
+    TRI_LO = 0x400a,
+    TRI_HI,
+    NOISE_VOL,
+    NOISE_LO = 0x400e,
+    NOISE_HI,
+    DMC_FREQ,
+    DMC_RAW,
+    DMC_START,
+    DMC_LEN,
+    OAMDMA,
+    SND_CHN,
+    JOY1,
+    JOY2, // also the APU frame counter when written
+}
+
+impl IORegister {
+    /// Decodes an address in $2000-$4017, folding the PPU register mirrors.
+    pub fn from_address(addr: Address) -> Option<IORegister> {
+        let register = match addr {
+            0x2000..=0x3fff => match addr & 0x2007 {
+                0x2000 => IORegister::PPUCTRL,
+                0x2001 => IORegister::PPUMASK,
+                0x2002 => IORegister::PPUSTATUS,
+                0x2003 => IORegister::OAMADDR,
+                0x2004 => IORegister::OAMDATA,
+                0x2005 => IORegister::PPUSCROLL,
+                0x2006 => IORegister::PPUADDR,
+                _ => IORegister::PPUDATA,
+            },
+            0x4000 => IORegister::SQ1_VOL,
+            0x4001 => IORegister::SQ1_SWEEP,
+            0x4002 => IORegister::SQ1_LO,
+            0x4003 => IORegister::SQ1_HI,
+            0x4004 => IORegister::SQ2_VOL,
+            0x4005 => IORegister::SQ2_SWEEP,
+            0x4006 => IORegister::SQ2_LO,
+            0x4007 => IORegister::SQ2_HI,
+            0x4008 => IORegister::TRI_LINEAR,
+            0x400a => IORegister::TRI_LO,
+            0x400b => IORegister::TRI_HI,
+            0x400c => IORegister::NOISE_VOL,
+            0x400e => IORegister::NOISE_LO,
+            0x400f => IORegister::NOISE_HI,
+            0x4010 => IORegister::DMC_FREQ,
+            0x4011 => IORegister::DMC_RAW,
+            0x4012 => IORegister::DMC_START,
+            0x4013 => IORegister::DMC_LEN,
+            0x4014 => IORegister::OAMDMA,
+            0x4015 => IORegister::SND_CHN,
+            0x4016 => IORegister::JOY1,
+            0x4017 => IORegister::JOY2,
+            _ => return None,
+        };
+        Some(register)
+    }
+}
+
+/// Bits of `reg` the device drives when read, the rest float on the open bus.
+fn driven_bits(reg: IORegister) -> Byte {
+    match reg {
+        IORegister::PPUSTATUS => 0xe0,
+        IORegister::OAMDATA | IORegister::PPUDATA => 0xff,
+        // write-only PPU registers
+        IORegister::PPUCTRL
+        | IORegister::PPUMASK
+        | IORegister::OAMADDR
+        | IORegister::PPUSCROLL
+        | IORegister::PPUADDR => 0,
+        // The controller ports only drive the low 5 bits, $4015 leaves bit 5 floating
+        IORegister::JOY1 | IORegister::JOY2 => 0x1f,
+        IORegister::SND_CHN => !0x20,
+        _ => 0xff,
+    }
+}
+
+fn merge_open_bus(value: Byte, driven: Byte, open_bus: Byte) -> Byte {
+    (value & driven) | (open_bus & !driven)
+}
 
 pub struct MainBus {
     m_ram: [Byte; 0x800],
     m_ext_ram: Vec<u8>,
+    // set when PRG-RAM changed since it was last saved
+    m_ext_ram_dirty: bool,
+    // The mapper's battery-backed RAM as last saved or loaded
+    m_mapper_battery_ram: Vec<u8>,
     cartridge: Cartridge,
-    mapper: Mapper
+    mapper: Option<Box<dyn Mapper>>,
+    m_write_callbacks: HashMap<IORegister, Box<dyn FnMut(Byte)>>,
+    m_read_callbacks: HashMap<IORegister, Box<dyn FnMut() -> Byte>>,
+    m_peek_callbacks: HashMap<IORegister, Box<dyn Fn() -> Byte>>,
+    // last value on the CPU data bus, what unmapped reads return
+    m_data_bus: Byte,
+    m_ppu_latch: IoLatch,
+    m_cycles: u64,
+    m_dma_requested: bool,
+    m_hooks: Hooks,
+    // address of the last opcode fetched, the PC hooks see
+    m_instruction_pc: Address,
+}
+
+impl Default for MainBus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MainBus {
@@ -25,8 +160,19 @@ impl MainBus {
         MainBus {
             m_ram: [0; 0x800],
             m_ext_ram: Vec::new(),
+            m_ext_ram_dirty: false,
+            m_mapper_battery_ram: Vec::new(),
             cartridge: Cartridge::new(),
-            mapper: Mapper::new()
+            mapper: None,
+            m_write_callbacks: HashMap::new(),
+            m_read_callbacks: HashMap::new(),
+            m_peek_callbacks: HashMap::new(),
+            m_data_bus: 0,
+            m_ppu_latch: IoLatch::new(),
+            m_cycles: 0,
+            m_dma_requested: false,
+            m_hooks: Hooks::new(),
+            m_instruction_pc: 0,
         }
     }
 
@@ -34,33 +180,321 @@ impl MainBus {
         self.cartridge = cartridge; 
     }
 
-    pub fn set_mapper(&mut self, mapper: Mapper) -> bool {
-        self.mapper = mapper;
+    pub fn set_mapper(&mut self, mapper: Box<dyn Mapper>) -> bool {
+        if mapper.has_extended_ram() {
+            self.m_ext_ram.resize(mapper.prg_ram_size(), 0);
+        }
+        self.m_mapper_battery_ram = mapper.battery_ram().to_vec();
+        self.mapper = Some(mapper);
+
+        true
+    }
+
+    pub fn mapper(&self) -> Option<&dyn Mapper> {
+        self.mapper.as_deref()
+    }
+
+    /// For the PPU, which reads CHR and reports scanlines through the mapper.
+    pub fn mapper_mut(&mut self) -> Option<&mut (dyn Mapper + 'static)> {
+        self.mapper.as_deref_mut()
+    }
+
+    /// PRG-RAM at $6000-$7FFF, empty if the cartridge has none.
+    pub fn prg_ram(&self) -> &[Byte] {
+        &self.m_ext_ram
+    }
 
-        if self.mapper.has_extended_ram() {
-            self.m_ext_ram.resize(0x2000, 0);
+    /// Fills PRG-RAM from `data`, e.g. a battery save. Extra or missing bytes are ignored.
+    pub fn load_prg_ram(&mut self, data: &[Byte]) {
+        let length = data.len().min(self.m_ext_ram.len());
+        self.m_ext_ram[..length].copy_from_slice(&data[..length]);
+        self.m_ext_ram_dirty = false;
+    }
+
+    /// Where in PRG-RAM an access to `addr` lands, as banked and enabled by the mapper.
+    fn prg_ram_index(&self, addr: Address, write: bool) -> Option<usize> {
+        if self.m_ext_ram.is_empty() {
+            return None;
+        }
+        let offset = match self.mapper.as_ref() {
+            Some(mapper) if addr >= 0x8000 => mapper.prg_ram_over_rom(addr, write)?,
+            Some(mapper) => mapper.prg_ram_address(addr, write)?,
+            None if addr >= 0x8000 => return None,
+            None => (addr - 0x6000) as usize,
+        };
+        Some(offset % self.m_ext_ram.len())
+    }
+
+    /// Returns true once after PRG-RAM was written to, so it only gets saved when it changed.
+    pub fn take_prg_ram_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.m_ext_ram_dirty)
+    }
+
+    /// Everything a battery save holds: PRG-RAM, then any battery-backed RAM inside the
+    /// mapper.
+    pub fn battery_data(&self) -> Vec<Byte> {
+        let mut data = self.m_ext_ram.clone();
+        if let Some(mapper) = self.mapper.as_ref() {
+            data.extend_from_slice(mapper.battery_ram());
+        }
+        data
+    }
+
+    /// Restores a battery save written from `battery_data`.
+    pub fn load_battery_data(&mut self, data: &[Byte]) {
+        self.load_prg_ram(data);
+        let rest = data.get(self.m_ext_ram.len()..).unwrap_or_default();
+        if let Some(mapper) = self.mapper.as_mut() {
+            mapper.load_battery_ram(rest);
+            self.m_mapper_battery_ram = mapper.battery_ram().to_vec();
+        }
+    }
+
+    /// Like `take_prg_ram_dirty`, also covering the mapper's battery-backed RAM.
+    pub fn take_battery_dirty(&mut self) -> bool {
+        let mut dirty = self.take_prg_ram_dirty();
+        if let Some(mapper) = self.mapper.as_ref() {
+            if mapper.battery_ram() != self.m_mapper_battery_ram.as_slice() {
+                self.m_mapper_battery_ram = mapper.battery_ram().to_vec();
+                dirty = true;
+            }
         }
+        dirty
+    }
+
+    /// Lets the device behind `reg` handle CPU writes to it.
+    pub fn set_write_callback(&mut self, reg: IORegister, callback: Box<dyn FnMut(Byte)>) {
+        self.m_write_callbacks.insert(reg, callback);
+    }
+
+    /// Lets the device behind `reg` answer CPU reads from it.
+    pub fn set_read_callback(&mut self, reg: IORegister, callback: Box<dyn FnMut() -> Byte>) {
+        self.m_read_callbacks.insert(reg, callback);
+    }
+
+    /// Lets the device behind `reg` answer peeks: what a read would return right now,
+    /// without clearing flags, advancing buffers or shifting bits. Registers without
+    /// one peek as open bus.
+    pub fn set_peek_callback(&mut self, reg: IORegister, callback: Box<dyn Fn() -> Byte>) {
+        self.m_peek_callbacks.insert(reg, callback);
+    }
+
+    /// Calls `callback` on every `kind` access to `range`, with the PC of the
+    /// instruction making it. Returning `HookAction::Break` sets `take_break`.
+    pub fn add_hook(
+        &mut self,
+        kind: AccessKind,
+        range: RangeInclusive<Address>,
+        callback: Box<dyn FnMut(&Access) -> HookAction>,
+    ) -> HookId {
+        self.m_hooks.add(kind, range, callback)
+    }
+
+    pub fn remove_hook(&mut self, id: HookId) -> bool {
+        self.m_hooks.remove(id)
+    }
 
-        return true;
+    /// Returns true once after a hook asked to break.
+    pub fn take_break(&mut self) -> bool {
+        self.m_hooks.take_break()
     }
 
-    pub fn read(&self, addr: Address) -> Byte {
+    pub fn read(&mut self, addr: Address) -> Byte {
+        let value = self.read_value(addr);
+        if self.m_hooks.watches(AccessKind::Read) {
+            self.fire_hooks(AccessKind::Read, addr, value);
+        }
+        value
+    }
+
+    pub fn fetch(&mut self, addr: Address) -> Byte {
+        self.m_instruction_pc = addr;
+        let value = self.read_value(addr);
+        if self.m_hooks.watches(AccessKind::Execute) {
+            self.fire_hooks(AccessKind::Execute, addr, value);
+        }
+        value
+    }
+
+    fn fire_hooks(&mut self, kind: AccessKind, addr: Address, value: Byte) {
+        let pc = self.m_instruction_pc;
+        self.m_hooks.fire(Access { kind, addr, value, pc });
+    }
+
+    fn read_value(&mut self, addr: Address) -> Byte {
+        let value = if addr < 0x2000 {
+            self.m_ram[(addr & 0x7FF) as usize]
+        } else if addr < 0x4000 {
+            self.read_ppu_register(addr)
+        } else if addr < 0x4018 {
+            self.read_io_register(addr)
+        } else if addr < 0x4020 {
+            self.peek(addr)
+        } else if addr < 0x6000 {
+            self.read_expansion(addr)
+        } else if let Some(index) = self.prg_ram_index(addr, false) {
+            self.m_ext_ram[index]
+        } else if addr < 0x8000 {
+            self.read_expansion(addr)
+        } else {
+            match self.mapper.as_mut() {
+                Some(mapper) => mapper.read_prg(addr),
+                None => self.m_data_bus,
+            }
+        };
+        self.m_data_bus = value;
+        value
+    }
+
+    /// $4020-$7FFF reads the mapper answers, open bus where it doesn't.
+    fn read_expansion(&mut self, addr: Address) -> Byte {
+        let expansion = self.mapper.as_mut().and_then(|mapper| mapper.read_expansion(addr));
+        expansion.unwrap_or(self.m_data_bus)
+    }
+
+    fn read_ppu_register(&mut self, addr: Address) -> Byte {
+        let reg = IORegister::from_address(addr).unwrap();
+        if let Some(callback) = self.m_read_callbacks.get_mut(&reg) {
+            let value = callback();
+            self.m_ppu_latch.refresh(value, driven_bits(reg), self.m_cycles);
+        }
+        self.m_ppu_latch.value(self.m_cycles)
+    }
+
+    fn read_io_register(&mut self, addr: Address) -> Byte {
+        let open_bus = self.m_data_bus;
+        match IORegister::from_address(addr)
+            .and_then(|reg| self.m_read_callbacks.get_mut(&reg).map(|callback| (reg, callback)))
+        {
+            Some((reg, callback)) => merge_open_bus(callback(), driven_bits(reg), open_bus),
+            None => open_bus,
+        }
+    }
+
+    /// Peeks an I/O register, with the undriven bits coming from `open_bus`.
+    fn peek_register(&self, addr: Address, open_bus: Byte) -> Byte {
+        match IORegister::from_address(addr)
+            .and_then(|reg| self.m_peek_callbacks.get(&reg).map(|callback| (reg, callback)))
+        {
+            Some((reg, callback)) => merge_open_bus(callback(), driven_bits(reg), open_bus),
+            None => open_bus,
+        }
+    }
+
+    /// Copies page `page` into OAM through OAMDATA, the way the DMA unit does. The
+    /// CPU picks up the stall through `take_dma_request`.
+    fn oam_dma(&mut self, page: Byte) {
+        let base = (page as Address) << 8;
+        for offset in 0..0x100 {
+            let value = self.read(base | offset);
+            self.write(IORegister::OAMDATA as Address, value);
+        }
+        self.m_dma_requested = true;
+    }
+
+    /// Reads memory without side effects. I/O registers answer through their peek
+    /// callbacks, or read as open bus.
+    pub fn peek(&self, addr: Address) -> Byte {
         if addr < 0x2000 {
             return self.m_ram[(addr & 0x7FF) as usize];
         }
 
-        if addr >= 0x8000 {
-            // let val = self.cartridge.get_rom()[(addr - 0x8000) as usize];
-            // println!("MainBus Read a Byte: {:02X}", val);
-            // return val;
+        if addr < 0x4000 {
+            return self.peek_register(addr, self.m_ppu_latch.value(self.m_cycles));
         }
 
-        0
+        if addr < 0x4018 {
+            return self.peek_register(addr, self.m_data_bus);
+        }
+
+        if addr < 0x4020 {
+            // The disabled CPU test mode registers
+            return self.m_data_bus;
+        }
+
+        if addr >= 0x6000 {
+            if let Some(index) = self.prg_ram_index(addr, false) {
+                return self.m_ext_ram[index];
+            }
+        }
+
+        if addr < 0x8000 {
+            let expansion = self.mapper.as_ref().and_then(|mapper| mapper.peek_expansion(addr));
+            return expansion.unwrap_or(self.m_data_bus);
+        }
+
+        match self.mapper.as_ref() {
+            Some(mapper) => mapper.peek_prg(addr),
+            None => self.m_data_bus,
+        }
     }
 
     pub fn write(&mut self, addr: Address, val: Byte) {
+        if self.m_hooks.watches(AccessKind::Write) {
+            self.fire_hooks(AccessKind::Write, addr, val);
+        }
+        self.m_data_bus = val;
         if addr < 0x2000 {
             self.m_ram[(addr & 0x7FF) as usize] = val;
+        } else if addr < 0x4018 {
+            if addr < 0x4000 {
+                self.m_ppu_latch.refresh(val, 0xff, self.m_cycles);
+            }
+            if let Some(callback) = IORegister::from_address(addr)
+                .and_then(|reg| self.m_write_callbacks.get_mut(&reg))
+            {
+                callback(val);
+            }
+            if addr < 0x4000 {
+                if let Some(mapper) = self.mapper.as_mut() {
+                    mapper.ppu_register_write(addr, val);
+                }
+            }
+            if addr == IORegister::OAMDMA as Address {
+                self.oam_dma(val);
+            }
+        } else if addr < 0x4020 {
+            // Test mode registers, ignored
+        } else if addr < 0x6000 {
+            if let Some(mapper) = self.mapper.as_mut() {
+                mapper.write_expansion(addr, val);
+            }
+        } else if let Some(index) = self.prg_ram_index(addr, true) {
+            self.m_ext_ram_dirty |= self.m_ext_ram[index] != val;
+            self.m_ext_ram[index] = val;
+        } else if addr >= 0x8000 {
+            if let Some(mapper) = self.mapper.as_mut() {
+                mapper.write_prg(addr, val);
+            }
+        }
+    }
+}
+
+impl Bus for MainBus {
+    fn read(&mut self, addr: Address) -> Byte {
+        MainBus::read(self, addr)
+    }
+
+    fn fetch(&mut self, addr: Address) -> Byte {
+        MainBus::fetch(self, addr)
+    }
+
+    fn write(&mut self, addr: Address, val: Byte) {
+        MainBus::write(self, addr, val)
+    }
+
+    fn peek(&self, addr: Address) -> Byte {
+        MainBus::peek(self, addr)
+    }
+
+    fn tick(&mut self) {
+        self.m_cycles += 1;
+        if let Some(mapper) = self.mapper.as_mut() {
+            mapper.cpu_cycle();
         }
     }
+
+    fn take_dma_request(&mut self) -> bool {
+        std::mem::take(&mut self.m_dma_requested)
+    }
 }
@@ -5,77 +5,219 @@
  * @LastEditTime: 2023-10-29 23:01:54
  */
 
- use cartridge::Cartridge;
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
 use crate::cartridge;
 
-pub struct Mapper {
-    cartridge: Cartridge,
-    one_bank: bool,
-    uses_character_ram: bool,
-    character_ram: Vec<u8>
+use mapper_nrom::MapperNROM;
+use crate::mapper_nrom;
+
+use mapper_sxrom::MapperSxROM;
+use crate::mapper_sxrom;
+
+use mapper_txrom::MapperTxROM;
+use mapper_txrom::Mmc3Revision;
+use crate::mapper_txrom;
+
+use mapper_uxrom::MapperUxROM;
+use crate::mapper_uxrom;
+
+use mapper_cnrom::MapperCNROM;
+use crate::mapper_cnrom;
+
+use mapper_axrom::MapperAxROM;
+use crate::mapper_axrom;
+
+use mapper_color_dreams::MapperColorDreams;
+use crate::mapper_color_dreams;
+
+use mapper_bnrom::MapperBNROM;
+use crate::mapper_bnrom;
+
+use mapper_gxrom::MapperGxROM;
+use crate::mapper_gxrom;
+
+use mapper_pxrom::MapperPxROM;
+use crate::mapper_pxrom;
+
+use mapper_exrom::MapperExROM;
+use crate::mapper_exrom;
+
+use mapper_vrc4::MapperVRC4;
+use crate::mapper_vrc4;
+
+use mapper_vrc6::MapperVRC6;
+use crate::mapper_vrc6;
+
+use mapper_vrc7::MapperVRC7;
+use crate::mapper_vrc7;
+
+use mapper_fme7::MapperFME7;
+use crate::mapper_fme7;
+
+use mapper_namco163::MapperNamco163;
+use crate::mapper_namco163;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NameTableMirroring {
+    Horizontal = 0,
+    Vertical = 1,
+    FourScreen = 8,
+    OneScreenLower,
+    OneScreenHigher,
 }
 
-impl Mapper {
-    pub fn new() -> Self {
-        Mapper {
-            cartridge: Cartridge::new(),
-            one_bank: false,
-            uses_character_ram: false,
-            character_ram: Vec::new()
-        }
+/// The cartridge hardware between the console and PRG/CHR memory. PRG addresses are
+/// CPU addresses ($8000-$FFFF), CHR addresses are PPU addresses ($0000-$1FFF).
+pub trait Mapper {
+    fn read_prg(&mut self, addr: Address) -> Byte {
+        self.peek_prg(addr)
     }
+    /// Reads PRG memory without side effects.
+    fn peek_prg(&self, addr: Address) -> Byte;
+    fn write_prg(&mut self, addr: Address, value: Byte);
 
-    pub fn load(&mut self, cartridge: Cartridge) {
-        self.cartridge = cartridge; 
-
-        if self.cartridge.get_rom().len() == 0x4000 {
-            self.one_bank = true;
-        } else {
-            self.one_bank = false;
-        }
-
-        if self.cartridge.get_vrom().len() == 0 {
-            self.uses_character_ram = true;
-            self.character_ram.resize(0x2000, 0);
-            println!("Uses character ram"); 
-        } else {
-            println!("Using CHR-ROM");
-            self.uses_character_ram = false;
-        }
+    /// Pattern fetch by the PPU. Mappers may switch banks on what gets fetched (the
+    /// MMC2/MMC4 latches), anyone else reading CHR should use `peek_chr`.
+    fn read_chr(&mut self, addr: Address) -> Byte {
+        self.peek_chr(addr)
     }
+    /// Reads CHR memory without side effects.
+    fn peek_chr(&self, addr: Address) -> Byte;
+    fn write_chr(&mut self, addr: Address, value: Byte);
+
+    fn name_table_mirroring(&self) -> NameTableMirroring;
 
-    pub fn write_prg(&mut self, addr: u16, value: u8) {
-        println!("ROM memory write attempt at {}  to set {}", addr, value);
+    /// Nametable fetch by the PPU ($2000-$2FFF). `ciram` is the console's VRAM (4KB for
+    /// four-screen boards); mappers with their own nametable logic (MMC5) override this.
+    fn read_name_table(&mut self, addr: Address, ciram: &[Byte]) -> Byte {
+        self.peek_name_table(addr, ciram)
+    }
+    fn peek_name_table(&self, addr: Address, ciram: &[Byte]) -> Byte {
+        ciram[name_table_index(self.name_table_mirroring(), addr)]
+    }
+    fn write_name_table(&mut self, addr: Address, value: Byte, ciram: &mut [Byte]) {
+        ciram[name_table_index(self.name_table_mirroring(), addr)] = value;
     }
 
-    pub fn read_prg(&mut self, addr: u16) -> u8 {
-        if !self.one_bank {
-            let index = (addr - 0x8000) as usize;
-            return self.cartridge.get_rom()[index];
-        } else {
-            let index = ((addr - 0x8000) & 0x3fff) as usize;
-            return self.cartridge.get_rom()[index];
-        }
+    fn has_extended_ram(&self) -> bool;
+    fn prg_ram_size(&self) -> usize;
+    /// Offset into PRG-RAM that a CPU access to `addr` in $6000-$7FFF hits, `None` if
+    /// PRG-RAM is disabled (or write protected, for writes) and the access goes to open bus.
+    fn prg_ram_address(&self, addr: Address, _write: bool) -> Option<usize> {
+        Some((addr - 0x6000) as usize)
+    }
+    /// Like `prg_ram_address` for $8000-$FFFF, for mappers that can put PRG-RAM there.
+    fn prg_ram_over_rom(&self, _addr: Address, _write: bool) -> Option<usize> {
+        None
     }
 
-    pub fn write_chr(&mut self, addr: u16, value: u8){
-        if self.uses_character_ram {
-            self.character_ram[addr as usize] = value
-        } else {
-            println!("Read-only CHR memory write attempt at {}", addr);
-        }
+    /// CPU reads from the expansion area $4020-$5FFF, and from $6000-$7FFF where PRG-RAM
+    /// doesn't answer. `None` reads as open bus.
+    fn read_expansion(&mut self, addr: Address) -> Option<Byte> {
+        self.peek_expansion(addr)
     }
+    fn peek_expansion(&self, _addr: Address) -> Option<Byte> {
+        None
+    }
+    fn write_expansion(&mut self, _addr: Address, _value: Byte) {}
+    /// CPU writes to the PPU registers, for mappers that snoop them.
+    fn ppu_register_write(&mut self, _addr: Address, _value: Byte) {}
 
-    pub fn read_chr(&mut self, addr: u16) -> u8 {
-        if self.uses_character_ram {
-            return self.character_ram[addr as usize];
-        } else {
-            return self.cartridge.get_vrom()[addr as usize];
-        }
+    /// Whether the mapper is pulling the CPU's IRQ line low.
+    fn irq_line(&self) -> bool {
+        false
     }
+    /// Called by the PPU at the end of every visible scanline.
+    fn scanline(&mut self) {}
+    /// Called once per CPU cycle.
+    fn cpu_cycle(&mut self) {}
+    /// Called by the PPU with every address it puts on its bus, for mappers that
+    /// watch it (MMC3 counts rising edges of A12).
+    fn ppu_address(&mut self, _addr: Address) {}
 
-    pub fn has_extended_ram(&mut self) -> bool {
-        self.cartridge.has_extended_ram()
+    /// Current output of each expansion audio channel, in -1.0..=1.0 of that channel's
+    /// full scale, for the mixer to weigh against the APU. Updated by `cpu_cycle`.
+    fn audio_channels(&self) -> &[f32] {
+        &[]
     }
 
-}
\ No newline at end of file
+    /// Battery-backed memory inside the mapper (the Namco 163's sound RAM), saved after
+    /// PRG-RAM.
+    fn battery_ram(&self) -> &[Byte] {
+        &[]
+    }
+    fn load_battery_ram(&mut self, _data: &[Byte]) {}
+
+    /// Mapper registers and CHR-RAM, for save states.
+    fn save_state(&self) -> Vec<Byte>;
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String>;
+}
+
+/// Index into CIRAM of nametable address `addr` under `mirroring`.
+pub fn name_table_index(mirroring: NameTableMirroring, addr: Address) -> usize {
+    let table = (addr >> 10) & 0x3;
+    let page = match mirroring {
+        NameTableMirroring::Horizontal => table >> 1,
+        NameTableMirroring::Vertical => table & 0x1,
+        NameTableMirroring::FourScreen => table,
+        NameTableMirroring::OneScreenLower => 0,
+        NameTableMirroring::OneScreenHigher => 1,
+    };
+    page as usize * 0x400 + (addr & 0x3ff) as usize
+}
+
+/// Discrete boards that leave PRG-ROM enabled during writes have the ROM and the CPU
+/// drive the data bus at once; the register latches the written value ANDed with the
+/// ROM byte at that address. Games avoid it by writing to a ROM byte holding the same value.
+pub fn bus_conflict(value: Byte, rom_byte: Byte) -> Byte {
+    value & rom_byte
+}
+
+/// Bus conflicts from the NES 2.0 submapper of mappers 2, 3 and 7: 1 has none, 2 ANDs.
+/// `None` keeps the board's default.
+fn submapper_bus_conflicts(cartridge: &Cartridge) -> Option<bool> {
+    match cartridge.get_submapper() {
+        1 => Some(false),
+        2 => Some(true),
+        _ => None,
+    }
+}
+
+/// Picks the mapper implementation for the cartridge's mapper number.
+pub fn create_mapper(cartridge: Cartridge) -> Result<Box<dyn Mapper>, String> {
+    match cartridge.get_mapper() {
+        0 => Ok(Box::new(MapperNROM::new(cartridge))),
+        1 => Ok(Box::new(MapperSxROM::new(cartridge))),
+        2 => match submapper_bus_conflicts(&cartridge) {
+            Some(bus_conflicts) => Ok(Box::new(MapperUxROM::with_bus_conflicts(cartridge, bus_conflicts))),
+            None => Ok(Box::new(MapperUxROM::new(cartridge))),
+        },
+        3 => match submapper_bus_conflicts(&cartridge) {
+            Some(bus_conflicts) => Ok(Box::new(MapperCNROM::with_bus_conflicts(cartridge, bus_conflicts))),
+            None => Ok(Box::new(MapperCNROM::new(cartridge))),
+        },
+        // NES 2.0 submapper 4 is the MMC3A
+        4 if cartridge.get_submapper() == 4 => Ok(Box::new(MapperTxROM::with_revision(cartridge, Mmc3Revision::A))),
+        4 => Ok(Box::new(MapperTxROM::new(cartridge))),
+        5 => Ok(Box::new(MapperExROM::new(cartridge))),
+        7 => match submapper_bus_conflicts(&cartridge) {
+            Some(bus_conflicts) => Ok(Box::new(MapperAxROM::with_bus_conflicts(cartridge, bus_conflicts))),
+            None => Ok(Box::new(MapperAxROM::new(cartridge))),
+        },
+        9 => Ok(Box::new(MapperPxROM::new(cartridge))),
+        10 => Ok(Box::new(MapperPxROM::new_fxrom(cartridge))),
+        11 => Ok(Box::new(MapperColorDreams::new(cartridge))),
+        19 => Ok(Box::new(MapperNamco163::new(cartridge))),
+        21 | 22 | 23 | 25 => Ok(Box::new(MapperVRC4::new(cartridge))),
+        24 | 26 => Ok(Box::new(MapperVRC6::new(cartridge))),
+        34 => Ok(Box::new(MapperBNROM::new(cartridge))),
+        66 => Ok(Box::new(MapperGxROM::new(cartridge))),
+        69 => Ok(Box::new(MapperFME7::new(cartridge))),
+        85 => Ok(Box::new(MapperVRC7::new(cartridge))),
+        number => Err(format!("Mapper #{} is not supported.", number)),
+    }
+}
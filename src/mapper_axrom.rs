@@ -0,0 +1,103 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::bus_conflict;
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+/// Mapper 7, AxROM: a switchable 32KB PRG bank and single-screen mirroring picked by bit 4.
+pub struct MapperAxROM {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    bus_conflicts: bool,
+    m_select: Byte,
+}
+
+impl MapperAxROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        // Only AMROM has bus conflicts, ANROM and AOROM do not
+        Self::with_bus_conflicts(cartridge, false)
+    }
+
+    /// Overrides whether the board has bus conflicts, see `mapper::bus_conflict`.
+    pub fn with_bus_conflicts(cartridge: Cartridge, bus_conflicts: bool) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperAxROM {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            bus_conflicts,
+            m_select: 0,
+        }
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+}
+
+impl Mapper for MapperAxROM {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        let rom = self.cartridge.get_rom();
+        let bank = (self.m_select & 0x7) as usize;
+        rom[(bank * 0x8000 + (addr & 0x7fff) as usize) % rom.len()]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        self.m_select = if self.bus_conflicts { bus_conflict(value, self.peek_prg(addr)) } else { value };
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[addr as usize % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = addr as usize % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        if self.m_select & 0x10 == 0 {
+            NameTableMirroring::OneScreenLower
+        } else {
+            NameTableMirroring::OneScreenHigher
+        }
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        self.cartridge.has_extended_ram()
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![self.m_select];
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        if state.len() != 1 + self.character_ram.len() {
+            return Err("AxROM state has the wrong size.".to_string());
+        }
+        self.m_select = state[0];
+        self.character_ram.copy_from_slice(&state[1..]);
+        Ok(())
+    }
+}
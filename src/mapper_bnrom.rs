@@ -0,0 +1,99 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::bus_conflict;
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+/// Mapper 34, BNROM: a switchable 32KB PRG bank and 8KB of CHR-RAM. The NINA-001
+/// board that shares the mapper number, with its registers at $7FFD-$7FFF, is not supported.
+pub struct MapperBNROM {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    bus_conflicts: bool,
+    m_select: Byte,
+}
+
+impl MapperBNROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self::with_bus_conflicts(cartridge, true)
+    }
+
+    /// Overrides whether the board has bus conflicts, see `mapper::bus_conflict`.
+    pub fn with_bus_conflicts(cartridge: Cartridge, bus_conflicts: bool) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperBNROM {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            bus_conflicts,
+            m_select: 0,
+        }
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+}
+
+impl Mapper for MapperBNROM {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        let rom = self.cartridge.get_rom();
+        let bank = self.m_select as usize;
+        rom[(bank * 0x8000 + (addr & 0x7fff) as usize) % rom.len()]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        self.m_select = if self.bus_conflicts { bus_conflict(value, self.peek_prg(addr)) } else { value };
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[addr as usize % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = addr as usize % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        self.cartridge.get_name_table_mirroring()
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        self.cartridge.has_extended_ram()
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![self.m_select];
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        if state.len() != 1 + self.character_ram.len() {
+            return Err("BNROM state has the wrong size.".to_string());
+        }
+        self.m_select = state[0];
+        self.character_ram.copy_from_slice(&state[1..]);
+        Ok(())
+    }
+}
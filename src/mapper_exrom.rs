@@ -0,0 +1,535 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+/// How many CPU cycles without a PPU fetch end the frame for in-frame detection.
+const IDLE_CYCLES: u8 = 3;
+
+/// PPU fetches in a scanline, counted from the nametable fetch that got it detected:
+/// tiles 2-33, then the sprite patterns, then tiles 0-1 of the next line.
+const SPRITE_FETCHES_START: u16 = 128;
+const PREFETCH_START: u16 = 160;
+const PREFETCH_END: u16 = 168;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PrgTarget {
+    Rom(usize),
+    Ram(usize),
+}
+
+/// Mapper 5, MMC5. PRG is banked in 8KB-32KB units with RAM or ROM in most windows,
+/// CHR in 1KB-8KB units with separate sprite and background sets for 8x16 sprites,
+/// and 1KB of ExRAM serves as an extra nametable, per-tile attributes and banks,
+/// split screen data or plain RAM. The chip follows rendering by watching PPU fetches:
+/// three fetches of the same nametable address end a scanline.
+pub struct MapperExROM {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    m_prg_mode: Byte,
+    m_chr_mode: Byte,
+    m_prg_ram_protect: [Byte; 2],
+    m_exram_mode: Byte,
+    m_name_table_mapping: Byte,
+    m_fill_tile: Byte,
+    m_fill_attribute: Byte,
+    // $5113-$5117
+    m_prg_banks: [Byte; 5],
+    // $5120-$5127, used by sprites, and by everything with 8x8 sprites
+    m_chr_banks_a: [Address; 8],
+    // $5128-$512B, used by the background with 8x16 sprites
+    m_chr_banks_b: [Address; 4],
+    m_chr_upper: Byte,
+    m_last_chr_set_b: bool,
+    m_split_control: Byte,
+    m_split_scroll: Byte,
+    m_split_bank: Byte,
+    m_irq_compare: Byte,
+    m_irq_enabled: bool,
+    m_irq_pending: bool,
+    m_multiplicand: Byte,
+    m_multiplier: Byte,
+    m_exram: [Byte; 0x400],
+    m_sprites_8x16: bool,
+    m_rendering_enabled: bool,
+    m_in_frame: bool,
+    m_scanline: Byte,
+    m_idle_cycles: u8,
+    m_last_fetch: Address,
+    m_matching_fetches: u8,
+    m_fetch_index: u16,
+    // nametable offset of the tile being fetched, for extended attributes
+    m_tile_offset: usize,
+    m_in_split: bool,
+}
+
+impl MapperExROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperExROM {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            m_prg_mode: 3,
+            m_chr_mode: 0,
+            m_prg_ram_protect: [0; 2],
+            m_exram_mode: 0,
+            m_name_table_mapping: 0,
+            m_fill_tile: 0,
+            m_fill_attribute: 0,
+            m_prg_banks: [0, 0, 0, 0, 0xff],
+            m_chr_banks_a: [0; 8],
+            m_chr_banks_b: [0; 4],
+            m_chr_upper: 0,
+            m_last_chr_set_b: false,
+            m_split_control: 0,
+            m_split_scroll: 0,
+            m_split_bank: 0,
+            m_irq_compare: 0,
+            m_irq_enabled: false,
+            m_irq_pending: false,
+            m_multiplicand: 0xff,
+            m_multiplier: 0xff,
+            m_exram: [0; 0x400],
+            m_sprites_8x16: false,
+            m_rendering_enabled: false,
+            m_in_frame: false,
+            m_scanline: 0,
+            m_idle_cycles: 0,
+            m_last_fetch: 0,
+            m_matching_fetches: 0,
+            m_fetch_index: u16::MAX,
+            m_tile_offset: 0,
+            m_in_split: false,
+        }
+    }
+
+    fn prg_target(&self, addr: Address) -> PrgTarget {
+        if addr < 0x8000 {
+            let bank = (self.m_prg_banks[0] & 0x07) as usize;
+            return PrgTarget::Ram(bank * 0x2000 + (addr & 0x1fff) as usize);
+        }
+        // (register, window size)
+        let (register, size) = match (self.m_prg_mode, addr) {
+            (0, _) => (4, 0x8000),
+            (1, 0x8000..=0xbfff) => (2, 0x4000),
+            (1, _) => (4, 0x4000),
+            (2, 0x8000..=0xbfff) => (2, 0x4000),
+            (2, 0xc000..=0xdfff) => (3, 0x2000),
+            (2, _) => (4, 0x2000),
+            (_, _) => (1 + ((addr - 0x8000) >> 13) as usize, 0x2000),
+        };
+        let value = self.m_prg_banks[register];
+        // Bits of the 8KB bank number below the window size are ignored
+        let bank = (value & 0x7f) as usize & !(size / 0x2000 - 1);
+        let offset = bank * 0x2000 + (addr as usize & (size - 1));
+        // $5117 always maps ROM
+        if register == 4 || value & 0x80 != 0 {
+            PrgTarget::Rom(offset)
+        } else {
+            PrgTarget::Ram(offset)
+        }
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.m_prg_ram_protect == [0x2, 0x1]
+    }
+
+    fn prg_ram_offset(&self, addr: Address, write: bool) -> Option<usize> {
+        match self.prg_target(addr) {
+            PrgTarget::Ram(offset) if !write || self.prg_ram_writable() => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// 1KB CHR bank for `addr` out of set A or B.
+    fn chr_bank(&self, addr: Address, set_b: bool) -> usize {
+        let slot = (addr >> 10) as usize & 0x7;
+        if set_b {
+            // Set B covers 4KB and repeats for both pattern tables
+            let slot = slot & 0x3;
+            match self.m_chr_mode {
+                0 => self.m_chr_banks_b[3] as usize * 8 + slot,
+                1 => self.m_chr_banks_b[3] as usize * 4 + slot,
+                2 => self.m_chr_banks_b[slot | 1] as usize * 2 + (slot & 1),
+                _ => self.m_chr_banks_b[slot] as usize,
+            }
+        } else {
+            match self.m_chr_mode {
+                0 => self.m_chr_banks_a[7] as usize * 8 + slot,
+                1 => self.m_chr_banks_a[slot | 3] as usize * 4 + (slot & 3),
+                2 => self.m_chr_banks_a[slot | 1] as usize * 2 + (slot & 1),
+                _ => self.m_chr_banks_a[slot] as usize,
+            }
+        }
+    }
+
+    fn chr_offset(&self, addr: Address, set_b: bool) -> usize {
+        self.chr_bank(addr, set_b) * 0x400 + (addr & 0x3ff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+
+    fn chr_at(&self, offset: usize) -> Byte {
+        let chr = self.chr();
+        chr[offset % chr.len()]
+    }
+
+    /// Tile column being fetched, and whether it belongs to the next scanline.
+    fn background_tile(&self) -> Option<(usize, bool)> {
+        match self.m_fetch_index {
+            index if index < SPRITE_FETCHES_START => Some((index as usize / 4 + 2, false)),
+            index if (PREFETCH_START..PREFETCH_END).contains(&index) => {
+                Some(((index - PREFETCH_START) as usize / 4, true))
+            }
+            _ => None,
+        }
+    }
+
+    fn split_active(&self) -> bool {
+        self.m_split_control & 0x80 != 0 && self.m_exram_mode <= 1
+    }
+
+    /// Row of the split screen being drawn, in pixels.
+    fn split_y(&self, next_line: bool) -> usize {
+        let y = self.m_split_scroll as usize + self.m_scanline as usize + next_line as usize;
+        y % 240
+    }
+
+    /// Tracks PPU fetches: scanline detection, in-frame state and where in the scanline
+    /// the PPU is.
+    fn fetch(&mut self, addr: Address) {
+        self.m_idle_cycles = 0;
+        self.m_fetch_index = self.m_fetch_index.saturating_add(1);
+        if (0x2000..0x3000).contains(&addr) && addr == self.m_last_fetch {
+            self.m_matching_fetches += 1;
+            if self.m_matching_fetches == 2 {
+                self.start_scanline();
+            }
+        } else {
+            self.m_matching_fetches = 0;
+        }
+        self.m_last_fetch = addr;
+    }
+
+    fn start_scanline(&mut self) {
+        if self.m_in_frame {
+            self.m_scanline = self.m_scanline.wrapping_add(1);
+            if self.m_scanline == self.m_irq_compare && self.m_irq_compare != 0 {
+                self.m_irq_pending = true;
+            }
+        } else {
+            self.m_in_frame = true;
+            self.m_scanline = 0;
+        }
+        self.m_fetch_index = 0;
+        self.m_matching_fetches = 0;
+    }
+
+    fn leave_frame(&mut self) {
+        self.m_in_frame = false;
+        self.m_fetch_index = u16::MAX;
+    }
+
+    /// What a nametable fetch returns, after split screen, extended attributes and the
+    /// per-nametable mapping.
+    fn name_table_value(&self, addr: Address, ciram: &[Byte]) -> Byte {
+        let offset = (addr & 0x3ff) as usize;
+        let is_attribute = offset >= 0x3c0;
+        if self.m_in_frame && self.split_active() && self.m_in_split {
+            if let Some((tile, next_line)) = self.background_tile() {
+                let y = self.split_y(next_line);
+                if !is_attribute {
+                    return self.m_exram[(y / 8) * 32 + tile];
+                }
+                let attribute = self.m_exram[0x3c0 + (y / 32) * 8 + tile / 4];
+                let shift = ((y / 16) & 1) * 4 + ((tile / 2) & 1) * 2;
+                return ((attribute >> shift) & 0x3) * 0x55;
+            }
+        }
+        if is_attribute && self.m_exram_mode == 1 && self.m_in_frame {
+            // Extended attributes: the palette comes from ExRAM, for every tile
+            return (self.m_exram[self.m_tile_offset] >> 6) * 0x55;
+        }
+
+        let source = (self.m_name_table_mapping >> (((addr >> 10) & 0x3) * 2)) & 0x3;
+        match source {
+            0 | 1 => ciram[source as usize * 0x400 + offset],
+            2 if self.m_exram_mode <= 1 => self.m_exram[offset],
+            2 => 0,
+            _ if is_attribute => (self.m_fill_attribute & 0x3) * 0x55,
+            _ => self.m_fill_tile,
+        }
+    }
+
+    fn pattern_value(&self, addr: Address) -> Byte {
+        let sprite_fetch = (SPRITE_FETCHES_START..PREFETCH_START).contains(&self.m_fetch_index);
+        if !self.m_in_frame {
+            // $2007 access: the last written set
+            return self.chr_at(self.chr_offset(addr, self.m_last_chr_set_b && self.m_sprites_8x16));
+        }
+        if !sprite_fetch {
+            if self.split_active() && self.m_in_split {
+                if let Some((_, next_line)) = self.background_tile() {
+                    let fine_y = self.split_y(next_line) & 0x7;
+                    let offset = (addr & 0xff8) as usize | fine_y;
+                    return self.chr_at(self.m_split_bank as usize * 0x1000 + offset);
+                }
+            }
+            if self.m_exram_mode == 1 {
+                let bank = (self.m_exram[self.m_tile_offset] & 0x3f) as usize
+                    | (self.m_chr_upper as usize) << 6;
+                return self.chr_at(bank * 0x1000 + (addr & 0xfff) as usize);
+            }
+        }
+        let set_b = self.m_sprites_8x16 && !sprite_fetch;
+        self.chr_at(self.chr_offset(addr, set_b))
+    }
+}
+
+impl Mapper for MapperExROM {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        let rom = self.cartridge.get_rom();
+        match self.prg_target(addr) {
+            PrgTarget::Rom(offset) => rom[offset % rom.len()],
+            // RAM windows are served by the bus through `prg_ram_over_rom`
+            PrgTarget::Ram(_) => 0,
+        }
+    }
+
+    fn write_prg(&mut self, _addr: Address, _value: Byte) {}
+
+    fn read_chr(&mut self, addr: Address) -> Byte {
+        self.fetch(addr);
+        self.pattern_value(addr)
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        self.pattern_value(addr)
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr, false) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    /// Only a summary, the real mapping is per nametable through `read_name_table`.
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        match self.m_name_table_mapping {
+            0x00 => NameTableMirroring::OneScreenLower,
+            0x55 => NameTableMirroring::OneScreenHigher,
+            0x44 => NameTableMirroring::Vertical,
+            0x50 => NameTableMirroring::Horizontal,
+            _ => NameTableMirroring::FourScreen,
+        }
+    }
+
+    fn read_name_table(&mut self, addr: Address, ciram: &[Byte]) -> Byte {
+        self.fetch(addr);
+        if (addr & 0x3ff) < 0x3c0 {
+            // A name fetch starts a tile: note where, and whether it is in the split
+            self.m_tile_offset = (addr & 0x3ff) as usize;
+            self.m_in_split = match self.background_tile() {
+                Some((tile, _)) => {
+                    let threshold = (self.m_split_control & 0x1f) as usize;
+                    if self.m_split_control & 0x40 != 0 { tile >= threshold } else { tile < threshold }
+                }
+                None => false,
+            };
+        }
+        self.name_table_value(addr, ciram)
+    }
+
+    fn peek_name_table(&self, addr: Address, ciram: &[Byte]) -> Byte {
+        self.name_table_value(addr, ciram)
+    }
+
+    fn write_name_table(&mut self, addr: Address, value: Byte, ciram: &mut [Byte]) {
+        let source = (self.m_name_table_mapping >> (((addr >> 10) & 0x3) * 2)) & 0x3;
+        let offset = (addr & 0x3ff) as usize;
+        match source {
+            0 | 1 => ciram[source as usize * 0x400 + offset] = value,
+            2 if self.m_exram_mode <= 1 => self.m_exram[offset] = value,
+            _ => (),
+        }
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        true
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn prg_ram_address(&self, addr: Address, write: bool) -> Option<usize> {
+        self.prg_ram_offset(addr, write)
+    }
+
+    fn prg_ram_over_rom(&self, addr: Address, write: bool) -> Option<usize> {
+        self.prg_ram_offset(addr, write)
+    }
+
+    fn peek_expansion(&self, addr: Address) -> Option<Byte> {
+        match addr {
+            0x5204 => Some((self.m_irq_pending as Byte) << 7 | (self.m_in_frame as Byte) << 6),
+            0x5205 => Some((self.m_multiplicand as u16 * self.m_multiplier as u16) as Byte),
+            0x5206 => Some(((self.m_multiplicand as u16 * self.m_multiplier as u16) >> 8) as Byte),
+            // ExRAM is only readable in RAM modes
+            0x5c00..=0x5fff if self.m_exram_mode >= 2 => Some(self.m_exram[(addr - 0x5c00) as usize]),
+            _ => None,
+        }
+    }
+
+    fn read_expansion(&mut self, addr: Address) -> Option<Byte> {
+        let value = self.peek_expansion(addr);
+        if addr == 0x5204 {
+            self.m_irq_pending = false;
+        }
+        value
+    }
+
+    fn write_expansion(&mut self, addr: Address, value: Byte) {
+        match addr {
+            0x5100 => self.m_prg_mode = value & 0x3,
+            0x5101 => self.m_chr_mode = value & 0x3,
+            0x5102 => self.m_prg_ram_protect[0] = value & 0x3,
+            0x5103 => self.m_prg_ram_protect[1] = value & 0x3,
+            0x5104 => self.m_exram_mode = value & 0x3,
+            0x5105 => self.m_name_table_mapping = value,
+            0x5106 => self.m_fill_tile = value,
+            0x5107 => self.m_fill_attribute = value & 0x3,
+            0x5113..=0x5117 => self.m_prg_banks[(addr - 0x5113) as usize] = value,
+            0x5120..=0x5127 => {
+                self.m_chr_banks_a[(addr - 0x5120) as usize] = value as Address | (self.m_chr_upper as Address) << 8;
+                self.m_last_chr_set_b = false;
+            }
+            0x5128..=0x512b => {
+                self.m_chr_banks_b[(addr - 0x5128) as usize] = value as Address | (self.m_chr_upper as Address) << 8;
+                self.m_last_chr_set_b = true;
+            }
+            0x5130 => self.m_chr_upper = value & 0x3,
+            0x5200 => self.m_split_control = value,
+            0x5201 => self.m_split_scroll = value,
+            0x5202 => self.m_split_bank = value,
+            0x5203 => self.m_irq_compare = value,
+            0x5204 => self.m_irq_enabled = value & 0x80 != 0,
+            0x5205 => self.m_multiplicand = value,
+            0x5206 => self.m_multiplier = value,
+            // Read-only in mode 3
+            0x5c00..=0x5fff if self.m_exram_mode != 3 => self.m_exram[(addr - 0x5c00) as usize] = value,
+            _ => (),
+        }
+    }
+
+    fn ppu_register_write(&mut self, addr: Address, value: Byte) {
+        match addr & 0x7 {
+            0 => self.m_sprites_8x16 = value & 0x20 != 0,
+            1 => {
+                self.m_rendering_enabled = value & 0x18 != 0;
+                if !self.m_rendering_enabled {
+                    self.leave_frame();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn irq_line(&self) -> bool {
+        self.m_irq_pending && self.m_irq_enabled
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.m_in_frame {
+            self.m_idle_cycles += 1;
+            if self.m_idle_cycles >= IDLE_CYCLES {
+                self.leave_frame();
+            }
+        }
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![
+            self.m_prg_mode,
+            self.m_chr_mode,
+            self.m_prg_ram_protect[0],
+            self.m_prg_ram_protect[1],
+            self.m_exram_mode,
+            self.m_name_table_mapping,
+            self.m_fill_tile,
+            self.m_fill_attribute,
+            self.m_chr_upper,
+            self.m_last_chr_set_b as Byte,
+            self.m_split_control,
+            self.m_split_scroll,
+            self.m_split_bank,
+            self.m_irq_compare,
+            self.m_irq_enabled as Byte,
+            self.m_irq_pending as Byte,
+            self.m_multiplicand,
+            self.m_multiplier,
+            self.m_sprites_8x16 as Byte,
+            self.m_rendering_enabled as Byte,
+        ];
+        state.extend_from_slice(&self.m_prg_banks);
+        for bank in self.m_chr_banks_a.iter().chain(self.m_chr_banks_b.iter()) {
+            state.extend_from_slice(&bank.to_le_bytes());
+        }
+        state.extend_from_slice(&self.m_exram);
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        const HEADER: usize = 20 + 5 + 24;
+        if state.len() != HEADER + 0x400 + self.character_ram.len() {
+            return Err("MMC5 state has the wrong size.".to_string());
+        }
+        self.m_prg_mode = state[0];
+        self.m_chr_mode = state[1];
+        self.m_prg_ram_protect = [state[2], state[3]];
+        self.m_exram_mode = state[4];
+        self.m_name_table_mapping = state[5];
+        self.m_fill_tile = state[6];
+        self.m_fill_attribute = state[7];
+        self.m_chr_upper = state[8];
+        self.m_last_chr_set_b = state[9] != 0;
+        self.m_split_control = state[10];
+        self.m_split_scroll = state[11];
+        self.m_split_bank = state[12];
+        self.m_irq_compare = state[13];
+        self.m_irq_enabled = state[14] != 0;
+        self.m_irq_pending = state[15] != 0;
+        self.m_multiplicand = state[16];
+        self.m_multiplier = state[17];
+        self.m_sprites_8x16 = state[18] != 0;
+        self.m_rendering_enabled = state[19] != 0;
+        self.m_prg_banks.copy_from_slice(&state[20..25]);
+        let banks: Vec<Address> = state[25..HEADER]
+            .chunks(2)
+            .map(|bytes| Address::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        self.m_chr_banks_a.copy_from_slice(&banks[..8]);
+        self.m_chr_banks_b.copy_from_slice(&banks[8..]);
+        self.m_exram.copy_from_slice(&state[HEADER..HEADER + 0x400]);
+        self.character_ram.copy_from_slice(&state[HEADER + 0x400..]);
+        self.leave_frame();
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+use sunsoft5b_audio::Sunsoft5bAudio;
+use sunsoft5b_audio::SUNSOFT5B_AUDIO_STATE_SIZE;
+use crate::sunsoft5b_audio;
+
+/// Mapper 69, Sunsoft FME-7 and 5B. Sixteen registers selected through $8000 and
+/// written through $A000: eight 1KB CHR banks, four 8KB PRG banks (the one at $6000
+/// can be ROM or RAM), mirroring and a 16-bit IRQ counter decremented every CPU cycle.
+/// The 5B adds three channels of audio at $C000/$E000.
+pub struct MapperFME7 {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    m_command: Byte,
+    m_chr_banks: [Byte; 8],
+    // $6000, $8000, $A000, $C000
+    m_prg_banks: [Byte; 4],
+    m_mirroring: Byte,
+    m_irq_enabled: bool,
+    m_counter_enabled: bool,
+    m_irq_counter: u16,
+    m_irq_pending: bool,
+    m_audio: Sunsoft5bAudio,
+}
+
+impl MapperFME7 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperFME7 {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            m_command: 0,
+            m_chr_banks: [0; 8],
+            m_prg_banks: [0; 4],
+            m_mirroring: 0,
+            m_irq_enabled: false,
+            m_counter_enabled: false,
+            m_irq_counter: 0,
+            m_irq_pending: false,
+            m_audio: Sunsoft5bAudio::new(),
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let banks = self.cartridge.get_rom().len() / 0x2000;
+        let bank = match addr {
+            0xe000..=0xffff => banks - 1,
+            _ => (self.m_prg_banks[((addr - 0x6000) >> 13) as usize] & 0x3f) as usize % banks,
+        };
+        bank * 0x2000 + (addr & 0x1fff) as usize
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        self.m_chr_banks[(addr >> 10) as usize & 0x7] as usize * 0x400 + (addr & 0x3ff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+
+    fn write_register(&mut self, value: Byte) {
+        match self.m_command {
+            0x0..=0x7 => self.m_chr_banks[self.m_command as usize] = value,
+            0x8..=0xb => self.m_prg_banks[(self.m_command - 0x8) as usize] = value,
+            0xc => self.m_mirroring = value & 0x3,
+            0xd => {
+                self.m_irq_enabled = value & 0x01 != 0;
+                self.m_counter_enabled = value & 0x80 != 0;
+                self.m_irq_pending = false;
+            }
+            0xe => self.m_irq_counter = (self.m_irq_counter & 0xff00) | value as u16,
+            _ => self.m_irq_counter = (self.m_irq_counter & 0x00ff) | (value as u16) << 8,
+        }
+    }
+}
+
+impl Mapper for MapperFME7 {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        match addr {
+            0x8000..=0x9fff => self.m_command = value & 0x0f,
+            0xa000..=0xbfff => self.write_register(value),
+            0xc000..=0xdfff => self.m_audio.write_address(value),
+            _ => self.m_audio.write_data(value),
+        }
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[self.chr_offset(addr) % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        match self.m_mirroring {
+            0 => NameTableMirroring::Vertical,
+            1 => NameTableMirroring::Horizontal,
+            2 => NameTableMirroring::OneScreenLower,
+            _ => NameTableMirroring::OneScreenHigher,
+        }
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        true
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    // Bit 6 of the $6000 bank maps RAM instead of ROM, bit 7 enables that RAM
+    fn prg_ram_address(&self, addr: Address, _write: bool) -> Option<usize> {
+        let bank = self.m_prg_banks[0];
+        if bank & 0xc0 != 0xc0 {
+            return None;
+        }
+        Some((bank & 0x3f) as usize * 0x2000 + (addr & 0x1fff) as usize)
+    }
+
+    fn peek_expansion(&self, addr: Address) -> Option<Byte> {
+        // ROM at $6000
+        if addr >= 0x6000 && self.m_prg_banks[0] & 0x40 == 0 {
+            return Some(self.peek_prg(addr));
+        }
+        None
+    }
+
+    fn irq_line(&self) -> bool {
+        self.m_irq_pending
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.m_counter_enabled {
+            self.m_irq_counter = self.m_irq_counter.wrapping_sub(1);
+            if self.m_irq_counter == 0xffff && self.m_irq_enabled {
+                self.m_irq_pending = true;
+            }
+        }
+        self.m_audio.cpu_cycle();
+    }
+
+    fn audio_channels(&self) -> &[f32] {
+        self.m_audio.channels()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![
+            self.m_command,
+            self.m_mirroring,
+            self.m_irq_enabled as Byte,
+            self.m_counter_enabled as Byte,
+            self.m_irq_pending as Byte,
+        ];
+        state.extend_from_slice(&self.m_irq_counter.to_le_bytes());
+        state.extend_from_slice(&self.m_chr_banks);
+        state.extend_from_slice(&self.m_prg_banks);
+        state.extend_from_slice(&self.m_audio.save_state());
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        const AUDIO: usize = 7 + 8 + 4;
+        const HEADER: usize = AUDIO + SUNSOFT5B_AUDIO_STATE_SIZE;
+        if state.len() != HEADER + self.character_ram.len() {
+            return Err("FME-7 state has the wrong size.".to_string());
+        }
+        self.m_command = state[0];
+        self.m_mirroring = state[1];
+        self.m_irq_enabled = state[2] != 0;
+        self.m_counter_enabled = state[3] != 0;
+        self.m_irq_pending = state[4] != 0;
+        self.m_irq_counter = u16::from_le_bytes([state[5], state[6]]);
+        self.m_chr_banks.copy_from_slice(&state[7..15]);
+        self.m_prg_banks.copy_from_slice(&state[15..AUDIO]);
+        self.m_audio.load_state(&state[AUDIO..HEADER]);
+        self.character_ram.copy_from_slice(&state[HEADER..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+use namco163_audio::Namco163Audio;
+use namco163_audio::NAMCO163_AUDIO_STATE_SIZE;
+use crate::namco163_audio;
+
+/// Mapper 19, Namco 163. Three switchable 8KB PRG banks, eight 1KB CHR banks, four
+/// nametable banks that pick a CIRAM page ($E0-$FF) or a CHR-ROM bank, a 15-bit IRQ
+/// counter and up to eight channels of wavetable audio. The audio's 128 bytes of RAM
+/// are battery-backed along with PRG-RAM on battery boards. CHR banks $E0-$FF always
+/// read CHR-ROM; using CIRAM as pattern memory isn't supported.
+pub struct MapperNamco163 {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    m_prg_banks: [Byte; 3],
+    m_chr_banks: [Byte; 8],
+    m_name_table_banks: [Byte; 4],
+    m_sound_disabled: bool,
+    // $F800, also the PRG-RAM write protection
+    m_write_protect: Byte,
+    m_irq_counter: u16,
+    m_irq_enabled: bool,
+    m_irq_pending: bool,
+    m_audio: Namco163Audio,
+}
+
+impl MapperNamco163 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperNamco163 {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            m_prg_banks: [0; 3],
+            m_chr_banks: [0; 8],
+            m_name_table_banks: [0xe0, 0xe1, 0xe0, 0xe1],
+            m_sound_disabled: false,
+            m_write_protect: 0,
+            m_irq_counter: 0,
+            m_irq_enabled: false,
+            m_irq_pending: false,
+            m_audio: Namco163Audio::new(),
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let banks = self.cartridge.get_rom().len() / 0x2000;
+        let bank = match (addr >> 13) & 0x3 {
+            3 => banks - 1,
+            slot => self.m_prg_banks[slot as usize] as usize % banks,
+        };
+        bank * 0x2000 + (addr & 0x1fff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+
+    fn chr_at(&self, bank: Byte, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[(bank as usize * 0x400 + (addr & 0x3ff) as usize) % chr.len()]
+    }
+
+    /// CIRAM page for nametable `addr`, `None` if it is mapped to CHR-ROM.
+    fn ciram_page(&self, addr: Address) -> Option<usize> {
+        let bank = self.m_name_table_banks[(addr >> 10) as usize & 0x3];
+        if bank >= 0xe0 {
+            Some((bank & 0x1) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl Mapper for MapperNamco163 {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        match addr {
+            0x8000..=0xbfff => self.m_chr_banks[((addr - 0x8000) >> 11) as usize] = value,
+            0xc000..=0xdfff => self.m_name_table_banks[((addr - 0xc000) >> 11) as usize] = value,
+            0xe000..=0xe7ff => {
+                self.m_prg_banks[0] = value & 0x3f;
+                self.m_sound_disabled = value & 0x40 != 0;
+            }
+            0xe800..=0xefff => self.m_prg_banks[1] = value & 0x3f,
+            0xf000..=0xf7ff => self.m_prg_banks[2] = value & 0x3f,
+            _ => {
+                self.m_write_protect = value;
+                self.m_audio.write_address(value);
+            }
+        }
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        self.chr_at(self.m_chr_banks[(addr >> 10) as usize & 0x7], addr)
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let bank = self.m_chr_banks[(addr >> 10) as usize & 0x7];
+            let index = (bank as usize * 0x400 + (addr & 0x3ff) as usize) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    /// Only a summary, nametables can also come from CHR-ROM.
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        if self.m_name_table_banks.iter().any(|&bank| bank < 0xe0) {
+            return NameTableMirroring::FourScreen;
+        }
+        match self.m_name_table_banks.map(|bank| bank & 0x1) {
+            [0, 1, 0, 1] => NameTableMirroring::Vertical,
+            [0, 0, 1, 1] => NameTableMirroring::Horizontal,
+            [0, 0, 0, 0] => NameTableMirroring::OneScreenLower,
+            [1, 1, 1, 1] => NameTableMirroring::OneScreenHigher,
+            _ => NameTableMirroring::FourScreen,
+        }
+    }
+
+    fn peek_name_table(&self, addr: Address, ciram: &[Byte]) -> Byte {
+        match self.ciram_page(addr) {
+            Some(page) => ciram[page * 0x400 + (addr & 0x3ff) as usize],
+            None => self.chr_at(self.m_name_table_banks[(addr >> 10) as usize & 0x3], addr),
+        }
+    }
+
+    fn write_name_table(&mut self, addr: Address, value: Byte, ciram: &mut [Byte]) {
+        if let Some(page) = self.ciram_page(addr) {
+            ciram[page * 0x400 + (addr & 0x3ff) as usize] = value;
+        }
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        self.cartridge.has_extended_ram()
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    // Writes need $4x in the upper nibble of $F800 and the 2KB window's bit clear
+    fn prg_ram_address(&self, addr: Address, write: bool) -> Option<usize> {
+        let offset = (addr - 0x6000) as usize;
+        let protected = self.m_write_protect & 0xf0 != 0x40 || self.m_write_protect & (1 << (offset >> 11)) != 0;
+        if write && protected {
+            return None;
+        }
+        Some(offset)
+    }
+
+    fn peek_expansion(&self, addr: Address) -> Option<Byte> {
+        match addr {
+            0x4800..=0x4fff => Some(self.m_audio.peek_data()),
+            0x5000..=0x57ff => Some(self.m_irq_counter as Byte),
+            0x5800..=0x5fff => Some((self.m_irq_counter >> 8) as Byte | (self.m_irq_enabled as Byte) << 7),
+            _ => None,
+        }
+    }
+
+    fn read_expansion(&mut self, addr: Address) -> Option<Byte> {
+        match addr {
+            0x4800..=0x4fff => Some(self.m_audio.read_data()),
+            _ => self.peek_expansion(addr),
+        }
+    }
+
+    fn write_expansion(&mut self, addr: Address, value: Byte) {
+        match addr {
+            0x4800..=0x4fff => self.m_audio.write_data(value),
+            0x5000..=0x57ff => {
+                self.m_irq_counter = (self.m_irq_counter & 0x7f00) | value as u16;
+                self.m_irq_pending = false;
+            }
+            0x5800..=0x5fff => {
+                self.m_irq_counter = (self.m_irq_counter & 0x00ff) | ((value & 0x7f) as u16) << 8;
+                self.m_irq_enabled = value & 0x80 != 0;
+                self.m_irq_pending = false;
+            }
+            _ => (),
+        }
+    }
+
+    fn irq_line(&self) -> bool {
+        self.m_irq_pending
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.m_irq_enabled && self.m_irq_counter < 0x7fff {
+            self.m_irq_counter += 1;
+            if self.m_irq_counter == 0x7fff {
+                self.m_irq_pending = true;
+            }
+        }
+        if !self.m_sound_disabled {
+            self.m_audio.cpu_cycle();
+        }
+    }
+
+    fn audio_channels(&self) -> &[f32] {
+        self.m_audio.channels()
+    }
+
+    fn battery_ram(&self) -> &[Byte] {
+        if self.cartridge.has_battery() {
+            self.m_audio.ram()
+        } else {
+            &[]
+        }
+    }
+
+    fn load_battery_ram(&mut self, data: &[Byte]) {
+        if self.cartridge.has_battery() {
+            self.m_audio.load_ram(data);
+        }
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = self.m_prg_banks.to_vec();
+        state.extend_from_slice(&self.m_chr_banks);
+        state.extend_from_slice(&self.m_name_table_banks);
+        state.extend_from_slice(&[
+            self.m_sound_disabled as Byte,
+            self.m_write_protect,
+            self.m_irq_enabled as Byte,
+            self.m_irq_pending as Byte,
+        ]);
+        state.extend_from_slice(&self.m_irq_counter.to_le_bytes());
+        state.extend_from_slice(&self.m_audio.save_state());
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        const AUDIO: usize = 3 + 8 + 4 + 4 + 2;
+        const HEADER: usize = AUDIO + NAMCO163_AUDIO_STATE_SIZE;
+        if state.len() != HEADER + self.character_ram.len() {
+            return Err("Namco 163 state has the wrong size.".to_string());
+        }
+        self.m_prg_banks.copy_from_slice(&state[0..3]);
+        self.m_chr_banks.copy_from_slice(&state[3..11]);
+        self.m_name_table_banks.copy_from_slice(&state[11..15]);
+        self.m_sound_disabled = state[15] != 0;
+        self.m_write_protect = state[16];
+        self.m_irq_enabled = state[17] != 0;
+        self.m_irq_pending = state[18] != 0;
+        self.m_irq_counter = u16::from_le_bytes([state[19], state[20]]);
+        self.m_audio.load_state(&state[AUDIO..HEADER]);
+        self.character_ram.copy_from_slice(&state[HEADER..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+/// Mapper 0: 16KB or 32KB of PRG-ROM and 8KB of CHR, no bank switching.
+pub struct MapperNROM {
+    cartridge: Cartridge,
+    one_bank: bool,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+}
+
+impl MapperNROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let one_bank = cartridge.get_rom().len() == 0x4000;
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperNROM {
+            cartridge,
+            one_bank,
+            uses_character_ram,
+            character_ram,
+        }
+    }
+}
+
+impl Mapper for MapperNROM {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        if !self.one_bank {
+            let index = (addr - 0x8000) as usize;
+            self.cartridge.get_rom()[index]
+        } else {
+            let index = ((addr - 0x8000) & 0x3fff) as usize;
+            self.cartridge.get_rom()[index]
+        }
+    }
+
+    // No registers, writes to ROM are ignored
+    fn write_prg(&mut self, _addr: Address, _value: Byte) {}
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        if self.uses_character_ram {
+            self.character_ram[addr as usize]
+        } else {
+            self.cartridge.get_vrom()[addr as usize]
+        }
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            self.character_ram[addr as usize] = value
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        self.cartridge.get_name_table_mirroring()
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        self.cartridge.has_extended_ram()
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        self.character_ram.clone()
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        if state.len() != self.character_ram.len() {
+            return Err("NROM state has the wrong size.".to_string());
+        }
+        self.character_ram.copy_from_slice(state);
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+const LATCH_FD: usize = 0;
+const LATCH_FE: usize = 1;
+
+/// Mapper 9, MMC2 (PxROM), and mapper 10, MMC4 (FxROM). Each 4KB half of CHR has an
+/// $FD and an $FE bank; which one is mapped is decided by a latch the PPU flips by
+/// fetching tile $FD or $FE from that half. MMC2 switches 8KB of PRG at $8000, MMC4
+/// 16KB.
+pub struct MapperPxROM {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    m_mmc4: bool,
+    m_prg_bank: Byte,
+    // [half][latch]
+    m_chr_banks: [[Byte; 2]; 2],
+    m_latches: [usize; 2],
+    m_mirroring: Byte,
+}
+
+impl MapperPxROM {
+    /// MMC2
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self::with_chip(cartridge, false)
+    }
+
+    /// MMC4
+    pub fn new_fxrom(cartridge: Cartridge) -> Self {
+        Self::with_chip(cartridge, true)
+    }
+
+    fn with_chip(cartridge: Cartridge, mmc4: bool) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperPxROM {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            m_mmc4: mmc4,
+            m_prg_bank: 0,
+            m_chr_banks: [[0; 2]; 2],
+            m_latches: [LATCH_FE; 2],
+            m_mirroring: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let rom_size = self.cartridge.get_rom().len();
+        let bank_size = if self.m_mmc4 { 0x4000 } else { 0x2000 };
+        let banks = (rom_size / bank_size).max(1);
+        let slots = 0x8000 / bank_size;
+        let slot = (addr as usize - 0x8000) / bank_size;
+        let bank = if slot == 0 {
+            self.m_prg_bank as usize
+        } else {
+            // The rest is fixed to the last banks
+            (banks * slots + slot - slots) % banks
+        };
+        (bank * bank_size + (addr as usize & (bank_size - 1))) % rom_size
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        let half = (addr >> 12) as usize & 0x1;
+        let bank = self.m_chr_banks[half][self.m_latches[half]];
+        bank as usize * 0x1000 + (addr & 0xfff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+}
+
+impl Mapper for MapperPxROM {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        match addr {
+            0xa000..=0xafff => self.m_prg_bank = value & 0x0f,
+            0xb000..=0xbfff => self.m_chr_banks[0][LATCH_FD] = value & 0x1f,
+            0xc000..=0xcfff => self.m_chr_banks[0][LATCH_FE] = value & 0x1f,
+            0xd000..=0xdfff => self.m_chr_banks[1][LATCH_FD] = value & 0x1f,
+            0xe000..=0xefff => self.m_chr_banks[1][LATCH_FE] = value & 0x1f,
+            0xf000..=0xffff => self.m_mirroring = value & 0x1,
+            _ => (),
+        }
+    }
+
+    fn read_chr(&mut self, addr: Address) -> Byte {
+        let value = self.peek_chr(addr);
+        // The latch flips after the fetch, so the $FD/$FE tile itself still comes
+        // from the old bank. MMC2 only reacts to $0FD8/$0FE8 in the lower half.
+        let half = (addr >> 12) as usize & 0x1;
+        let exact = half == 0 && !self.m_mmc4;
+        match addr & 0xfff {
+            0xfd8 => self.m_latches[half] = LATCH_FD,
+            0xfe8 => self.m_latches[half] = LATCH_FE,
+            0xfd9..=0xfdf if !exact => self.m_latches[half] = LATCH_FD,
+            0xfe9..=0xfef if !exact => self.m_latches[half] = LATCH_FE,
+            _ => (),
+        }
+        value
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[self.chr_offset(addr) % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        if self.m_mirroring == 0 {
+            NameTableMirroring::Vertical
+        } else {
+            NameTableMirroring::Horizontal
+        }
+    }
+
+    // Only the MMC4 boards have PRG-RAM
+    fn has_extended_ram(&self) -> bool {
+        self.m_mmc4 || self.cartridge.has_extended_ram()
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![
+            self.m_prg_bank,
+            self.m_chr_banks[0][LATCH_FD],
+            self.m_chr_banks[0][LATCH_FE],
+            self.m_chr_banks[1][LATCH_FD],
+            self.m_chr_banks[1][LATCH_FE],
+            self.m_latches[0] as Byte,
+            self.m_latches[1] as Byte,
+            self.m_mirroring,
+        ];
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        if state.len() != 8 + self.character_ram.len() {
+            return Err("MMC2 state has the wrong size.".to_string());
+        }
+        self.m_prg_bank = state[0];
+        self.m_chr_banks = [[state[1], state[2]], [state[3], state[4]]];
+        self.m_latches = [state[5] as usize & 0x1, state[6] as usize & 0x1];
+        self.m_mirroring = state[7];
+        self.character_ram.copy_from_slice(&state[8..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+/// Mapper 1, MMC1. Registers are loaded one bit at a time through a 5-bit shift
+/// register at $8000-$FFFF; the address of the fifth write picks the register.
+/// SUROM/SXROM use CHR bank bit 4 to pick a 256KB PRG half, SOROM/SXROM use CHR
+/// bank bits 3-2 to bank 16KB/32KB of PRG-RAM.
+pub struct MapperSxROM {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    m_shift_register: Byte,
+    m_write_count: u8,
+    m_control: Byte,
+    m_chr_bank_0: Byte,
+    m_chr_bank_1: Byte,
+    m_prg_bank: Byte,
+    m_cycles: u64,
+    m_last_write: Option<u64>,
+}
+
+impl MapperSxROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperSxROM {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            m_shift_register: 0,
+            m_write_count: 0,
+            // Powers up with the last bank fixed at $C000, where the reset vector is
+            m_control: 0x0c,
+            m_chr_bank_0: 0,
+            m_chr_bank_1: 0,
+            m_prg_bank: 0,
+            m_cycles: 0,
+            m_last_write: None,
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let banks = self.cartridge.get_rom().len() / 0x4000;
+        // SUROM/SXROM: 512KB of PRG as two 256KB halves
+        let outer = if banks > 16 { self.m_chr_bank_0 & 0x10 } else { 0 };
+        let bank = self.m_prg_bank & 0x0f;
+        let bank = match (self.m_control >> 2) & 0x3 {
+            // 32KB at $8000, the low bit is ignored
+            0 | 1 => (bank & 0x0e) | (addr >= 0xc000) as Byte,
+            // First bank fixed at $8000
+            2 => if addr < 0xc000 { 0 } else { bank },
+            // Last bank fixed at $C000
+            _ => if addr < 0xc000 { bank } else { 0x0f },
+        };
+        ((outer | bank) as usize % banks) * 0x4000 + (addr & 0x3fff) as usize
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        let bank = if self.m_control & 0x10 != 0 {
+            if addr < 0x1000 { self.m_chr_bank_0 } else { self.m_chr_bank_1 }
+        } else {
+            // 8KB mode, the low bit is ignored
+            (self.m_chr_bank_0 & 0x1e) | (addr >= 0x1000) as Byte
+        };
+        bank as usize * 0x1000 + (addr & 0xfff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+}
+
+impl Mapper for MapperSxROM {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        // Only the first of writes on consecutive cycles (the two writes of a
+        // read-modify-write instruction) reaches the shift register
+        let consecutive = self.m_last_write.is_some_and(|last| self.m_cycles - last <= 1);
+        self.m_last_write = Some(self.m_cycles);
+        if consecutive {
+            return;
+        }
+
+        if value & 0x80 != 0 {
+            self.m_shift_register = 0;
+            self.m_write_count = 0;
+            self.m_control |= 0x0c;
+            return;
+        }
+
+        self.m_shift_register = (self.m_shift_register >> 1) | ((value & 0x1) << 4);
+        self.m_write_count += 1;
+        if self.m_write_count < 5 {
+            return;
+        }
+
+        let register = self.m_shift_register;
+        match addr {
+            0x8000..=0x9fff => self.m_control = register,
+            0xa000..=0xbfff => self.m_chr_bank_0 = register,
+            0xc000..=0xdfff => self.m_chr_bank_1 = register,
+            _ => self.m_prg_bank = register,
+        }
+        self.m_shift_register = 0;
+        self.m_write_count = 0;
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[self.chr_offset(addr) % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        match self.m_control & 0x3 {
+            0 => NameTableMirroring::OneScreenLower,
+            1 => NameTableMirroring::OneScreenHigher,
+            2 => NameTableMirroring::Vertical,
+            _ => NameTableMirroring::Horizontal,
+        }
+    }
+
+    // Nearly every MMC1 board has PRG-RAM, battery or not
+    fn has_extended_ram(&self) -> bool {
+        true
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn prg_ram_address(&self, addr: Address, _write: bool) -> Option<usize> {
+        if self.m_prg_bank & 0x10 != 0 {
+            return None;
+        }
+        let bank = match self.prg_ram_size() {
+            // SOROM
+            0x4000 => (self.m_chr_bank_0 >> 3) & 0x1,
+            // SXROM
+            0x8000 => (self.m_chr_bank_0 >> 2) & 0x3,
+            _ => 0,
+        };
+        Some(bank as usize * 0x2000 + (addr - 0x6000) as usize)
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.m_cycles += 1;
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![
+            self.m_shift_register,
+            self.m_write_count,
+            self.m_control,
+            self.m_chr_bank_0,
+            self.m_chr_bank_1,
+            self.m_prg_bank,
+        ];
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        if state.len() != 6 + self.character_ram.len() {
+            return Err("MMC1 state has the wrong size.".to_string());
+        }
+        self.m_shift_register = state[0];
+        self.m_write_count = state[1];
+        self.m_control = state[2];
+        self.m_chr_bank_0 = state[3];
+        self.m_chr_bank_1 = state[4];
+        self.m_prg_bank = state[5];
+        self.character_ram.copy_from_slice(&state[6..]);
+        Ok(())
+    }
+}
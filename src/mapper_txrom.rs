@@ -0,0 +1,245 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+/// The two behaviours of the MMC3 IRQ counter when it reaches or is reloaded with 0.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mmc3Revision {
+    /// Old MMC3A (NEC): only fires when the counter is decremented to 0, or reloaded
+    /// with 0 after a write to $C001
+    A,
+    /// MMC3B/C (Sharp): fires whenever the counter is 0 after being clocked
+    B,
+}
+
+/// How many CPU cycles A12 has to stay low before a rising edge clocks the counter,
+/// which filters out the fast toggling while the PPU fetches sprite patterns.
+const A12_LOW_CYCLES: u64 = 3;
+
+/// Mapper 4, MMC3. Eight bank registers selected through $8000 and written through
+/// $8001, 8KB PRG and 1KB/2KB CHR banks, and a scanline counter clocked by rising
+/// edges of PPU A12 that pulls IRQ low when it hits 0.
+pub struct MapperTxROM {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    m_revision: Mmc3Revision,
+    m_bank_select: Byte,
+    m_registers: [Byte; 8],
+    m_mirroring: Byte,
+    m_prg_ram_protect: Byte,
+    m_irq_latch: Byte,
+    m_irq_counter: Byte,
+    m_irq_reload: bool,
+    m_irq_enabled: bool,
+    m_irq_pending: bool,
+    m_cycles: u64,
+    // CPU cycle A12 went low on, `None` while it is high
+    m_a12_low_since: Option<u64>,
+}
+
+impl MapperTxROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        Self::with_revision(cartridge, Mmc3Revision::B)
+    }
+
+    pub fn with_revision(cartridge: Cartridge, revision: Mmc3Revision) -> Self {
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperTxROM {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            m_revision: revision,
+            m_bank_select: 0,
+            m_registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            m_mirroring: 0,
+            m_prg_ram_protect: 0x80,
+            m_irq_latch: 0,
+            m_irq_counter: 0,
+            m_irq_reload: false,
+            m_irq_enabled: false,
+            m_irq_pending: false,
+            m_cycles: 0,
+            m_a12_low_since: Some(0),
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let banks = self.cartridge.get_rom().len() / 0x2000;
+        let second_last = (banks + banks - 2) % banks;
+        let bank = match (addr >> 13) & 0x3 {
+            0 if self.m_bank_select & 0x40 != 0 => second_last,
+            0 => self.m_registers[6] as usize,
+            1 => self.m_registers[7] as usize,
+            2 if self.m_bank_select & 0x40 != 0 => self.m_registers[6] as usize,
+            2 => second_last,
+            _ => banks - 1,
+        };
+        (bank % banks) * 0x2000 + (addr & 0x1fff) as usize
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        // CHR inversion swaps the 2KB and the 1KB halves
+        let addr = if self.m_bank_select & 0x80 != 0 { addr ^ 0x1000 } else { addr };
+        let bank = match addr >> 10 {
+            0 => self.m_registers[0] & 0xfe,
+            1 => self.m_registers[0] | 0x01,
+            2 => self.m_registers[1] & 0xfe,
+            3 => self.m_registers[1] | 0x01,
+            slot => self.m_registers[slot as usize - 2],
+        };
+        bank as usize * 0x400 + (addr & 0x3ff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let reload = self.m_irq_reload;
+        let previous = self.m_irq_counter;
+        if self.m_irq_counter == 0 || reload {
+            self.m_irq_counter = self.m_irq_latch;
+            self.m_irq_reload = false;
+        } else {
+            self.m_irq_counter -= 1;
+        }
+
+        let fire = match self.m_revision {
+            Mmc3Revision::A => self.m_irq_counter == 0 && (previous != 0 || reload),
+            Mmc3Revision::B => self.m_irq_counter == 0,
+        };
+        if fire && self.m_irq_enabled {
+            self.m_irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for MapperTxROM {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        let even = addr & 0x1 == 0;
+        match addr {
+            0x8000..=0x9fff if even => self.m_bank_select = value,
+            0x8000..=0x9fff => self.m_registers[(self.m_bank_select & 0x7) as usize] = value,
+            0xa000..=0xbfff if even => self.m_mirroring = value & 0x1,
+            0xa000..=0xbfff => self.m_prg_ram_protect = value,
+            0xc000..=0xdfff if even => self.m_irq_latch = value,
+            0xc000..=0xdfff => {
+                self.m_irq_counter = 0;
+                self.m_irq_reload = true;
+            }
+            _ if even => {
+                // Disabling also acknowledges a pending IRQ
+                self.m_irq_enabled = false;
+                self.m_irq_pending = false;
+            }
+            _ => self.m_irq_enabled = true,
+        }
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[self.chr_offset(addr) % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        if self.cartridge.get_name_table_mirroring() == NameTableMirroring::FourScreen {
+            NameTableMirroring::FourScreen
+        } else if self.m_mirroring == 0 {
+            NameTableMirroring::Vertical
+        } else {
+            NameTableMirroring::Horizontal
+        }
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        true
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn prg_ram_address(&self, addr: Address, write: bool) -> Option<usize> {
+        let enabled = self.m_prg_ram_protect & 0x80 != 0;
+        let write_protected = self.m_prg_ram_protect & 0x40 != 0;
+        if !enabled || (write && write_protected) {
+            return None;
+        }
+        Some((addr - 0x6000) as usize)
+    }
+
+    fn irq_line(&self) -> bool {
+        self.m_irq_pending
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.m_cycles += 1;
+    }
+
+    fn ppu_address(&mut self, addr: Address) {
+        if addr & 0x1000 == 0 {
+            self.m_a12_low_since.get_or_insert(self.m_cycles);
+        } else if let Some(low_since) = self.m_a12_low_since.take() {
+            if self.m_cycles - low_since >= A12_LOW_CYCLES {
+                self.clock_irq_counter();
+            }
+        }
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![
+            self.m_bank_select,
+            self.m_mirroring,
+            self.m_prg_ram_protect,
+            self.m_irq_latch,
+            self.m_irq_counter,
+            self.m_irq_reload as Byte,
+            self.m_irq_enabled as Byte,
+            self.m_irq_pending as Byte,
+        ];
+        state.extend_from_slice(&self.m_registers);
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        if state.len() != 16 + self.character_ram.len() {
+            return Err("MMC3 state has the wrong size.".to_string());
+        }
+        self.m_bank_select = state[0];
+        self.m_mirroring = state[1];
+        self.m_prg_ram_protect = state[2];
+        self.m_irq_latch = state[3];
+        self.m_irq_counter = state[4];
+        self.m_irq_reload = state[5] != 0;
+        self.m_irq_enabled = state[6] != 0;
+        self.m_irq_pending = state[7] != 0;
+        self.m_registers.copy_from_slice(&state[8..16]);
+        self.character_ram.copy_from_slice(&state[16..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+use vrc_irq::VrcIrq;
+use vrc_irq::VRC_IRQ_STATE_SIZE;
+use crate::vrc_irq;
+
+/// Mappers 21, 22, 23 and 25, Konami VRC2 and VRC4. Two switchable 8KB PRG banks,
+/// eight 1KB CHR banks written a nibble at a time, and on the VRC4 a PRG swap mode
+/// and the VRC IRQ counter. The boards differ in which CPU address lines feed the
+/// chip's two register select pins; NES 2.0 submappers name the board, without one
+/// both candidate wirings of the mapper number are decoded at once.
+pub struct MapperVRC4 {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    vrc2: bool,
+    // CPU address lines wired to register select bits 0 and 1
+    a0_lines: Address,
+    a1_lines: Address,
+    // VRC2a drops the low bit of CHR banks
+    chr_shift: u8,
+    m_prg_banks: [Byte; 2],
+    m_chr_banks: [Address; 8],
+    m_mirroring: Byte,
+    m_prg_swap: bool,
+    m_irq: VrcIrq,
+}
+
+impl MapperVRC4 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        // (VRC2, lines for bit 0, lines for bit 1)
+        let (vrc2, a0_lines, a1_lines) = match (cartridge.get_mapper(), cartridge.get_submapper()) {
+            // VRC4a, VRC4c
+            (21, 1) => (false, 0x02, 0x04),
+            (21, 2) => (false, 0x40, 0x80),
+            (21, _) => (false, 0x42, 0x84),
+            // VRC2a
+            (22, _) => (true, 0x02, 0x01),
+            // VRC4f, VRC4e, VRC2b
+            (23, 1) => (false, 0x01, 0x02),
+            (23, 2) => (false, 0x04, 0x08),
+            (23, 3) => (true, 0x01, 0x02),
+            (23, _) => (false, 0x05, 0x0a),
+            // VRC4b, VRC4d, VRC2c
+            (_, 1) => (false, 0x02, 0x01),
+            (_, 2) => (false, 0x08, 0x04),
+            (_, 3) => (true, 0x02, 0x01),
+            (_, _) => (false, 0x0a, 0x05),
+        };
+        let chr_shift = (cartridge.get_mapper() == 22) as u8;
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperVRC4 {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            vrc2,
+            a0_lines,
+            a1_lines,
+            chr_shift,
+            m_prg_banks: [0; 2],
+            m_chr_banks: [0; 8],
+            m_mirroring: 0,
+            m_prg_swap: false,
+            m_irq: VrcIrq::new(),
+        }
+    }
+
+    /// Register select from the address lines of this board, 0-3.
+    fn register(&self, addr: Address) -> u8 {
+        (addr & self.a0_lines != 0) as u8 | ((addr & self.a1_lines != 0) as u8) << 1
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let banks = self.cartridge.get_rom().len() / 0x2000;
+        let second_last = (banks + banks - 2) % banks;
+        let bank = match (addr >> 13) & 0x3 {
+            0 if self.m_prg_swap => second_last,
+            0 => self.m_prg_banks[0] as usize,
+            1 => self.m_prg_banks[1] as usize,
+            2 if self.m_prg_swap => self.m_prg_banks[0] as usize,
+            2 => second_last,
+            _ => banks - 1,
+        };
+        (bank % banks) * 0x2000 + (addr & 0x1fff) as usize
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        let bank = self.m_chr_banks[(addr >> 10) as usize & 0x7] >> self.chr_shift;
+        bank as usize * 0x400 + (addr & 0x3ff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+}
+
+impl Mapper for MapperVRC4 {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        let register = self.register(addr);
+        match addr & 0xf000 {
+            0x8000 => self.m_prg_banks[0] = value & 0x1f,
+            0x9000 if self.vrc2 => self.m_mirroring = value & 0x1,
+            0x9000 if register < 2 => self.m_mirroring = value & 0x3,
+            0x9000 => self.m_prg_swap = value & 0x2 != 0,
+            0xa000 => self.m_prg_banks[1] = value & 0x1f,
+            0xb000..=0xefff => {
+                // Two registers per bank, low nibble first
+                let slot = (((addr >> 12) - 0xb) * 2 + (register >> 1) as Address) as usize;
+                let bank = self.m_chr_banks[slot];
+                self.m_chr_banks[slot] = if register & 0x1 == 0 {
+                    (bank & 0x1f0) | (value & 0x0f) as Address
+                } else {
+                    (bank & 0x00f) | ((value & 0x1f) as Address) << 4
+                };
+            }
+            _ if self.vrc2 => (),
+            _ => match register {
+                0 => self.m_irq.write_latch_low(value),
+                1 => self.m_irq.write_latch_high(value),
+                2 => self.m_irq.write_control(value),
+                _ => self.m_irq.acknowledge(),
+            },
+        }
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[self.chr_offset(addr) % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        match self.m_mirroring {
+            0 => NameTableMirroring::Vertical,
+            1 => NameTableMirroring::Horizontal,
+            2 => NameTableMirroring::OneScreenLower,
+            _ => NameTableMirroring::OneScreenHigher,
+        }
+    }
+
+    // VRC4 boards all have PRG-RAM, VRC2 boards only sometimes
+    fn has_extended_ram(&self) -> bool {
+        !self.vrc2 || self.cartridge.has_extended_ram()
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn irq_line(&self) -> bool {
+        self.m_irq.pending()
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.m_irq.cpu_cycle();
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![self.m_prg_banks[0], self.m_prg_banks[1], self.m_mirroring, self.m_prg_swap as Byte];
+        for bank in self.m_chr_banks {
+            state.extend_from_slice(&bank.to_le_bytes());
+        }
+        state.extend_from_slice(&self.m_irq.save_state());
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        const HEADER: usize = 4 + 16 + VRC_IRQ_STATE_SIZE;
+        if state.len() != HEADER + self.character_ram.len() {
+            return Err("VRC4 state has the wrong size.".to_string());
+        }
+        self.m_prg_banks = [state[0], state[1]];
+        self.m_mirroring = state[2];
+        self.m_prg_swap = state[3] != 0;
+        for (slot, bytes) in state[4..20].chunks(2).enumerate() {
+            self.m_chr_banks[slot] = Address::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        self.m_irq.load_state(&state[20..HEADER]);
+        self.character_ram.copy_from_slice(&state[HEADER..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+use vrc_irq::VrcIrq;
+use vrc_irq::VRC_IRQ_STATE_SIZE;
+use crate::vrc_irq;
+
+use vrc6_audio::Vrc6Audio;
+use vrc6_audio::VRC6_AUDIO_STATE_SIZE;
+use crate::vrc6_audio;
+
+/// Mappers 24 and 26, Konami VRC6. 16KB + 8KB of switchable PRG, eight CHR registers,
+/// the VRC IRQ counter and two pulse channels plus a sawtooth of expansion audio.
+/// Mapper 26 has the register select pins wired to A1/A0 instead of A0/A1. Of $B003
+/// only the modes games use are supported: CHR from the pattern table registers and
+/// nametables from CIRAM.
+pub struct MapperVRC6 {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    swapped_lines: bool,
+    m_prg_16k: Byte,
+    m_prg_8k: Byte,
+    m_chr_banks: [Byte; 8],
+    // $B003
+    m_control: Byte,
+    m_irq: VrcIrq,
+    m_audio: Vrc6Audio,
+}
+
+impl MapperVRC6 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let swapped_lines = cartridge.get_mapper() == 26;
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperVRC6 {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            swapped_lines,
+            m_prg_16k: 0,
+            m_prg_8k: 0,
+            m_chr_banks: [0; 8],
+            m_control: 0,
+            m_irq: VrcIrq::new(),
+            m_audio: Vrc6Audio::new(),
+        }
+    }
+
+    /// `addr` with the register select in A1-A0.
+    fn decode(&self, addr: Address) -> Address {
+        let register = if self.swapped_lines { (addr & 0x1) << 1 | (addr >> 1) & 0x1 } else { addr & 0x3 };
+        (addr & 0xf000) | register
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let rom_size = self.cartridge.get_rom().len();
+        let offset = match addr {
+            0x8000..=0xbfff => self.m_prg_16k as usize * 0x4000 + (addr & 0x3fff) as usize,
+            0xc000..=0xdfff => self.m_prg_8k as usize * 0x2000 + (addr & 0x1fff) as usize,
+            _ => rom_size - 0x2000 + (addr & 0x1fff) as usize,
+        };
+        offset % rom_size
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        let slot = (addr >> 10) as usize & 0x7;
+        let a10 = slot as Byte & 0x1;
+        let bank = match (self.m_control & 0x3, slot) {
+            (0, _) => self.m_chr_banks[slot],
+            // 2KB banks, PPU A10 picks the half
+            (1, _) => (self.m_chr_banks[slot >> 1] & 0xfe) | a10,
+            (_, 0..=3) => self.m_chr_banks[slot],
+            (_, _) => (self.m_chr_banks[4 + ((slot - 4) >> 1)] & 0xfe) | a10,
+        };
+        bank as usize * 0x400 + (addr & 0x3ff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+}
+
+impl Mapper for MapperVRC6 {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        let addr = self.decode(addr);
+        match addr {
+            0x8000..=0x8003 => self.m_prg_16k = value & 0x0f,
+            0xb003 => self.m_control = value,
+            0x9000..=0xb002 => self.m_audio.write(addr, value),
+            0xc000..=0xc003 => self.m_prg_8k = value & 0x1f,
+            0xd000..=0xe003 => self.m_chr_banks[((addr >> 12) as usize - 0xd) * 4 + (addr & 0x3) as usize] = value,
+            0xf000 => self.m_irq.write_latch(value),
+            0xf001 => self.m_irq.write_control(value),
+            0xf002 => self.m_irq.acknowledge(),
+            _ => (),
+        }
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[self.chr_offset(addr) % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        match (self.m_control >> 2) & 0x3 {
+            0 => NameTableMirroring::Vertical,
+            1 => NameTableMirroring::Horizontal,
+            2 => NameTableMirroring::OneScreenLower,
+            _ => NameTableMirroring::OneScreenHigher,
+        }
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        true
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn prg_ram_address(&self, addr: Address, _write: bool) -> Option<usize> {
+        if self.m_control & 0x80 == 0 {
+            return None;
+        }
+        Some((addr - 0x6000) as usize)
+    }
+
+    fn irq_line(&self) -> bool {
+        self.m_irq.pending()
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.m_irq.cpu_cycle();
+        self.m_audio.cpu_cycle();
+    }
+
+    fn audio_channels(&self) -> &[f32] {
+        self.m_audio.channels()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = vec![self.m_prg_16k, self.m_prg_8k, self.m_control];
+        state.extend_from_slice(&self.m_chr_banks);
+        state.extend_from_slice(&self.m_irq.save_state());
+        state.extend_from_slice(&self.m_audio.save_state());
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        const IRQ: usize = 3 + 8;
+        const AUDIO: usize = IRQ + VRC_IRQ_STATE_SIZE;
+        const HEADER: usize = AUDIO + VRC6_AUDIO_STATE_SIZE;
+        if state.len() != HEADER + self.character_ram.len() {
+            return Err("VRC6 state has the wrong size.".to_string());
+        }
+        self.m_prg_16k = state[0];
+        self.m_prg_8k = state[1];
+        self.m_control = state[2];
+        self.m_chr_banks.copy_from_slice(&state[3..IRQ]);
+        self.m_irq.load_state(&state[IRQ..AUDIO]);
+        self.m_audio.load_state(&state[AUDIO..HEADER]);
+        self.character_ram.copy_from_slice(&state[HEADER..]);
+        Ok(())
+    }
+}
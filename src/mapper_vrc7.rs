@@ -0,0 +1,195 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cartridge::Cartridge;
+use crate::cartridge;
+
+use mapper::Mapper;
+use mapper::NameTableMirroring;
+use crate::mapper;
+
+use vrc_irq::VrcIrq;
+use vrc_irq::VRC_IRQ_STATE_SIZE;
+use crate::vrc_irq;
+
+use vrc7_audio::Vrc7Audio;
+use vrc7_audio::VRC7_AUDIO_STATE_SIZE;
+use crate::vrc7_audio;
+
+/// Mapper 85, Konami VRC7. Three switchable 8KB PRG banks, eight 1KB CHR banks, the
+/// VRC IRQ counter and six channels of FM audio. VRC7b (Tiny Toon Adventures 2) selects
+/// registers with A3, VRC7a (Lagrange Point) with A4; without a submapper both are decoded.
+pub struct MapperVRC7 {
+    cartridge: Cartridge,
+    uses_character_ram: bool,
+    character_ram: Vec<Byte>,
+    select_lines: Address,
+    m_prg_banks: [Byte; 3],
+    m_chr_banks: [Byte; 8],
+    // $E000
+    m_control: Byte,
+    m_irq: VrcIrq,
+    m_audio: Vrc7Audio,
+}
+
+impl MapperVRC7 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let select_lines = match cartridge.get_submapper() {
+            1 => 0x08,
+            2 => 0x10,
+            _ => 0x18,
+        };
+        let uses_character_ram = cartridge.get_vrom().is_empty();
+        let character_ram = if uses_character_ram { vec![0; 0x2000] } else { Vec::new() };
+        MapperVRC7 {
+            cartridge,
+            uses_character_ram,
+            character_ram,
+            select_lines,
+            m_prg_banks: [0; 3],
+            m_chr_banks: [0; 8],
+            m_control: 0,
+            m_irq: VrcIrq::new(),
+            m_audio: Vrc7Audio::new(),
+        }
+    }
+
+    fn audio_silenced(&self) -> bool {
+        self.m_control & 0x40 != 0
+    }
+
+    fn prg_offset(&self, addr: Address) -> usize {
+        let banks = self.cartridge.get_rom().len() / 0x2000;
+        let bank = match (addr >> 13) & 0x3 {
+            3 => banks - 1,
+            slot => self.m_prg_banks[slot as usize] as usize % banks,
+        };
+        bank * 0x2000 + (addr & 0x1fff) as usize
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        self.m_chr_banks[(addr >> 10) as usize & 0x7] as usize * 0x400 + (addr & 0x3ff) as usize
+    }
+
+    fn chr(&self) -> &[Byte] {
+        if self.uses_character_ram {
+            &self.character_ram
+        } else {
+            self.cartridge.get_vrom()
+        }
+    }
+}
+
+impl Mapper for MapperVRC7 {
+    fn peek_prg(&self, addr: Address) -> Byte {
+        self.cartridge.get_rom()[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Byte) {
+        // The audio ports also decode A5
+        match addr & 0xf030 {
+            0x9010 => return self.m_audio.write_address(value),
+            0x9030 if !self.audio_silenced() => return self.m_audio.write_data(value),
+            0x9030 => return,
+            _ => (),
+        }
+        let odd = addr & self.select_lines != 0;
+        match (addr & 0xf000, odd) {
+            (0x8000, false) => self.m_prg_banks[0] = value & 0x3f,
+            (0x8000, true) => self.m_prg_banks[1] = value & 0x3f,
+            (0x9000, false) => self.m_prg_banks[2] = value & 0x3f,
+            (0x9000, true) => (),
+            (0xa000..=0xd000, _) => {
+                let slot = ((addr >> 12) as usize - 0xa) * 2 + odd as usize;
+                self.m_chr_banks[slot] = value;
+            }
+            (0xe000, false) => {
+                self.m_control = value;
+                if self.audio_silenced() {
+                    self.m_audio.reset();
+                }
+            }
+            (0xe000, true) => self.m_irq.write_latch(value),
+            (_, false) => self.m_irq.write_control(value),
+            (_, true) => self.m_irq.acknowledge(),
+        }
+    }
+
+    fn peek_chr(&self, addr: Address) -> Byte {
+        let chr = self.chr();
+        chr[self.chr_offset(addr) % chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Byte) {
+        if self.uses_character_ram {
+            let index = self.chr_offset(addr) % self.character_ram.len();
+            self.character_ram[index] = value;
+        }
+    }
+
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        match self.m_control & 0x3 {
+            0 => NameTableMirroring::Vertical,
+            1 => NameTableMirroring::Horizontal,
+            2 => NameTableMirroring::OneScreenLower,
+            _ => NameTableMirroring::OneScreenHigher,
+        }
+    }
+
+    fn has_extended_ram(&self) -> bool {
+        true
+    }
+
+    fn prg_ram_size(&self) -> usize {
+        self.cartridge.prg_ram_size()
+    }
+
+    fn prg_ram_address(&self, addr: Address, _write: bool) -> Option<usize> {
+        if self.m_control & 0x80 == 0 {
+            return None;
+        }
+        Some((addr - 0x6000) as usize)
+    }
+
+    fn irq_line(&self) -> bool {
+        self.m_irq.pending()
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.m_irq.cpu_cycle();
+        if !self.audio_silenced() {
+            self.m_audio.cpu_cycle();
+        }
+    }
+
+    fn audio_channels(&self) -> &[f32] {
+        self.m_audio.channels()
+    }
+
+    fn save_state(&self) -> Vec<Byte> {
+        let mut state = self.m_prg_banks.to_vec();
+        state.extend_from_slice(&self.m_chr_banks);
+        state.push(self.m_control);
+        state.extend_from_slice(&self.m_irq.save_state());
+        state.extend_from_slice(&self.m_audio.save_state());
+        state.extend_from_slice(&self.character_ram);
+        state
+    }
+
+    fn load_state(&mut self, state: &[Byte]) -> Result<(), String> {
+        const IRQ: usize = 3 + 8 + 1;
+        const AUDIO: usize = IRQ + VRC_IRQ_STATE_SIZE;
+        const HEADER: usize = AUDIO + VRC7_AUDIO_STATE_SIZE;
+        if state.len() != HEADER + self.character_ram.len() {
+            return Err("VRC7 state has the wrong size.".to_string());
+        }
+        self.m_prg_banks.copy_from_slice(&state[0..3]);
+        self.m_chr_banks.copy_from_slice(&state[3..11]);
+        self.m_control = state[11];
+        self.m_irq.load_state(&state[IRQ..AUDIO]);
+        self.m_audio.load_state(&state[AUDIO..HEADER]);
+        self.character_ram.copy_from_slice(&state[HEADER..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use chip::Byte;
+use crate::chip;
+
+pub const NAMCO163_AUDIO_STATE_SIZE: usize = 0x80 + 3;
+
+/// CPU cycles the chip spends on each channel update.
+const CHANNEL_CYCLES: u8 = 15;
+
+/// Namco 163 expansion audio: up to eight wavetable channels playing 4-bit samples out
+/// of 128 bytes of internal RAM, which also holds the channel registers ($40-$7F,
+/// channel 7 last). Only one channel is updated every 15 CPU cycles, so the more are
+/// enabled, the lower each one's sample rate. The RAM is reached through an address port
+/// ($F800, with auto-increment) and a data port ($4800).
+pub struct Namco163Audio {
+    m_ram: [Byte; 0x80],
+    m_address: Byte,
+    m_auto_increment: bool,
+    m_cycles: u8,
+    // Channel the next update goes to
+    m_channel: usize,
+    m_output: [f32; 8],
+}
+
+impl Default for Namco163Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Namco163Audio {
+    pub fn new() -> Self {
+        Namco163Audio {
+            m_ram: [0; 0x80],
+            m_address: 0,
+            m_auto_increment: false,
+            m_cycles: 0,
+            m_channel: 7,
+            m_output: [0.0; 8],
+        }
+    }
+
+    pub fn write_address(&mut self, value: Byte) {
+        self.m_address = value & 0x7f;
+        self.m_auto_increment = value & 0x80 != 0;
+    }
+
+    pub fn peek_data(&self) -> Byte {
+        self.m_ram[self.m_address as usize]
+    }
+
+    pub fn read_data(&mut self) -> Byte {
+        let value = self.peek_data();
+        self.increment();
+        value
+    }
+
+    pub fn write_data(&mut self, value: Byte) {
+        self.m_ram[self.m_address as usize] = value;
+        self.increment();
+    }
+
+    fn increment(&mut self) {
+        if self.m_auto_increment {
+            self.m_address = (self.m_address + 1) & 0x7f;
+        }
+    }
+
+    fn enabled_channels(&self) -> usize {
+        ((self.m_ram[0x7f] >> 4) & 0x7) as usize + 1
+    }
+
+    pub fn cpu_cycle(&mut self) {
+        self.m_cycles += 1;
+        if self.m_cycles < CHANNEL_CYCLES {
+            return;
+        }
+        self.m_cycles = 0;
+
+        let first = 8 - self.enabled_channels();
+        // Channels 7 down to `first`, over again
+        let channel = if self.m_channel < first { 7 } else { self.m_channel };
+        self.update_channel(channel);
+        self.m_channel = if channel == first { 7 } else { channel - 1 };
+        for silent in &mut self.m_output[..first] {
+            *silent = 0.0;
+        }
+    }
+
+    fn update_channel(&mut self, channel: usize) {
+        let base = 0x40 + channel * 8;
+        let registers = &self.m_ram[base..base + 8];
+        let frequency = registers[0] as u32 | (registers[2] as u32) << 8 | ((registers[4] & 0x3) as u32) << 16;
+        let phase = registers[1] as u32 | (registers[3] as u32) << 8 | (registers[5] as u32) << 16;
+        let length = 0x100 - (registers[4] & 0xfc) as u32;
+        let offset = registers[6] as u32;
+        let volume = (registers[7] & 0x0f) as i32;
+
+        let phase = (phase + frequency) % (length << 16);
+        self.m_ram[base + 1] = phase as Byte;
+        self.m_ram[base + 3] = (phase >> 8) as Byte;
+        self.m_ram[base + 5] = (phase >> 16) as Byte;
+
+        // Two samples per byte, low nibble first
+        let index = (((phase >> 16) + offset) & 0xff) as usize;
+        let sample = (self.m_ram[index >> 1] >> ((index & 0x1) * 4)) & 0x0f;
+        self.m_output[channel] = ((sample as i32 - 8) * volume) as f32 / 120.0;
+    }
+
+    /// Channels 0-7; disabled channels are silent.
+    pub fn channels(&self) -> &[f32] {
+        &self.m_output
+    }
+
+    /// The internal RAM, which some boards keep battery-backed.
+    pub fn ram(&self) -> &[Byte] {
+        &self.m_ram
+    }
+
+    pub fn load_ram(&mut self, data: &[Byte]) {
+        let length = data.len().min(self.m_ram.len());
+        self.m_ram[..length].copy_from_slice(&data[..length]);
+    }
+
+    pub fn save_state(&self) -> Vec<Byte> {
+        let mut state = self.m_ram.to_vec();
+        state.extend_from_slice(&[self.m_address, self.m_auto_increment as Byte, self.m_channel as Byte]);
+        state
+    }
+
+    pub fn load_state(&mut self, state: &[Byte]) {
+        self.m_ram.copy_from_slice(&state[..0x80]);
+        self.m_address = state[0x80] & 0x7f;
+        self.m_auto_increment = state[0x81] != 0;
+        self.m_channel = (state[0x82] & 0x7) as usize;
+    }
+}
@@ -0,0 +1,37 @@
+use std::os::raw::c_int;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+const SIGINT: c_int = 2;
+const SIGTERM: c_int = 15;
+const SIG_DFL: usize = 0;
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    // The C library's, on both Unix and Windows
+    fn signal(signum: c_int, handler: usize) -> usize;
+}
+
+extern "C" fn request_stop(signum: c_int) {
+    STOP_REQUESTED.store(true, Ordering::Relaxed);
+    // A second Ctrl-C still kills the process
+    unsafe {
+        signal(signum, SIG_DFL);
+    }
+}
+
+/// Makes SIGINT (Ctrl-C) and SIGTERM ask the emulator to stop, so it can flush battery
+/// saves on the way out instead of being killed.
+pub fn install_stop_handler() {
+    let handler = request_stop as extern "C" fn(c_int) as usize;
+    unsafe {
+        signal(SIGINT, handler);
+        signal(SIGTERM, handler);
+    }
+}
+
+/// Whether SIGINT or SIGTERM arrived since `install_stop_handler`.
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,120 @@
+use chip::Byte;
+use crate::chip;
+
+use std::fmt;
+use std::ops::BitAnd;
+use std::ops::BitOr;
+use std::ops::BitOrAssign;
+use std::ops::Not;
+
+/// The processor status register P.
+///
+/// Only six of its bits exist in the CPU. Bit 5 and the B flag (bit 4) only appear on
+/// the copy pushed to the stack: bit 5 is always 1 there and B tells PHP/BRK (1) apart
+/// from IRQ/NMI (0). They are dropped again when P is pulled by PLP or RTI.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatusFlags(Byte);
+
+impl StatusFlags {
+    pub const CARRY: StatusFlags = StatusFlags(1 << 0);
+    pub const ZERO: StatusFlags = StatusFlags(1 << 1);
+    pub const INTERRUPT_DISABLE: StatusFlags = StatusFlags(1 << 2);
+    pub const DECIMAL: StatusFlags = StatusFlags(1 << 3);
+    /// Only on the stack
+    pub const BREAK: StatusFlags = StatusFlags(1 << 4);
+    /// Only on the stack, always 1
+    pub const UNUSED: StatusFlags = StatusFlags(1 << 5);
+    pub const OVERFLOW: StatusFlags = StatusFlags(1 << 6);
+    pub const NEGATIVE: StatusFlags = StatusFlags(1 << 7);
+
+    /// The bits that exist in the register.
+    const REGISTER_MASK: Byte = !(Self::BREAK.0 | Self::UNUSED.0);
+
+    pub const fn empty() -> Self {
+        StatusFlags(0)
+    }
+
+    pub const fn bits(self) -> Byte {
+        self.0
+    }
+
+    /// Keeps every bit of `bits`, including B and bit 5.
+    pub const fn from_bits(bits: Byte) -> Self {
+        StatusFlags(bits)
+    }
+
+    pub const fn contains(self, other: StatusFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: StatusFlags) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: StatusFlags) {
+        self.0 &= !other.0;
+    }
+
+    pub fn set(&mut self, other: StatusFlags, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+
+    /// The byte pushed to the stack: with B set by PHP and BRK, clear by IRQ and NMI.
+    pub const fn to_stack_byte(self, break_flag: bool) -> Byte {
+        (self.0 & Self::REGISTER_MASK) | Self::UNUSED.0 | if break_flag { Self::BREAK.0 } else { 0 }
+    }
+
+    /// The register as loaded by PLP and RTI, which ignore B and bit 5.
+    pub const fn from_stack_byte(byte: Byte) -> Self {
+        StatusFlags(byte & Self::REGISTER_MASK)
+    }
+}
+
+impl BitOr for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitor(self, other: StatusFlags) -> StatusFlags {
+        StatusFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for StatusFlags {
+    fn bitor_assign(&mut self, other: StatusFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitand(self, other: StatusFlags) -> StatusFlags {
+        StatusFlags(self.0 & other.0)
+    }
+}
+
+impl Not for StatusFlags {
+    type Output = StatusFlags;
+
+    fn not(self) -> StatusFlags {
+        StatusFlags(!self.0)
+    }
+}
+
+/// Formats like most debuggers, e.g. `Nv-bdIzC`: upper case for set flags.
+impl fmt::Debug for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = b"CZIDB-VN";
+        let text: String = (0..8)
+            .rev()
+            .map(|bit| {
+                let name = names[bit] as char;
+                if self.0 & (1 << bit) != 0 { name } else { name.to_ascii_lowercase() }
+            })
+            .collect();
+        write!(f, "{}", text)
+    }
+}
@@ -0,0 +1,184 @@
+use chip::Byte;
+use crate::chip;
+
+pub const SUNSOFT5B_AUDIO_STATE_SIZE: usize = 0x11;
+
+/// CPU cycles per envelope step; tone and noise counters advance every other step,
+/// so a tone period of P plays at CPU clock / (32 * P).
+const STEP_CYCLES: u8 = 8;
+
+/// The Sunsoft 5B, a YM2149F (AY-3-8910) inside the FME-7: three square wave channels
+/// that can each mix in the shared noise generator, with a fixed volume or the shared
+/// envelope. Registers are written through an address port ($C000) and a data port
+/// ($E000); envelopes and counters restart after a save state is loaded.
+pub struct Sunsoft5bAudio {
+    m_registers: [Byte; 0x10],
+    m_address: Byte,
+    m_step_cycles: u8,
+    m_half_step: bool,
+    m_tone_counters: [u16; 3],
+    m_tone_outputs: [bool; 3],
+    m_noise_counter: u16,
+    // 17-bit LFSR
+    m_noise_shift: u32,
+    m_envelope_counter: u16,
+    // 0-31 within the current ramp
+    m_envelope_step: Byte,
+    m_envelope_attack: bool,
+    m_envelope_holding: bool,
+    m_output: [f32; 3],
+}
+
+impl Default for Sunsoft5bAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sunsoft5bAudio {
+    pub fn new() -> Self {
+        Sunsoft5bAudio {
+            m_registers: [0; 0x10],
+            m_address: 0,
+            m_step_cycles: 0,
+            m_half_step: false,
+            m_tone_counters: [0; 3],
+            m_tone_outputs: [false; 3],
+            m_noise_counter: 0,
+            m_noise_shift: 1,
+            m_envelope_counter: 0,
+            m_envelope_step: 0,
+            m_envelope_attack: false,
+            m_envelope_holding: false,
+            m_output: [0.0; 3],
+        }
+    }
+
+    pub fn write_address(&mut self, value: Byte) {
+        self.m_address = value & 0x0f;
+    }
+
+    pub fn write_data(&mut self, value: Byte) {
+        self.m_registers[self.m_address as usize] = value;
+        if self.m_address == 0x0d {
+            // A new envelope shape restarts the envelope
+            self.m_envelope_step = 0;
+            self.m_envelope_counter = 0;
+            self.m_envelope_attack = value & 0x4 != 0;
+            self.m_envelope_holding = false;
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let period = self.m_registers[channel * 2] as u16 | ((self.m_registers[channel * 2 + 1] & 0x0f) as u16) << 8;
+        period.max(1)
+    }
+
+    fn envelope_level(&self) -> Byte {
+        let shape = self.m_registers[0x0d];
+        if self.m_envelope_holding && shape & 0x8 == 0 {
+            // Shapes without continue fall to 0 after the first ramp
+            return 0;
+        }
+        if self.m_envelope_attack { self.m_envelope_step } else { 31 - self.m_envelope_step }
+    }
+
+    fn clock_envelope(&mut self) {
+        if self.m_envelope_holding {
+            return;
+        }
+        let period = (self.m_registers[0x0b] as u16 | (self.m_registers[0x0c] as u16) << 8).max(1);
+        self.m_envelope_counter += 1;
+        if self.m_envelope_counter < period {
+            return;
+        }
+        self.m_envelope_counter = 0;
+        if self.m_envelope_step < 31 {
+            self.m_envelope_step += 1;
+            return;
+        }
+
+        let shape = self.m_registers[0x0d];
+        let (continues, alternate, hold) = (shape & 0x8 != 0, shape & 0x2 != 0, shape & 0x1 != 0);
+        if !continues || hold {
+            self.m_envelope_holding = true;
+            if continues && alternate {
+                self.m_envelope_attack = !self.m_envelope_attack;
+            }
+        } else {
+            if alternate {
+                self.m_envelope_attack = !self.m_envelope_attack;
+            }
+            self.m_envelope_step = 0;
+        }
+    }
+
+    fn clock_generators(&mut self) {
+        for channel in 0..3 {
+            self.m_tone_counters[channel] += 1;
+            if self.m_tone_counters[channel] >= self.tone_period(channel) {
+                self.m_tone_counters[channel] = 0;
+                self.m_tone_outputs[channel] = !self.m_tone_outputs[channel];
+            }
+        }
+        let noise_period = ((self.m_registers[0x06] & 0x1f) as u16).max(1);
+        self.m_noise_counter += 1;
+        if self.m_noise_counter >= noise_period {
+            self.m_noise_counter = 0;
+            let feedback = (self.m_noise_shift ^ (self.m_noise_shift >> 3)) & 0x1;
+            self.m_noise_shift = (self.m_noise_shift >> 1) | (feedback << 16);
+        }
+    }
+
+    pub fn cpu_cycle(&mut self) {
+        self.m_step_cycles += 1;
+        if self.m_step_cycles < STEP_CYCLES {
+            return;
+        }
+        self.m_step_cycles = 0;
+        self.clock_envelope();
+        self.m_half_step = !self.m_half_step;
+        if self.m_half_step {
+            self.clock_generators();
+        }
+
+        let mixer = self.m_registers[0x07];
+        let noise = self.m_noise_shift & 0x1 != 0;
+        for channel in 0..3 {
+            let tone_on = self.m_tone_outputs[channel] || mixer & (0x1 << channel) != 0;
+            let noise_on = noise || mixer & (0x8 << channel) != 0;
+            let volume = self.m_registers[0x08 + channel];
+            // 5-bit levels; the fixed volumes are every other one
+            let level = if volume & 0x10 != 0 {
+                self.envelope_level()
+            } else if volume & 0x0f == 0 {
+                0
+            } else {
+                (volume & 0x0f) * 2 + 1
+            };
+            self.m_output[channel] = if tone_on && noise_on && level != 0 {
+                // Logarithmic DAC, 1.5dB per level
+                10f32.powf((level as f32 - 31.0) * 1.5 / 20.0)
+            } else {
+                0.0
+            };
+        }
+    }
+
+    /// Channels A, B and C.
+    pub fn channels(&self) -> &[f32] {
+        &self.m_output
+    }
+
+    pub fn save_state(&self) -> Vec<Byte> {
+        let mut state = self.m_registers.to_vec();
+        state.push(self.m_address);
+        state
+    }
+
+    pub fn load_state(&mut self, state: &[Byte]) {
+        *self = Self::new();
+        self.m_registers.copy_from_slice(&state[..0x10]);
+        self.m_address = state[0x10];
+    }
+}
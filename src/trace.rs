@@ -0,0 +1,97 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+use cpu::CPU;
+use crate::cpu;
+
+use cpu_opcodes::INSTRUCTIONS;
+use cpu_opcodes::AddressingMode;
+use cpu_opcodes::MemoryAccess;
+use crate::cpu_opcodes;
+
+use disasm::MemorySource;
+use crate::disasm;
+
+use bus::Bus;
+use crate::bus;
+
+/// Formats the instruction at `cpu.r_pc` and the current CPU state as one line in the
+/// column layout of nestest.log:
+///
+/// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+///
+/// There is no PPU yet, so its position is derived from the cycle count (3 dots per cycle).
+pub fn format_instruction<B: Bus>(cpu: &CPU<B>, memory: &dyn MemorySource) -> String {
+    let pc = cpu.r_pc;
+    let instruction = disasm::disassemble(memory, pc);
+    let read = |addr: Address| memory.peek(addr);
+    let low = instruction.operand as Byte;
+    let absolute = instruction.operand;
+    let read_word_zp = |addr: Byte| {
+        read(addr as Address) as Address | (read(addr.wrapping_add(1) as Address) as Address) << 8
+    };
+
+    // JMP and JSR don't access the memory they point at
+    let show_value = INSTRUCTIONS[instruction.opcode as usize].access != MemoryAccess::None;
+    let value = |addr: Address| if show_value { format!(" = {:02X}", read(addr)) } else { String::new() };
+    let operand = match instruction.mode {
+        AddressingMode::Implied | AddressingMode::Accumulator | AddressingMode::Immediate | AddressingMode::Relative => instruction.operand_text(),
+        AddressingMode::ZeroPage => format!("${:02X}{}", low, value(low as Address)),
+        AddressingMode::ZeroPageX => {
+            let addr = low.wrapping_add(cpu.r_x);
+            format!("${:02X},X @ {:02X}{}", low, addr, value(addr as Address))
+        }
+        AddressingMode::ZeroPageY => {
+            let addr = low.wrapping_add(cpu.r_y);
+            format!("${:02X},Y @ {:02X}{}", low, addr, value(addr as Address))
+        }
+        AddressingMode::Absolute => format!("${:04X}{}", absolute, value(absolute)),
+        AddressingMode::AbsoluteX => {
+            let addr = absolute.wrapping_add(cpu.r_x as Address);
+            format!("${:04X},X @ {:04X}{}", absolute, addr, value(addr))
+        }
+        AddressingMode::AbsoluteY => {
+            let addr = absolute.wrapping_add(cpu.r_y as Address);
+            format!("${:04X},Y @ {:04X}{}", absolute, addr, value(addr))
+        }
+        AddressingMode::Indirect => {
+            // Reproduces the page wrap bug of JMP ($xxFF)
+            let target = read(absolute) as Address
+                | (read((absolute & 0xff00) | (absolute.wrapping_add(1) & 0xff)) as Address) << 8;
+            format!("(${:04X}) = {:04X}", absolute, target)
+        }
+        AddressingMode::IndirectX => {
+            let pointer = low.wrapping_add(cpu.r_x);
+            let addr = read_word_zp(pointer);
+            format!("(${:02X},X) @ {:02X} = {:04X}{}", low, pointer, addr, value(addr))
+        }
+        AddressingMode::IndirectY => {
+            let base = read_word_zp(low);
+            let addr = base.wrapping_add(cpu.r_y as Address);
+            format!("(${:02X}),Y = {:04X} @ {:04X}{}", low, base, addr, value(addr))
+        }
+    };
+
+    // nestest.log shows P with bit 5 set and B clear, as an interrupt would push it
+    let status = cpu.status().to_stack_byte(false);
+    // `CPU::step` has already counted the cycle the instruction starts on
+    let cycles = cpu.m_cycles.wrapping_sub(1);
+    let dots = cycles * 3;
+
+    format!(
+        "{:04X}  {:<8} {}{:<31} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        pc,
+        instruction.bytes(),
+        if instruction.official { ' ' } else { '*' },
+        format!("{} {}", instruction.mnemonic, operand).trim_end(),
+        cpu.r_a,
+        cpu.r_x,
+        cpu.r_y,
+        status,
+        cpu.r_sp,
+        dots / 341 % 262,
+        dots % 341,
+        cycles
+    )
+}
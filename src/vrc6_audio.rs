@@ -0,0 +1,227 @@
+use chip::Byte;
+use chip::Address;
+use crate::chip;
+
+pub const VRC6_AUDIO_STATE_SIZE: usize = 2 * 7 + 10;
+
+/// One VRC6 pulse channel: 16-step duty sequencer with a 4-bit volume.
+#[derive(Default)]
+struct Pulse {
+    m_volume: Byte,
+    m_duty: Byte,
+    // Ignores the duty and outputs the volume constantly
+    m_digitized: bool,
+    m_period: Address,
+    m_enabled: bool,
+    m_divider: Address,
+    m_step: Byte,
+}
+
+impl Pulse {
+    fn write(&mut self, register: Address, value: Byte) {
+        match register {
+            0 => {
+                self.m_digitized = value & 0x80 != 0;
+                self.m_duty = (value >> 4) & 0x7;
+                self.m_volume = value & 0x0f;
+            }
+            1 => self.m_period = (self.m_period & 0xf00) | value as Address,
+            _ => {
+                self.m_period = (self.m_period & 0x0ff) | ((value & 0x0f) as Address) << 8;
+                self.m_enabled = value & 0x80 != 0;
+                if !self.m_enabled {
+                    self.m_step = 0;
+                }
+            }
+        }
+    }
+
+    // Held at step 0 while disabled
+    fn clock(&mut self, shift: u8) {
+        if !self.m_enabled {
+            return;
+        }
+        if self.m_divider == 0 {
+            self.m_divider = self.m_period >> shift;
+            self.m_step = (self.m_step + 1) & 0x0f;
+        } else {
+            self.m_divider -= 1;
+        }
+    }
+
+    fn output(&self) -> Byte {
+        if self.m_enabled && (self.m_digitized || self.m_step <= self.m_duty) {
+            self.m_volume
+        } else {
+            0
+        }
+    }
+
+    fn save_state(&self) -> [Byte; 7] {
+        let [period_low, period_high] = self.m_period.to_le_bytes();
+        let [divider_low, divider_high] = self.m_divider.to_le_bytes();
+        let control = (self.m_digitized as Byte) << 7 | self.m_duty << 4 | self.m_volume;
+        [control, period_low, period_high, self.m_enabled as Byte, divider_low, divider_high, self.m_step]
+    }
+
+    fn load_state(&mut self, state: &[Byte]) {
+        self.write(0, state[0]);
+        self.m_period = Address::from_le_bytes([state[1], state[2]]);
+        self.m_enabled = state[3] != 0;
+        self.m_divider = Address::from_le_bytes([state[4], state[5]]);
+        self.m_step = state[6];
+    }
+}
+
+/// The VRC6 sawtooth: an accumulator that adds the rate every other clock and resets
+/// on the fourteenth, output through its top 5 bits.
+#[derive(Default)]
+struct Sawtooth {
+    m_rate: Byte,
+    m_period: Address,
+    m_enabled: bool,
+    m_divider: Address,
+    m_step: Byte,
+    m_accumulator: Byte,
+}
+
+impl Sawtooth {
+    fn write(&mut self, register: Address, value: Byte) {
+        match register {
+            0 => self.m_rate = value & 0x3f,
+            1 => self.m_period = (self.m_period & 0xf00) | value as Address,
+            _ => {
+                self.m_period = (self.m_period & 0x0ff) | ((value & 0x0f) as Address) << 8;
+                self.m_enabled = value & 0x80 != 0;
+                if !self.m_enabled {
+                    self.m_step = 0;
+                    self.m_accumulator = 0;
+                }
+            }
+        }
+    }
+
+    // Held at 0 while disabled
+    fn clock(&mut self, shift: u8) {
+        if !self.m_enabled {
+            return;
+        }
+        if self.m_divider != 0 {
+            self.m_divider -= 1;
+            return;
+        }
+        self.m_divider = self.m_period >> shift;
+        self.m_step += 1;
+        if self.m_step == 14 {
+            self.m_step = 0;
+            self.m_accumulator = 0;
+        } else if self.m_step & 0x1 == 0 {
+            self.m_accumulator = self.m_accumulator.wrapping_add(self.m_rate);
+        }
+    }
+
+    fn output(&self) -> Byte {
+        if self.m_enabled {
+            self.m_accumulator >> 3
+        } else {
+            0
+        }
+    }
+}
+
+/// VRC6 expansion audio: two pulse channels and a sawtooth, clocked by the CPU. Channel
+/// registers are at $9000-$9002, $A000-$A002 and $B000-$B002, the frequency control at
+/// $9003.
+#[derive(Default)]
+pub struct Vrc6Audio {
+    m_pulses: [Pulse; 2],
+    m_sawtooth: Sawtooth,
+    m_halted: bool,
+    // Period shift from $9003: 4 (16x faster) or 8 (256x faster)
+    m_shift: u8,
+    m_output: [f32; 3],
+}
+
+impl Vrc6Audio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `addr` with the register select pins already decoded to A1-A0.
+    pub fn write(&mut self, addr: Address, value: Byte) {
+        let register = addr & 0x3;
+        match (addr & 0xf000, register) {
+            (0x9000, 3) => {
+                self.m_halted = value & 0x1 != 0;
+                self.m_shift = if value & 0x4 != 0 {
+                    8
+                } else if value & 0x2 != 0 {
+                    4
+                } else {
+                    0
+                };
+            }
+            (0x9000, _) => self.m_pulses[0].write(register, value),
+            (0xa000, 3) | (0xb000, 3) => (),
+            (0xa000, _) => self.m_pulses[1].write(register, value),
+            (0xb000, _) => self.m_sawtooth.write(register, value),
+            _ => (),
+        }
+    }
+
+    pub fn cpu_cycle(&mut self) {
+        if !self.m_halted {
+            for pulse in self.m_pulses.iter_mut() {
+                pulse.clock(self.m_shift);
+            }
+            self.m_sawtooth.clock(self.m_shift);
+        }
+        self.m_output = [
+            self.m_pulses[0].output() as f32 / 15.0,
+            self.m_pulses[1].output() as f32 / 15.0,
+            self.m_sawtooth.output() as f32 / 31.0,
+        ];
+    }
+
+    /// Pulse 1, pulse 2 and sawtooth.
+    pub fn channels(&self) -> &[f32] {
+        &self.m_output
+    }
+
+    pub fn save_state(&self) -> Vec<Byte> {
+        let mut state = Vec::with_capacity(VRC6_AUDIO_STATE_SIZE);
+        for pulse in &self.m_pulses {
+            state.extend_from_slice(&pulse.save_state());
+        }
+        let saw = &self.m_sawtooth;
+        let [period_low, period_high] = saw.m_period.to_le_bytes();
+        let [divider_low, divider_high] = saw.m_divider.to_le_bytes();
+        state.extend_from_slice(&[
+            saw.m_rate,
+            period_low,
+            period_high,
+            saw.m_enabled as Byte,
+            divider_low,
+            divider_high,
+            saw.m_step,
+            saw.m_accumulator,
+            self.m_halted as Byte,
+            self.m_shift,
+        ]);
+        state
+    }
+
+    pub fn load_state(&mut self, state: &[Byte]) {
+        self.m_pulses[0].load_state(&state[0..7]);
+        self.m_pulses[1].load_state(&state[7..14]);
+        let saw = &mut self.m_sawtooth;
+        saw.m_rate = state[14];
+        saw.m_period = Address::from_le_bytes([state[15], state[16]]);
+        saw.m_enabled = state[17] != 0;
+        saw.m_divider = Address::from_le_bytes([state[18], state[19]]);
+        saw.m_step = state[20];
+        saw.m_accumulator = state[21];
+        self.m_halted = state[22] != 0;
+        self.m_shift = state[23];
+    }
+}
@@ -0,0 +1,348 @@
+use chip::Byte;
+use crate::chip;
+
+pub const VRC7_AUDIO_STATE_SIZE: usize = 0x41;
+
+/// The VRC7's fixed instruments 1-15, in the layout of custom instrument registers $00-$07.
+const PATCHES: [[Byte; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xe8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0d, 0xd8, 0xf6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xfa, 0xb2, 0x20, 0x12],
+    [0x31, 0x61, 0x0c, 0x07, 0xa8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1e, 0x06, 0xe1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xa3, 0xe2, 0xf4, 0xf4],
+    [0x21, 0x61, 0x1d, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xa2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xb5, 0x01, 0x0f, 0x0f, 0xa8, 0xa5, 0x51, 0x02],
+    [0x17, 0xc1, 0x24, 0x07, 0xf8, 0xf8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xd3, 0x05, 0xc9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0c, 0x00, 0x94, 0xc0, 0x33, 0xf6],
+    [0x21, 0x72, 0x0d, 0x00, 0xc1, 0xd5, 0x56, 0x06],
+];
+
+/// Frequency multipliers, MULT 0 being x1/2.
+const MULTIPLIERS: [f32; 16] = [0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0];
+
+/// Key scale attenuation in dB at block 7 by the top 4 bits of the F-number, 6dB less
+/// per octave below.
+const KEY_SCALE_LEVELS: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25, 42.0,
+];
+
+/// The chip runs at 3.58MHz and takes 72 clocks per sample, twice the CPU's period.
+const CYCLES_PER_SAMPLE: u8 = 36;
+
+/// Envelope attenuation steps are 0.375dB, 128 of them reach silence.
+const ENVELOPE_STEP_DB: f32 = 0.375;
+const ENVELOPE_MAX: f32 = 127.0;
+
+/// Vibrato deviation steps, one every 1024 samples (6.1Hz).
+const VIBRATO: [f32; 8] = [0.0, 1.0, 2.0, 1.0, 0.0, -1.0, -2.0, -1.0];
+const VIBRATO_DEPTH: f32 = 0.004;
+/// Tremolo is a triangle of 4.875dB at 3.7Hz.
+const TREMOLO_PERIOD: u32 = 210 * 64;
+const TREMOLO_DB: f32 = 4.875;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Off,
+}
+
+/// What an operator takes from the instrument.
+struct Patch {
+    tremolo: bool,
+    vibrato: bool,
+    // Holds at the sustain level until key off, instead of decaying through it
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: f32,
+    key_scale_level: Byte,
+    // Modulator total level, or carrier volume, in dB
+    level_db: f32,
+    half_wave: bool,
+    attack: Byte,
+    decay: Byte,
+    sustain_level: f32,
+    release: Byte,
+}
+
+impl Patch {
+    /// `operator` 0 is the modulator, 1 the carrier.
+    fn new(patch: &[Byte], operator: usize, volume: Byte) -> Self {
+        let flags = patch[operator];
+        Patch {
+            tremolo: flags & 0x80 != 0,
+            vibrato: flags & 0x40 != 0,
+            sustained: flags & 0x20 != 0,
+            key_scale_rate: flags & 0x10 != 0,
+            multiplier: MULTIPLIERS[(flags & 0x0f) as usize],
+            key_scale_level: patch[2 + operator] >> 6,
+            level_db: if operator == 0 { (patch[2] & 0x3f) as f32 * 0.75 } else { volume as f32 * 3.0 },
+            half_wave: patch[3] & (0x08 << operator) != 0,
+            attack: patch[4 + operator] >> 4,
+            decay: patch[4 + operator] & 0x0f,
+            sustain_level: (patch[6 + operator] >> 4) as f32 * 8.0,
+            release: patch[6 + operator] & 0x0f,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Operator {
+    // In cycles, 0.0-1.0
+    m_phase: f32,
+    // Attenuation in envelope steps
+    m_envelope: f32,
+    m_stage: Stage,
+}
+
+impl Operator {
+    fn new() -> Self {
+        Operator { m_phase: 0.0, m_envelope: ENVELOPE_MAX, m_stage: Stage::Off }
+    }
+
+    fn key_on(&mut self) {
+        self.m_phase = 0.0;
+        self.m_stage = Stage::Attack;
+    }
+
+    fn key_off(&mut self) {
+        if self.m_stage != Stage::Off {
+            self.m_stage = Stage::Release;
+        }
+    }
+
+    /// Envelope steps per sample at `rate` (0-15), sped up by the key scale.
+    fn envelope_speed(rate: Byte, key_scale: Byte) -> f32 {
+        if rate == 0 {
+            return 0.0;
+        }
+        let rate = (rate * 4 + key_scale).min(63) as i32;
+        (4 + (rate & 0x3)) as f32 * 2f32.powi(rate / 4 - 15)
+    }
+
+    fn update_envelope(&mut self, patch: &Patch, key_scale: Byte, channel_sustain: bool) {
+        match self.m_stage {
+            Stage::Attack => {
+                if patch.attack == 15 {
+                    self.m_envelope = 0.0;
+                } else {
+                    // Exponential, fast at first
+                    let speed = Self::envelope_speed(patch.attack, key_scale);
+                    self.m_envelope -= speed * (1.0 + self.m_envelope / 8.0);
+                }
+                if self.m_envelope <= 0.0 {
+                    self.m_envelope = 0.0;
+                    self.m_stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.m_envelope += Self::envelope_speed(patch.decay, key_scale);
+                if self.m_envelope >= patch.sustain_level {
+                    self.m_envelope = patch.sustain_level;
+                    self.m_stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => {
+                if !patch.sustained {
+                    self.m_envelope += Self::envelope_speed(patch.release, key_scale);
+                }
+            }
+            Stage::Release => {
+                let rate = if channel_sustain {
+                    5
+                } else if patch.sustained {
+                    patch.release
+                } else {
+                    7
+                };
+                self.m_envelope += Self::envelope_speed(rate, key_scale);
+            }
+            Stage::Off => (),
+        }
+        if self.m_envelope >= ENVELOPE_MAX {
+            self.m_envelope = ENVELOPE_MAX;
+            if self.m_stage != Stage::Attack {
+                self.m_stage = Stage::Off;
+            }
+        }
+    }
+
+    /// Advances the phase by `increment` cycles and returns the output for a phase
+    /// offset of `modulation` cycles, attenuated by `attenuation` dB on top of the envelope.
+    fn output(&mut self, patch: &Patch, increment: f32, modulation: f32, attenuation: f32) -> f32 {
+        self.m_phase = (self.m_phase + increment).fract();
+        if self.m_stage == Stage::Off {
+            return 0.0;
+        }
+        let wave = (std::f32::consts::TAU * (self.m_phase + modulation)).sin();
+        if patch.half_wave && wave < 0.0 {
+            return 0.0;
+        }
+        let db = self.m_envelope * ENVELOPE_STEP_DB + attenuation;
+        wave * 10f32.powf(-db / 20.0)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Channel {
+    m_operators: [Operator; 2],
+    // The modulator's last two outputs, for feedback
+    m_feedback: [f32; 2],
+}
+
+/// VRC7 expansion audio, a cut-down YM2413 (OPLL): six two-operator FM channels, 15
+/// fixed instruments and one custom, no rhythm mode. Registers are written through
+/// an address port ($9010) and a data port ($9030).
+pub struct Vrc7Audio {
+    m_registers: [Byte; 0x40],
+    m_address: Byte,
+    m_channels: [Channel; 6],
+    m_cycles: u8,
+    m_tremolo_counter: u32,
+    m_vibrato_counter: u32,
+    m_output: [f32; 6],
+}
+
+impl Default for Vrc7Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vrc7Audio {
+    pub fn new() -> Self {
+        Vrc7Audio {
+            m_registers: [0; 0x40],
+            m_address: 0,
+            m_channels: [Channel { m_operators: [Operator::new(); 2], m_feedback: [0.0; 2] }; 6],
+            m_cycles: 0,
+            m_tremolo_counter: 0,
+            m_vibrato_counter: 0,
+            m_output: [0.0; 6],
+        }
+    }
+
+    /// Silences and clears the chip, as $E000 bit 6 does while set.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn write_address(&mut self, value: Byte) {
+        self.m_address = value & 0x3f;
+    }
+
+    pub fn write_data(&mut self, value: Byte) {
+        let register = self.m_address as usize;
+        let channel = register & 0x0f;
+        if register >= 0x10 && channel >= 6 {
+            return;
+        }
+        let previous = self.m_registers[register];
+        self.m_registers[register] = value;
+        if register & 0xf0 == 0x20 {
+            let operators = &mut self.m_channels[channel].m_operators;
+            match (previous & 0x10 != 0, value & 0x10 != 0) {
+                (false, true) => operators.iter_mut().for_each(Operator::key_on),
+                (true, false) => operators.iter_mut().for_each(Operator::key_off),
+                _ => (),
+            }
+        }
+    }
+
+    pub fn cpu_cycle(&mut self) {
+        self.m_cycles += 1;
+        if self.m_cycles == CYCLES_PER_SAMPLE {
+            self.m_cycles = 0;
+            self.sample();
+        }
+    }
+
+    fn sample(&mut self) {
+        self.m_tremolo_counter = (self.m_tremolo_counter + 1) % TREMOLO_PERIOD;
+        self.m_vibrato_counter = self.m_vibrato_counter.wrapping_add(1);
+        let triangle = self.m_tremolo_counter / 64;
+        let triangle = if triangle < 105 { triangle } else { 210 - triangle };
+        let tremolo_db = triangle as f32 / 105.0 * TREMOLO_DB;
+        let vibrato = 1.0 + VIBRATO[(self.m_vibrato_counter >> 10) as usize & 0x7] * VIBRATO_DEPTH;
+
+        for index in 0..6 {
+            self.m_output[index] = self.channel_sample(index, tremolo_db, vibrato);
+        }
+    }
+
+    fn channel_sample(&mut self, index: usize, tremolo_db: f32, vibrato: f32) -> f32 {
+        let control = self.m_registers[0x20 + index];
+        let f_number = self.m_registers[0x10 + index] as u16 | ((control & 0x1) as u16) << 8;
+        let block = (control >> 1) & 0x7;
+        let channel_sustain = control & 0x20 != 0;
+        let instrument = self.m_registers[0x30 + index];
+        let volume = instrument & 0x0f;
+        let patch_bytes = match instrument >> 4 {
+            0 => &self.m_registers[0..8],
+            number => &PATCHES[number as usize - 1][..],
+        };
+        let patches = [Patch::new(patch_bytes, 0, volume), Patch::new(patch_bytes, 1, volume)];
+        let feedback = patch_bytes[3] & 0x7;
+
+        let key_code = block << 1 | (f_number >> 8) as Byte;
+        // Cycles per sample before the multiplier: F-number * 2^block / 2^19
+        let base_increment = f_number as f32 * (1u32 << block) as f32 / (1u32 << 19) as f32;
+        let key_scale_db = KEY_SCALE_LEVELS[(f_number >> 5) as usize] - 6.0 * (7 - block) as f32;
+
+        let channel = &mut self.m_channels[index];
+        let mut operator_output = [0.0; 2];
+        for (operator, patch) in patches.iter().enumerate() {
+            let key_scale = if patch.key_scale_rate { key_code } else { key_code >> 2 };
+            let state = &mut channel.m_operators[operator];
+            state.update_envelope(patch, key_scale, channel_sustain);
+
+            let mut increment = base_increment * patch.multiplier;
+            if patch.vibrato {
+                increment *= vibrato;
+            }
+            // KSL 1-3 is 1.5, 3 and 6dB per octave
+            let key_scale_level = match patch.key_scale_level {
+                0 => 0.0,
+                level => key_scale_db.max(0.0) / (1 << (3 - level)) as f32,
+            };
+            let attenuation = patch.level_db + key_scale_level + if patch.tremolo { tremolo_db } else { 0.0 };
+            let modulation = if operator == 0 {
+                match feedback {
+                    0 => 0.0,
+                    feedback => (channel.m_feedback[0] + channel.m_feedback[1]) / 2.0 / (1 << (7 - feedback)) as f32,
+                }
+            } else {
+                operator_output[0] * 2.0
+            };
+            operator_output[operator] = state.output(patch, increment, modulation, attenuation);
+        }
+        channel.m_feedback = [channel.m_feedback[1], operator_output[0]];
+        operator_output[1]
+    }
+
+    /// The six FM channels.
+    pub fn channels(&self) -> &[f32] {
+        &self.m_output
+    }
+
+    /// Only the registers; envelopes and phases start over, so held notes fall silent
+    /// until keyed on again.
+    pub fn save_state(&self) -> Vec<Byte> {
+        let mut state = self.m_registers.to_vec();
+        state.push(self.m_address);
+        state
+    }
+
+    pub fn load_state(&mut self, state: &[Byte]) {
+        self.reset();
+        self.m_registers.copy_from_slice(&state[..0x40]);
+        self.m_address = state[0x40];
+    }
+}
@@ -0,0 +1,118 @@
+use chip::Byte;
+use crate::chip;
+
+/// PPU dots per scanline; the prescaler takes 3 of them per CPU cycle.
+const PRESCALER_PERIOD: i16 = 341;
+
+pub const VRC_IRQ_STATE_SIZE: usize = 5;
+
+/// The IRQ counter shared by the VRC4, VRC6 and VRC7. An 8-bit counter counts up from
+/// the latch and fires when it overflows, clocked either every CPU cycle or, in
+/// scanline mode, by a prescaler that approximates one scanline (113 2/3 CPU cycles).
+pub struct VrcIrq {
+    m_latch: Byte,
+    m_counter: Byte,
+    m_prescaler: i16,
+    m_enabled: bool,
+    m_enable_after_ack: bool,
+    m_cycle_mode: bool,
+    m_pending: bool,
+}
+
+impl Default for VrcIrq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VrcIrq {
+    pub fn new() -> Self {
+        VrcIrq {
+            m_latch: 0,
+            m_counter: 0,
+            m_prescaler: PRESCALER_PERIOD,
+            m_enabled: false,
+            m_enable_after_ack: false,
+            m_cycle_mode: false,
+            m_pending: false,
+        }
+    }
+
+    pub fn write_latch(&mut self, value: Byte) {
+        self.m_latch = value;
+    }
+
+    /// VRC4 writes the latch a nibble at a time.
+    pub fn write_latch_low(&mut self, value: Byte) {
+        self.m_latch = (self.m_latch & 0xf0) | (value & 0x0f);
+    }
+
+    pub fn write_latch_high(&mut self, value: Byte) {
+        self.m_latch = (self.m_latch & 0x0f) | (value << 4);
+    }
+
+    /// Bit 0 re-enables on acknowledge, bit 1 enables, bit 2 picks cycle mode.
+    /// Also acknowledges, and reloads the counter when enabling.
+    pub fn write_control(&mut self, value: Byte) {
+        self.m_enable_after_ack = value & 0x1 != 0;
+        self.m_enabled = value & 0x2 != 0;
+        self.m_cycle_mode = value & 0x4 != 0;
+        self.m_pending = false;
+        if self.m_enabled {
+            self.m_counter = self.m_latch;
+            self.m_prescaler = PRESCALER_PERIOD;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.m_pending = false;
+        self.m_enabled = self.m_enable_after_ack;
+    }
+
+    pub fn pending(&self) -> bool {
+        self.m_pending
+    }
+
+    pub fn cpu_cycle(&mut self) {
+        if !self.m_enabled {
+            return;
+        }
+        if self.m_cycle_mode {
+            self.clock();
+            return;
+        }
+        self.m_prescaler -= 3;
+        if self.m_prescaler <= 0 {
+            self.m_prescaler += PRESCALER_PERIOD;
+            self.clock();
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.m_counter == 0xff {
+            self.m_counter = self.m_latch;
+            self.m_pending = true;
+        } else {
+            self.m_counter += 1;
+        }
+    }
+
+    pub fn save_state(&self) -> [Byte; VRC_IRQ_STATE_SIZE] {
+        let [prescaler_low, prescaler_high] = self.m_prescaler.to_le_bytes();
+        let flags = self.m_enabled as Byte
+            | (self.m_enable_after_ack as Byte) << 1
+            | (self.m_cycle_mode as Byte) << 2
+            | (self.m_pending as Byte) << 3;
+        [self.m_latch, self.m_counter, prescaler_low, prescaler_high, flags]
+    }
+
+    pub fn load_state(&mut self, state: &[Byte]) {
+        self.m_latch = state[0];
+        self.m_counter = state[1];
+        self.m_prescaler = i16::from_le_bytes([state[2], state[3]]);
+        self.m_enabled = state[4] & 0x1 != 0;
+        self.m_enable_after_ack = state[4] & 0x2 != 0;
+        self.m_cycle_mode = state[4] & 0x4 != 0;
+        self.m_pending = state[4] & 0x8 != 0;
+    }
+}
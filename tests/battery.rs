@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use nes::battery;
+use nes::emulator::Emulator;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nes-battery-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes an NROM-128 image with battery-backed PRG-RAM that runs `program` from $8000.
+fn write_rom(path: &Path, program: &[u8]) {
+    let mut image = b"NES\x1a\x01\x00\x02\x00".to_vec();
+    image.resize(0x10, 0);
+    let mut prg = vec![0; 0x4000];
+    prg[..program.len()].copy_from_slice(program);
+    prg[0x3ffc] = 0x00;
+    prg[0x3ffd] = 0x80;
+    image.extend(prg);
+    fs::write(path, image).unwrap();
+}
+
+fn run(rom: &Path, save_dir: Option<&Path>) {
+    let mut emulator = Emulator::new();
+    emulator.set_save_directory(save_dir.map(Path::to_path_buf));
+    emulator.run(rom.to_str().unwrap().to_string());
+}
+
+#[test]
+fn save_path_replaces_the_rom_extension() {
+    assert_eq!(battery::save_path(Path::new("roms/zelda.nes"), None), Path::new("roms/zelda.sav"));
+    assert_eq!(
+        battery::save_path(Path::new("roms/zelda.nes"), Some(Path::new("saves"))),
+        Path::new("saves/zelda.sav")
+    );
+}
+
+#[test]
+fn prg_ram_survives_a_restart() {
+    let dir = temp_dir("restart");
+    let rom = dir.join("game.nes");
+    let saves = dir.join("saves");
+
+    // LDA #$42, STA $6000, KIL
+    write_rom(&rom, &[0xa9, 0x42, 0x8d, 0x00, 0x60, 0x02]);
+    run(&rom, Some(&saves));
+    let save = fs::read(saves.join("game.sav")).unwrap();
+    assert_eq!(save.len(), 0x2000);
+    assert_eq!(save[0], 0x42);
+    assert!(!saves.join("game.sav.tmp").exists());
+
+    // LDA $6000, STA $6001, KIL
+    write_rom(&rom, &[0xad, 0x00, 0x60, 0x8d, 0x01, 0x60, 0x02]);
+    run(&rom, Some(&saves));
+    let save = fs::read(saves.join("game.sav")).unwrap();
+    assert_eq!(&save[..2], &[0x42, 0x42]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stopping_a_running_game_flushes_the_save() {
+    let dir = temp_dir("stop");
+    let rom = dir.join("game.nes");
+
+    // LDA #$42, STA $6000, loop: JMP loop
+    write_rom(&rom, &[0xa9, 0x42, 0x8d, 0x00, 0x60, 0x4c, 0x05, 0x80]);
+    let mut emulator = Emulator::new();
+    let stop = emulator.stop_handle();
+    let stopper = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+    emulator.run(rom.to_str().unwrap().to_string());
+    stopper.join().unwrap();
+
+    assert!(!emulator.m_cpu.is_halted());
+    assert_eq!(emulator.m_cpu.r_pc, 0x8005);
+    assert_eq!(fs::read(dir.join("game.sav")).unwrap()[0], 0x42);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn untouched_prg_ram_is_not_saved() {
+    let dir = temp_dir("untouched");
+    let rom = dir.join("game.nes");
+    write_rom(&rom, &[0x02]);
+    run(&rom, None);
+    assert!(!dir.join("game.sav").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn store_replaces_the_previous_save() {
+    let dir = temp_dir("store");
+    let path = dir.join("game.sav");
+    battery::store(&path, &[1, 2, 3]).unwrap();
+    battery::store(&path, &[4, 5]).unwrap();
+    assert_eq!(battery::load(&path).unwrap(), Some(vec![4, 5]));
+    assert_eq!(battery::load(&dir.join("missing.sav")).unwrap(), None);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+use nes::bus::Bus;
+use nes::bus::FlatBus;
+use nes::cpu::CPU;
+
+/// Counts the cycles the CPU reports to its bus.
+struct TickCounter {
+    inner: FlatBus,
+    ticks: u64,
+}
+
+impl Bus for TickCounter {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.inner.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.inner.write(addr, val)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.inner.peek(addr)
+    }
+
+    fn tick(&mut self) {
+        self.ticks += 1;
+    }
+}
+
+fn ticks_match_cycles(cycle_accurate: bool) {
+    let mut inner = FlatBus::new();
+    // LDX #$10; loop: DEX; STA $0200,X; BNE loop; JMP *
+    inner.load(0x8000, &[0xa2, 0x10, 0xca, 0x9d, 0x00, 0x02, 0xd0, 0xfa, 0x4c, 0x08, 0x80]);
+    let mut cpu = CPU::new(TickCounter { inner, ticks: 0 });
+    cpu.reset_with_start_addr(0x8000);
+    cpu.set_cycle_accurate(cycle_accurate);
+
+    let start = cpu.m_cycles;
+    for _ in 0..50 {
+        cpu.step_instruction().unwrap();
+    }
+    assert_eq!(cpu.bus().ticks, cpu.m_cycles - start);
+}
+
+#[test]
+fn bus_is_ticked_once_per_cycle() {
+    ticks_match_cycles(false);
+    ticks_match_cycles(true);
+}
+
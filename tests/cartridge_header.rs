@@ -0,0 +1,93 @@
+use nes::cartridge::Cartridge;
+use nes::cartridge_header::CartridgeHeader;
+use nes::cartridge_header::ConsoleType;
+use nes::cartridge_header::HeaderFormat;
+use nes::cartridge_header::Timing;
+use nes::mapper::create_mapper;
+use nes::mapper::NameTableMirroring;
+
+fn header(bytes: &[u8]) -> [u8; 0x10] {
+    let mut header = [0; 0x10];
+    header[0..4].copy_from_slice(b"NES\x1a");
+    header[4..4 + bytes.len()].copy_from_slice(bytes);
+    header
+}
+
+#[test]
+fn ines() {
+    let parsed = CartridgeHeader::parse(&header(&[2, 1, 0x43, 0x10])).unwrap();
+    assert_eq!(parsed.format, HeaderFormat::INes);
+    assert_eq!(parsed.mapper, 0x14);
+    assert_eq!((parsed.prg_rom_size, parsed.chr_rom_size), (0x8000, 0x2000));
+    assert_eq!((parsed.prg_ram_size, parsed.prg_nvram_size, parsed.chr_ram_size), (0, 0x2000, 0));
+    assert_eq!(parsed.mirroring, NameTableMirroring::Vertical);
+    assert!(parsed.battery && !parsed.trainer);
+    assert_eq!((parsed.timing, parsed.console_type), (Timing::Ntsc, ConsoleType::Nes));
+
+    // Byte 7 onward ignored when it holds garbage
+    let parsed = CartridgeHeader::parse(&header(b"\x01\x00\x10DiskDude!")).unwrap();
+    assert_eq!((parsed.mapper, parsed.timing), (1, Timing::Ntsc));
+    assert_eq!((parsed.prg_ram_size, parsed.chr_ram_size), (0x2000, 0x2000));
+
+    assert!(CartridgeHeader::parse(&[0; 0x10]).is_err());
+}
+
+#[test]
+fn nes2_fields() {
+    let parsed = CartridgeHeader::parse(&header(&[
+        2, 1, 0x12, 0x49, 0x35, 0x10, 0x97, 0x07, 0x02, 0x21, 0x01, 0x2a,
+    ]))
+    .unwrap();
+    assert_eq!(parsed.format, HeaderFormat::Nes2);
+    assert_eq!((parsed.mapper, parsed.submapper), (0x541, 3));
+    assert_eq!((parsed.prg_rom_size, parsed.chr_rom_size), (0x8000, 0x202000));
+    assert_eq!((parsed.prg_ram_size, parsed.prg_nvram_size), (0x2000, 0x8000));
+    assert_eq!((parsed.chr_ram_size, parsed.chr_nvram_size), (0x2000, 0));
+    assert_eq!(parsed.mirroring, NameTableMirroring::Horizontal);
+    assert!(parsed.battery);
+    assert_eq!(parsed.timing, Timing::MultiRegion);
+    assert_eq!(parsed.console_type, ConsoleType::VsSystem { ppu: 1, hardware: 2 });
+    assert_eq!((parsed.misc_roms, parsed.expansion_device), (1, 0x2a));
+
+    let console = |flags7: u8, byte13: u8| {
+        let mut bytes = header(&[1, 0, 0, flags7]);
+        bytes[13] = byte13;
+        CartridgeHeader::parse(&bytes).unwrap().console_type
+    };
+    assert_eq!(console(0x0a, 0), ConsoleType::PlayChoice10);
+    assert_eq!(console(0x0b, 0x3), ConsoleType::Extended(3));
+    let timing = |byte12: u8| {
+        let mut bytes = header(&[1, 0, 0, 0x08]);
+        bytes[12] = byte12;
+        CartridgeHeader::parse(&bytes).unwrap().timing
+    };
+    assert_eq!([0, 1, 3].map(timing), [Timing::Ntsc, Timing::Pal, Timing::Dendy]);
+}
+
+#[test]
+fn nes2_exponent_multiplier_sizes() {
+    // PRG 2^15 * 3, CHR 2^10 * 1; the largest sizes overflow
+    let parsed = CartridgeHeader::parse(&header(&[0x3d, 0x28, 0, 0x08, 0, 0xff])).unwrap();
+    assert_eq!((parsed.prg_rom_size, parsed.chr_rom_size), (0x18000, 0x400));
+    assert!(CartridgeHeader::parse(&header(&[0xfc, 0, 0, 0x08, 0, 0x0f])).is_err());
+}
+
+#[test]
+fn cartridge_loads_nes2_images() {
+    let mut image = header(&[0x36, 0, 0x00, 0x08, 0x01, 0x0f, 0x07, 0x07]).to_vec();
+    image.extend((0..0xa000).map(|i| (i / 0x2000) as u8));
+    let path = std::env::temp_dir().join(format!("nes-header-{}.nes", std::process::id()));
+    std::fs::write(&path, image).unwrap();
+    let mut cartridge = Cartridge::new();
+    let result = cartridge.load_from_file(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    result.unwrap();
+
+    assert_eq!(cartridge.get_rom().len(), 0xa000);
+    assert_eq!(cartridge.get_mapper(), 0x100);
+    assert_eq!(cartridge.get_header().chr_ram_size, 0x2000);
+    assert!(cartridge.has_extended_ram() && !cartridge.has_battery());
+    assert_eq!(cartridge.prg_ram_size(), 0x2000);
+    let error = create_mapper(cartridge).err().unwrap();
+    assert_eq!(error, "Mapper #256 is not supported.");
+}
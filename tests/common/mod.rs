@@ -0,0 +1,136 @@
+// Each test binary only uses part of the helpers.
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+/// A tracer sink the test can still read after handing it to the CPU.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn take_lines(&self) -> Vec<String> {
+        let bytes = std::mem::take(&mut *self.0.borrow_mut());
+        String::from_utf8(bytes).unwrap().lines().map(str::to_string).collect()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Register and cycle state parsed from a nestest.log style line.
+#[derive(Debug, PartialEq)]
+pub struct TraceState {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub cycles: u64,
+}
+
+impl TraceState {
+    pub fn parse(line: &str) -> Option<TraceState> {
+        let field = |name: &str| -> Option<&str> {
+            let start = line.find(name)? + name.len();
+            line[start..].split_whitespace().next()
+        };
+        let byte = |name: &str| u8::from_str_radix(field(name)?, 16).ok();
+        Some(TraceState {
+            pc: u16::from_str_radix(line.get(0..4)?, 16).ok()?,
+            a: byte(" A:")?,
+            x: byte(" X:")?,
+            y: byte(" Y:")?,
+            p: byte(" P:")?,
+            sp: byte(" SP:")?,
+            cycles: field(" CYC:")?.parse().ok()?,
+        })
+    }
+}
+
+/// `size` bytes with every `bank_size` bank filled with its number.
+pub fn labelled(size: usize, bank_size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i / bank_size) as u8).collect()
+}
+
+/// Loads an iNES image with the given mapper, flags 6 bits and ROMs through a
+/// temporary file. PRG is padded to 16KB banks and CHR to 8KB banks.
+pub fn cartridge(mapper: u8, flags6: u8, prg: &[u8], chr: &[u8]) -> nes::cartridge::Cartridge {
+    load_image(ines_header(mapper, flags6, prg, chr), prg, chr)
+}
+
+/// Like `cartridge`, with a NES 2.0 header carrying PRG-RAM sizes (byte 10).
+pub fn nes2_cartridge(mapper: u8, flags6: u8, prg_ram_shifts: u8, prg: &[u8], chr: &[u8]) -> nes::cartridge::Cartridge {
+    let mut header = ines_header(mapper, flags6, prg, chr);
+    header[7] |= 0x08;
+    header[10] = prg_ram_shifts;
+    load_image(header, prg, chr)
+}
+
+/// Like `cartridge`, with a NES 2.0 header naming a submapper (byte 8).
+pub fn submapper_cartridge(mapper: u8, submapper: u8, prg: &[u8], chr: &[u8]) -> nes::cartridge::Cartridge {
+    let mut header = ines_header(mapper, 0, prg, chr);
+    header[7] |= 0x08;
+    header[8] = submapper << 4;
+    load_image(header, prg, chr)
+}
+
+/// Like `cartridge`, with a single 8KB PRG bank, only expressible through the NES 2.0
+/// exponent-multiplier size (2^13 * 1).
+pub fn prg_8kb_cartridge(mapper: u8, prg: &[u8], chr: &[u8]) -> nes::cartridge::Cartridge {
+    let mut image = ines_header(mapper, 0, &[], chr);
+    image[4] = 0x34;
+    image[7] |= 0x08;
+    image[9] = 0x0f;
+    image.extend(prg);
+    image.resize(0x10 + 0x2000, 0);
+    image.extend(chr);
+    image.resize(0x10 + 0x2000 + chr.len().div_ceil(0x2000) * 0x2000, 0);
+    load_file(image)
+}
+
+fn ines_header(mapper: u8, flags6: u8, prg: &[u8], chr: &[u8]) -> Vec<u8> {
+    let prg_banks = prg.len().div_ceil(0x4000).max(1);
+    let chr_banks = chr.len().div_ceil(0x2000);
+    let mut header = vec![
+        b'N', b'E', b'S', 0x1a,
+        prg_banks as u8,
+        chr_banks as u8,
+        (mapper << 4) | flags6,
+        mapper & 0xf0,
+    ];
+    header.resize(0x10, 0);
+    header
+}
+
+fn load_image(mut image: Vec<u8>, prg: &[u8], chr: &[u8]) -> nes::cartridge::Cartridge {
+    let prg_size = image[4] as usize * 0x4000;
+    let chr_size = image[5] as usize * 0x2000;
+    image.extend(prg);
+    image.resize(0x10 + prg_size, 0);
+    image.extend(chr);
+    image.resize(0x10 + prg_size + chr_size, 0);
+    load_file(image)
+}
+
+fn load_file(image: Vec<u8>) -> nes::cartridge::Cartridge {
+    static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("nes-test-{}-{}.nes", std::process::id(), count));
+    std::fs::write(&path, image).unwrap();
+    let mut cartridge = nes::cartridge::Cartridge::new();
+    let result = cartridge.load_from_file(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    result.unwrap();
+    cartridge
+}
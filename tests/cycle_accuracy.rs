@@ -0,0 +1,131 @@
+use nes::bus::Bus;
+use nes::cpu::CPU;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Access {
+    Read(u16),
+    Write(u16, u8),
+}
+
+use Access::Read;
+use Access::Write;
+
+/// Flat memory that records every bus access in order.
+struct RecordingBus {
+    memory: Vec<u8>,
+    accesses: Vec<Access>,
+}
+
+impl RecordingBus {
+    fn with_program(program: &[u8]) -> Self {
+        let mut memory = vec![0; 0x10000];
+        memory[0x8000..0x8000 + program.len()].copy_from_slice(program);
+        RecordingBus { memory, accesses: Vec::new() }
+    }
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.accesses.push(Read(addr));
+        self.memory[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.accesses.push(Write(addr, val));
+        self.memory[addr as usize] = val;
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+}
+
+/// Runs the first instruction of `program` at $8000 cycle by cycle and returns its bus accesses.
+fn accesses(program: &[u8], setup: impl FnOnce(&mut CPU<RecordingBus>)) -> Vec<Access> {
+    let mut bus = RecordingBus::with_program(program);
+    bus.memory[0x0300] = 0x41;
+    // zero page pointer to $02FF
+    bus.memory[0x0010] = 0xff;
+    bus.memory[0x0011] = 0x02;
+    let mut cpu = CPU::new(bus);
+    cpu.reset_with_start_addr(0x8000);
+    cpu.set_cycle_accurate(true);
+    setup(&mut cpu);
+
+    let start = cpu.m_cycles;
+    cpu.step_instruction().unwrap();
+    let cycles = cpu.m_cycles - start;
+
+    let accesses = cpu.bus().accesses.clone();
+    assert_eq!(accesses.len() as u64, cycles, "one bus access per cycle");
+    accesses
+}
+
+#[test]
+fn indexed_read_crossing_a_page_reads_the_unfixed_address_first() {
+    // LDA $20F0,X
+    let crossing = accesses(&[0xbd, 0xf0, 0x20], |cpu| cpu.r_x = 0x20);
+    assert_eq!(crossing, [Read(0x8000), Read(0x8001), Read(0x8002), Read(0x2010), Read(0x2110)]);
+
+    let same_page = accesses(&[0xbd, 0xf0, 0x20], |cpu| cpu.r_x = 0x01);
+    assert_eq!(same_page, [Read(0x8000), Read(0x8001), Read(0x8002), Read(0x20f1)]);
+}
+
+#[test]
+fn indexed_store_always_does_the_dummy_read() {
+    // STA $0200,Y
+    let accesses = accesses(&[0x99, 0x00, 0x02], |cpu| cpu.r_a = 0x5a);
+    assert_eq!(accesses, [Read(0x8000), Read(0x8001), Read(0x8002), Read(0x0200), Write(0x0200, 0x5a)]);
+}
+
+#[test]
+fn read_modify_write_writes_twice() {
+    // INC $0300
+    let accesses = accesses(&[0xee, 0x00, 0x03], |_| ());
+    assert_eq!(accesses, [
+        Read(0x8000),
+        Read(0x8001),
+        Read(0x8002),
+        Read(0x0300),
+        Write(0x0300, 0x41),
+        Write(0x0300, 0x42),
+    ]);
+}
+
+#[test]
+fn indirect_indexed_rmw_takes_eight_cycles() {
+    // DCP ($10),Y with Y = 1
+    let accesses = accesses(&[0xd3, 0x10], |cpu| cpu.r_y = 1);
+    assert_eq!(accesses, [
+        Read(0x8000),
+        Read(0x8001),
+        Read(0x0010),
+        Read(0x0011),
+        Read(0x0200),
+        Read(0x0300),
+        Write(0x0300, 0x41),
+        Write(0x0300, 0x40),
+    ]);
+}
+
+#[test]
+fn taken_branch_across_a_page_reads_the_next_opcode_twice() {
+    // BNE +$7F from $8000 lands on $8081, same page; from $80F0 it crosses
+    let accesses = accesses(&[0xd0, 0x7f], |_| ());
+    assert_eq!(accesses, [Read(0x8000), Read(0x8001), Read(0x8002)]);
+
+    let mut program = vec![0xea; 0xf2];
+    program[0] = 0x4c; // JMP $80F0
+    program[1] = 0xf0;
+    program[2] = 0x80;
+    program[0xf0] = 0xd0; // BNE +$20
+    program[0xf1] = 0x20;
+    let bus = RecordingBus::with_program(&program);
+    let mut cpu = CPU::new(bus);
+    cpu.reset_with_start_addr(0x8000);
+    cpu.set_cycle_accurate(true);
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0x8112);
+    assert_eq!(cpu.bus().accesses[3..], [Read(0x80f0), Read(0x80f1), Read(0x80f2), Read(0x8012)]);
+}
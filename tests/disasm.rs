@@ -0,0 +1,57 @@
+use nes::cartridge::Cartridge;
+use nes::disasm;
+use nes::cpu_opcodes::AddressingMode;
+
+#[test]
+fn disassembly_matches_nestest_log() {
+    let mut cartridge = Cartridge::new();
+    cartridge.load_from_file("tests/roms/nestest.nes").unwrap();
+    let expected = std::fs::read_to_string("tests/roms/nestest.log").unwrap();
+
+    for line in expected.lines() {
+        let pc = u16::from_str_radix(&line[0..4], 16).unwrap();
+        // A few tests run code they copied into RAM
+        if pc < 0x8000 {
+            continue;
+        }
+        let instruction = disasm::disassemble(&cartridge, pc);
+        assert_eq!(instruction.bytes(), line[6..14].trim_end(), "{}", line);
+        assert_eq!(instruction.official, &line[15..16] == " ", "{}", line);
+        assert_eq!(instruction.mnemonic, &line[16..19], "{}", line);
+    }
+}
+
+#[test]
+fn formats_operands_in_assembler_syntax() {
+    let program: &[u8] = &[
+        0xa9, 0x10,       // LDA #$10
+        0xb5, 0x20,       // LDA $20,X
+        0xbe, 0x00, 0x03, // LDX $0300,Y
+        0x6c, 0xff, 0x02, // JMP ($02FF)
+        0x81, 0x40,       // STA ($40,X)
+        0x11, 0x40,       // ORA ($40),Y
+        0x0a,             // ASL A
+        0xd0, 0xf0,       // BNE $0001
+        0x04, 0x44,       // NOP $44 (unofficial)
+    ];
+    let text: Vec<String> = disasm::disassemble_range(program, 0, 9)
+        .iter()
+        .map(|instruction| instruction.to_string())
+        .collect();
+    assert_eq!(text, [
+        "LDA #$10",
+        "LDA $20,X",
+        "LDX $0300,Y",
+        "JMP ($02FF)",
+        "STA ($40,X)",
+        "ORA ($40),Y",
+        "ASL A",
+        "BNE $0001",
+        "NOP $44",
+    ]);
+
+    let nop = disasm::disassemble(program, 17);
+    assert_eq!(nop.mode, AddressingMode::ZeroPage);
+    assert!(!nop.official);
+    assert_eq!(nop.length(), 2);
+}
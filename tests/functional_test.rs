@@ -0,0 +1,44 @@
+use nes::bus::FlatBus;
+use nes::cpu::CPU;
+
+const START_ADDR: u16 = 0x0400;
+const SUCCESS_ADDR: u16 = 0x3469;
+const MAX_INSTRUCTIONS: u64 = 100_000_000;
+
+/// Where the GPL-licensed test image lives, see tests/roms/README.md.
+const IMAGE_VARIABLE: &str = "NES_6502_FUNCTIONAL_TEST";
+
+#[test]
+#[ignore = "needs NES_6502_FUNCTIONAL_TEST, see tests/roms/README.md"]
+fn klaus_dormann_functional_test() {
+    run_functional_test(false);
+}
+
+#[test]
+#[ignore = "needs NES_6502_FUNCTIONAL_TEST, see tests/roms/README.md"]
+fn klaus_dormann_functional_test_cycle_accurate() {
+    run_functional_test(true);
+}
+
+fn run_functional_test(cycle_accurate: bool) {
+    let mut bus = FlatBus::new();
+    let path = std::env::var(IMAGE_VARIABLE).unwrap_or_else(|_| panic!("{} is not set", IMAGE_VARIABLE));
+    bus.load(0, &std::fs::read(&path).unwrap_or_else(|e| panic!("reading {}: {}", path, e)));
+
+    let mut cpu = CPU::new(bus);
+    cpu.reset_with_start_addr(START_ADDR);
+    // The stock build exercises BCD arithmetic, which the 2A03 doesn't have
+    cpu.set_decimal_mode(true);
+    cpu.set_cycle_accurate(cycle_accurate);
+
+    for _ in 0..MAX_INSTRUCTIONS {
+        let pc = cpu.r_pc;
+        cpu.step_instruction().unwrap();
+        // Every test ends in a `JMP *` or branch-to-self trap
+        if cpu.r_pc == pc {
+            assert_eq!(pc, SUCCESS_ADDR, "trapped at ${:04X}", pc);
+            return;
+        }
+    }
+    panic!("no trap reached after {} instructions", MAX_INSTRUCTIONS);
+}
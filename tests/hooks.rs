@@ -0,0 +1,78 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nes::cpu::CPU;
+use nes::hooks::Access;
+use nes::hooks::AccessKind;
+use nes::hooks::HookAction;
+use nes::main_bus::MainBus;
+
+/// LDA $10, STA $0300, KIL at $0400
+fn program_cpu(cycle_accurate: bool) -> CPU<MainBus> {
+    let mut bus = MainBus::new();
+    for (offset, value) in [0xa5, 0x10, 0x8d, 0x00, 0x03, 0x02].iter().enumerate() {
+        bus.write(0x0400 + offset as u16, *value);
+    }
+    bus.write(0x0010, 0x99);
+    let mut cpu = CPU::new(bus);
+    cpu.set_cycle_accurate(cycle_accurate);
+    cpu.reset_with_start_addr(0x0400);
+    cpu
+}
+
+fn recorder(log: &Rc<RefCell<Vec<Access>>>, action: HookAction) -> Box<dyn FnMut(&Access) -> HookAction> {
+    let log = log.clone();
+    Box::new(move |access| {
+        log.borrow_mut().push(*access);
+        action
+    })
+}
+
+#[test]
+fn hooks_see_accesses_in_their_range_with_the_pc() {
+    for cycle_accurate in [false, true] {
+        let mut cpu = program_cpu(cycle_accurate);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        cpu.bus_mut().add_hook(AccessKind::Read, 0x0000..=0x00ff, recorder(&log, HookAction::Continue));
+        cpu.bus_mut().add_hook(AccessKind::Write, 0x0300..=0x0300, recorder(&log, HookAction::Continue));
+        cpu.bus_mut().add_hook(AccessKind::Execute, 0x0402..=0x0402, recorder(&log, HookAction::Continue));
+
+        cpu.step_instruction().unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(
+            *log.borrow(),
+            [
+                Access { kind: AccessKind::Read, addr: 0x0010, value: 0x99, pc: 0x0400 },
+                Access { kind: AccessKind::Execute, addr: 0x0402, value: 0x8d, pc: 0x0402 },
+                Access { kind: AccessKind::Write, addr: 0x0300, value: 0x99, pc: 0x0402 },
+            ]
+        );
+    }
+}
+
+#[test]
+fn breakpoints_are_reported_once() {
+    let mut cpu = program_cpu(false);
+    let log = Rc::new(RefCell::new(Vec::new()));
+    cpu.bus_mut().add_hook(AccessKind::Execute, 0x0402..=0x0402, recorder(&log, HookAction::Break));
+
+    cpu.step_instruction().unwrap();
+    assert!(!cpu.bus_mut().take_break());
+    cpu.step_instruction().unwrap();
+    assert!(cpu.bus_mut().take_break());
+    assert!(!cpu.bus_mut().take_break());
+}
+
+#[test]
+fn removed_hooks_no_longer_fire() {
+    let mut cpu = program_cpu(false);
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let id = cpu.bus_mut().add_hook(AccessKind::Write, 0x0000..=0xffff, recorder(&log, HookAction::Continue));
+    assert!(cpu.bus_mut().remove_hook(id));
+    assert!(!cpu.bus_mut().remove_hook(id));
+
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    assert!(log.borrow().is_empty());
+    assert_eq!(cpu.bus().peek(0x0300), 0x99);
+}
@@ -0,0 +1,92 @@
+use nes::bus::FlatBus;
+use nes::cpu::IllegalOpcodePolicy;
+use nes::cpu::CPU;
+
+fn cpu_with_program(program: &[u8], policy: IllegalOpcodePolicy) -> CPU<FlatBus> {
+    let mut bus = FlatBus::new();
+    bus.load(0x8000, program);
+    let mut cpu = CPU::new(bus);
+    cpu.reset_with_start_addr(0x8000);
+    cpu.set_illegal_opcode_policy(policy);
+    cpu
+}
+
+#[test]
+fn emulate_jams_on_kil() {
+    let mut cpu = cpu_with_program(&[0x02, 0xea], IllegalOpcodePolicy::Emulate);
+    cpu.step_instruction().unwrap();
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.r_pc, 0x8000);
+    for _ in 0..10 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(cpu.r_pc, 0x8000);
+
+    cpu.reset_with_start_addr(0x8001);
+    cpu.step_instruction().unwrap();
+    assert!(!cpu.is_halted());
+    assert_eq!(cpu.r_pc, 0x8002);
+}
+
+#[test]
+fn emulate_runs_unstable_opcodes() {
+    let program = [
+        0xa2, 0x0f, 0xa9, 0x11, 0x8b, 0xff, // LDX #$0F; LDA #$11; XAA #$FF
+        0xa9, 0x00, 0xab, 0x33, // LDA #$00; LXA #$33
+        0xa0, 0x05, 0xa2, 0x20, 0x9c, 0xf0, 0x02, // LDY #$05; LDX #$20; SHY $02F0,X
+        0xa0, 0x10, 0xa2, 0xff, 0x9e, 0x00, 0x02, // LDY #$10; LDX #$FF; SHX $0200,Y
+        0xa9, 0xf7, 0xa2, 0x3c, 0x9f, 0x00, 0x04, // LDA #$F7; LDX #$3C; AHX $0400,Y
+        0x9b, 0x00, 0x06, // TAS $0600,Y
+    ];
+    for cycle_accurate in [false, true] {
+        let mut cpu = cpu_with_program(&program, IllegalOpcodePolicy::Emulate);
+        cpu.set_cycle_accurate(cycle_accurate);
+        for _ in 0..3 {
+            cpu.step_instruction().unwrap();
+        }
+        assert_eq!(cpu.r_a, 0x0f);
+        for _ in 0..2 {
+            cpu.step_instruction().unwrap();
+        }
+        assert_eq!((cpu.r_a, cpu.r_x), (0x22, 0x22));
+        for _ in 0..10 {
+            cpu.step_instruction().unwrap();
+        }
+        // SHY crossed a page, so Y & $03 also became the high byte of the target
+        let memory = &cpu.bus().memory;
+        assert_eq!(memory[0x0110], 0x01);
+        assert_eq!(memory[0x0210], 0x03);
+        assert_eq!(memory[0x0410], 0x04);
+        assert_eq!(memory[0x0610], 0x04);
+        assert_eq!(cpu.r_sp, 0x34);
+        assert!(!cpu.is_halted());
+    }
+}
+
+#[test]
+fn halt_stops_on_kil_and_unstable_opcodes() {
+    for program in [[0x02, 0xea], [0x8b, 0xff]] {
+        let mut cpu = cpu_with_program(&program, IllegalOpcodePolicy::Halt);
+        cpu.r_a = 0x55;
+        cpu.step_instruction().unwrap();
+        assert!(cpu.is_halted());
+        assert_eq!((cpu.r_pc, cpu.r_a), (0x8000, 0x55));
+    }
+}
+
+#[test]
+fn error_rewinds_pc_and_reports_the_opcode() {
+    for (program, message) in [
+        ([0x02, 0xea, 0xea], "Illegal opcode 0x02 at 0x8000"),
+        ([0x9c, 0x00, 0x02], "Illegal opcode 0x9C at 0x8000"),
+    ] {
+        for cycle_accurate in [false, true] {
+            let mut cpu = cpu_with_program(&program, IllegalOpcodePolicy::Error);
+            cpu.set_cycle_accurate(cycle_accurate);
+            assert_eq!(cpu.step_instruction(), Err(message.to_string()));
+            assert_eq!(cpu.r_pc, 0x8000);
+            assert!(!cpu.is_halted());
+            assert_eq!(cpu.bus().memory[0x0200], 0);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use nes::bus::FlatBus;
+use nes::cpu::CPU;
+use nes::cpu::IrqSource;
+
+/// `program` at $8000, the NMI handler at $A000 and the IRQ/BRK handler at $9000.
+fn cpu_with_program(program: &[u8], cycle_accurate: bool) -> CPU<FlatBus> {
+    let mut bus = FlatBus::new();
+    bus.load(0x8000, program);
+    bus.load(0xfffa, &[0x00, 0xa0]);
+    bus.load(0xfffe, &[0x00, 0x90]);
+    let mut cpu = CPU::new(bus);
+    cpu.reset_with_start_addr(0x8000);
+    cpu.set_cycle_accurate(cycle_accurate);
+    cpu
+}
+
+fn steps(cpu: &mut CPU<FlatBus>, count: usize) {
+    for _ in 0..count {
+        cpu.step().unwrap();
+    }
+}
+
+/// Return address and status byte pushed by the last interrupt.
+fn stacked(cpu: &CPU<FlatBus>) -> (u16, u8) {
+    let memory = &cpu.bus().memory;
+    (memory[0x01fc] as u16 | (memory[0x01fd] as u16) << 8, memory[0x01fb])
+}
+
+#[test]
+fn nmi_vectors_even_with_i_set() {
+    for cycle_accurate in [false, true] {
+        let mut cpu = cpu_with_program(&[0xea, 0xea], cycle_accurate);
+        cpu.step_instruction().unwrap();
+        cpu.trigger_nmi();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0xa000);
+        assert_eq!(stacked(&cpu), (0x8001, 0x24));
+        assert_eq!(cpu.r_sp, 0xfa);
+    }
+}
+
+#[test]
+fn irq_is_masked_by_i() {
+    for cycle_accurate in [false, true] {
+        // NOP; NOP; CLI; NOP
+        let mut cpu = cpu_with_program(&[0xea, 0xea, 0x58, 0xea], cycle_accurate);
+        cpu.set_irq(IrqSource::External, true);
+        cpu.step_instruction().unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x8002);
+
+        // Released before I is cleared, nothing happens
+        cpu.set_irq(IrqSource::External, false);
+        cpu.step_instruction().unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x8004);
+    }
+}
+
+#[test]
+fn cli_sei_and_plp_delay_the_irq_poll_by_one_instruction() {
+    for cycle_accurate in [false, true] {
+        // CLI; NOP; NOP
+        let mut cpu = cpu_with_program(&[0x58, 0xea, 0xea], cycle_accurate);
+        cpu.set_irq(IrqSource::External, true);
+        cpu.step_instruction().unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x8002);
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x9000);
+        assert_eq!(stacked(&cpu), (0x8002, 0x20));
+
+        // CLI; SEI; NOP: the IRQ still gets in right after SEI
+        let mut cpu = cpu_with_program(&[0x58, 0x78, 0xea], cycle_accurate);
+        cpu.set_irq(IrqSource::External, true);
+        cpu.step_instruction().unwrap();
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x8002);
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x9000);
+        assert_eq!(stacked(&cpu), (0x8002, 0x24));
+
+        // LDA #$20; PHA; PLP; NOP; NOP: PLP clearing I works like CLI
+        let mut cpu = cpu_with_program(&[0xa9, 0x20, 0x48, 0x28, 0xea, 0xea], cycle_accurate);
+        cpu.set_irq(IrqSource::External, true);
+        for _ in 0..4 {
+            cpu.step_instruction().unwrap();
+        }
+        assert_eq!(cpu.r_pc, 0x8005);
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x9000);
+    }
+}
+
+#[test]
+fn nmi_hijacks_brk_and_irq_in_instant_mode() {
+    // Before the vector fetch: the BRK state is stacked, the NMI handler runs
+    let mut cpu = cpu_with_program(&[0x00, 0x00], false);
+    steps(&mut cpu, 3);
+    cpu.trigger_nmi();
+    assert_eq!(cpu.r_pc, 0xa000);
+    cpu.step_instruction().unwrap();
+    assert_eq!(stacked(&cpu), (0x8002, 0x34));
+    assert_eq!(cpu.r_sp, 0xfa);
+
+    // Too late: the NMI is taken after the BRK
+    let mut cpu = cpu_with_program(&[0x00, 0x00], false);
+    steps(&mut cpu, 5);
+    cpu.trigger_nmi();
+    assert_eq!(cpu.r_pc, 0x9000);
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0xa000);
+    assert_eq!(cpu.r_sp, 0xf7);
+
+    // CLI; NOP, then an IRQ hijacked
+    let mut cpu = cpu_with_program(&[0x58, 0xea, 0xea], false);
+    cpu.set_irq(IrqSource::External, true);
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    steps(&mut cpu, 1);
+    cpu.trigger_nmi();
+    assert_eq!(cpu.r_pc, 0xa000);
+    assert_eq!(stacked(&cpu), (0x8002, 0x20));
+}
+
+#[test]
+fn nmi_hijacks_brk_and_irq_in_cycle_accurate_mode() {
+    // Arriving before the vector fetch on the 6th cycle
+    let mut cpu = cpu_with_program(&[0x00, 0x00], true);
+    steps(&mut cpu, 5);
+    cpu.trigger_nmi();
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0xa000);
+    assert_eq!(stacked(&cpu), (0x8002, 0x34));
+    // Serviced by the hijack, not taken again
+    cpu.bus_mut().load(0xa000, &[0xea]);
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0xa001);
+
+    // Arriving after it
+    let mut cpu = cpu_with_program(&[0x00, 0x00], true);
+    steps(&mut cpu, 6);
+    cpu.trigger_nmi();
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0x9000);
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0xa000);
+
+    // CLI; NOP, then an IRQ hijacked
+    let mut cpu = cpu_with_program(&[0x58, 0xea, 0xea], true);
+    cpu.set_irq(IrqSource::External, true);
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    steps(&mut cpu, 2);
+    cpu.trigger_nmi();
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0xa000);
+    assert_eq!(stacked(&cpu), (0x8002, 0x20));
+}
@@ -0,0 +1,142 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nes::cartridge::Cartridge;
+use nes::main_bus::IORegister;
+use nes::main_bus::MainBus;
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::mapper::NameTableMirroring;
+
+fn nestest_bus() -> MainBus {
+    let mut cartridge = Cartridge::new();
+    cartridge.load_from_file("tests/roms/nestest.nes").unwrap();
+    let mut bus = MainBus::new();
+    bus.set_mapper(create_mapper(cartridge).unwrap());
+    bus
+}
+
+#[test]
+fn internal_ram_is_mirrored() {
+    let mut bus = nestest_bus();
+    bus.write(0x0801, 0x42);
+    assert_eq!(bus.read(0x0001), 0x42);
+    assert_eq!(bus.read(0x1801), 0x42);
+}
+
+#[test]
+fn prg_rom_is_mapped_at_8000() {
+    let mut bus = nestest_bus();
+    // nestest's reset vector points at $C004, NROM-128 mirrors it into $8000
+    assert_eq!(bus.read(0xfffc), 0x04);
+    assert_eq!(bus.read(0xfffd), 0xc0);
+    assert_eq!(bus.read(0x8000), bus.read(0xc000));
+    assert_eq!(bus.peek(0xc000), 0x4c);
+}
+
+#[test]
+fn ppu_registers_are_mirrored_every_8_bytes() {
+    let mut bus = nestest_bus();
+    let written = Rc::new(RefCell::new(Vec::new()));
+    let sink = written.clone();
+    bus.set_write_callback(IORegister::PPUDATA, Box::new(move |value| sink.borrow_mut().push(value)));
+    let status_reads = Rc::new(Cell::new(0));
+    let counter = status_reads.clone();
+    bus.set_read_callback(IORegister::PPUSTATUS, Box::new(move || {
+        counter.set(counter.get() + 1);
+        0x80
+    }));
+
+    bus.write(0x2007, 1);
+    bus.write(0x3fff, 2);
+    bus.write(0x2006, 3);
+    assert_eq!(*written.borrow(), [1, 2]);
+    assert_eq!(bus.read(0x3ffa) & 0xe0, 0x80);
+    // peeking must not trigger the callback
+    bus.peek(0x2002);
+    assert_eq!(status_reads.get(), 1);
+}
+
+#[test]
+fn controller_ports_and_oam_dma_decode() {
+    assert_eq!(IORegister::from_address(0x4014), Some(IORegister::OAMDMA));
+    assert_eq!(IORegister::from_address(0x4016), Some(IORegister::JOY1));
+    assert_eq!(IORegister::from_address(0x4017), Some(IORegister::JOY2));
+    assert_eq!(IORegister::from_address(0x4018), None);
+    assert_eq!(IORegister::from_address(0x4009), None);
+}
+
+#[test]
+fn peek_answers_like_a_read_without_side_effects() {
+    let mut bus = nestest_bus();
+    let reads = Rc::new(Cell::new(0));
+    let counter = reads.clone();
+    bus.set_read_callback(IORegister::JOY1, Box::new(move || {
+        counter.set(counter.get() + 1);
+        0x01
+    }));
+    bus.set_peek_callback(IORegister::JOY1, Box::new(|| 0x01));
+    bus.set_peek_callback(IORegister::PPUSTATUS, Box::new(|| 0xff));
+
+    bus.write(0x0000, 0x40);
+    bus.write(0x2000, 0x15);
+    bus.read(0x0000);
+    assert_eq!(bus.peek(0x4016), 0x41);
+    // PPUSTATUS drives bits 7-5, the rest comes from the PPU's latch
+    assert_eq!(bus.peek(0x2002), 0xf5);
+    assert_eq!(reads.get(), 0);
+    // peeking does not change the open bus either
+    assert_eq!(bus.read(0x5000), 0x40);
+    assert_eq!(bus.read(0x4016), 0x41);
+    assert_eq!(reads.get(), 1);
+}
+
+/// No PRG-RAM, counts the expansion reads that reach it.
+struct CountingMapper {
+    m_reads: Rc<Cell<usize>>,
+}
+
+impl Mapper for CountingMapper {
+    fn peek_prg(&self, _addr: u16) -> u8 {
+        0
+    }
+    fn write_prg(&mut self, _addr: u16, _value: u8) {}
+    fn peek_chr(&self, _addr: u16) -> u8 {
+        0
+    }
+    fn write_chr(&mut self, _addr: u16, _value: u8) {}
+    fn name_table_mirroring(&self) -> NameTableMirroring {
+        NameTableMirroring::Horizontal
+    }
+    fn has_extended_ram(&self) -> bool {
+        false
+    }
+    fn prg_ram_size(&self) -> usize {
+        0
+    }
+    fn read_expansion(&mut self, addr: u16) -> Option<u8> {
+        self.m_reads.set(self.m_reads.get() + 1);
+        self.peek_expansion(addr)
+    }
+    fn peek_expansion(&self, addr: u16) -> Option<u8> {
+        Some((addr >> 8) as u8)
+    }
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    fn load_state(&mut self, _state: &[u8]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[test]
+fn expansion_reads_reach_the_mapper_up_to_7fff() {
+    let reads = Rc::new(Cell::new(0));
+    let mut bus = MainBus::new();
+    bus.set_mapper(Box::new(CountingMapper { m_reads: reads.clone() }));
+    assert_eq!(bus.peek(0x6000), 0x60);
+    assert_eq!(reads.get(), 0);
+    assert_eq!((bus.read(0x5000), bus.read(0x6000), bus.read(0x7fff)), (0x50, 0x60, 0x7f));
+    assert_eq!(reads.get(), 3);
+}
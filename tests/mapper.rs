@@ -0,0 +1,34 @@
+mod common;
+
+use nes::mapper::create_mapper;
+use nes::mapper::NameTableMirroring;
+
+#[test]
+fn nrom_mirrors_a_single_prg_bank() {
+    let prg: Vec<u8> = (0..0x4000).map(|i| (i >> 8) as u8).collect();
+    let mapper = create_mapper(common::cartridge(0, 0x1, &prg, &[0x55; 0x2000])).unwrap();
+    assert_eq!(mapper.peek_prg(0x8100), 0x01);
+    assert_eq!(mapper.peek_prg(0xc100), 0x01);
+    assert_eq!(mapper.peek_prg(0xffff), 0x3f);
+    assert_eq!(mapper.peek_chr(0x1fff), 0x55);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::Vertical);
+    assert!(!mapper.irq_line());
+}
+
+#[test]
+fn unsupported_mappers_are_an_error() {
+    let error = create_mapper(common::cartridge(200, 0, &[], &[])).err().unwrap();
+    assert_eq!(error, "Mapper #200 is not supported.");
+}
+
+#[test]
+fn chr_ram_is_kept_in_save_states() {
+    let mut mapper = create_mapper(common::cartridge(0, 0, &[], &[])).unwrap();
+    mapper.write_chr(0x0123, 0x42);
+    let state = mapper.save_state();
+
+    let mut restored = create_mapper(common::cartridge(0, 0, &[], &[])).unwrap();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.peek_chr(0x0123), 0x42);
+    assert!(restored.load_state(&[]).is_err());
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::mapper::NameTableMirroring;
+use nes::mapper_axrom::MapperAxROM;
+use nes::mapper_uxrom::MapperUxROM;
+
+/// Bank numbers are small, so writing to a byte of 0xff avoids bus conflicts.
+fn board(mapper: u8, prg: &[u8], chr: &[u8]) -> Box<dyn Mapper> {
+    create_mapper(common::cartridge(mapper, 0, prg, chr)).unwrap()
+}
+
+#[test]
+fn uxrom_switches_the_first_16kb() {
+    let mut prg = common::labelled(0x20000, 0x4000);
+    prg[0] = 0xff;
+    let mut mapper = board(2, &prg, &[]);
+    mapper.write_prg(0x8000, 5);
+    assert_eq!((mapper.peek_prg(0x8001), mapper.peek_prg(0xc000)), (5, 7));
+    mapper.write_chr(0x1000, 0x42);
+    assert_eq!(mapper.peek_chr(0x1000), 0x42);
+}
+
+#[test]
+fn cnrom_switches_chr() {
+    let mut prg = vec![0; 0x8000];
+    prg[0] = 0xff;
+    let mut mapper = board(3, &prg, &common::labelled(0x8000, 0x2000));
+    mapper.write_prg(0x8000, 2);
+    assert_eq!(mapper.peek_chr(0x1fff), 2);
+}
+
+#[test]
+fn axrom_switches_32kb_and_the_screen() {
+    let mut mapper = board(7, &common::labelled(0x40000, 0x8000), &[]);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::OneScreenLower);
+    mapper.write_prg(0x8000, 0x13);
+    assert_eq!((mapper.peek_prg(0x8000), mapper.peek_prg(0xffff)), (3, 3));
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::OneScreenHigher);
+}
+
+#[test]
+fn gxrom_and_color_dreams_switch_prg_and_chr() {
+    let mut prg = common::labelled(0x20000, 0x8000);
+    prg[0x7fff] = 0xff;
+    let chr = common::labelled(0x20000, 0x2000);
+
+    let mut gxrom = board(66, &prg, &chr);
+    gxrom.write_prg(0xffff, 0x21);
+    assert_eq!((gxrom.peek_prg(0x8000), gxrom.peek_chr(0x0000)), (2, 1));
+
+    let mut color_dreams = board(11, &prg, &chr);
+    color_dreams.write_prg(0xffff, 0x92);
+    assert_eq!((color_dreams.peek_prg(0x8000), color_dreams.peek_chr(0x0000)), (2, 9));
+}
+
+#[test]
+fn bnrom_switches_32kb() {
+    let mut prg = common::labelled(0x20000, 0x8000);
+    prg[0x7fff] = 0xff;
+    let mut mapper = board(34, &prg, &[]);
+    mapper.write_prg(0xffff, 3);
+    assert_eq!(mapper.peek_prg(0x8000), 3);
+}
+
+#[test]
+fn chr_banks_wrap_around_small_chr() {
+    let mut prg = vec![0; 0x8000];
+    prg[0] = 0xff;
+    // 16KB of CHR, bank 3 is bank 1
+    let mut cnrom = board(3, &prg, &common::labelled(0x4000, 0x2000));
+    cnrom.write_prg(0x8000, 3);
+    assert_eq!((cnrom.peek_chr(0x0000), cnrom.peek_chr(0x1fff)), (1, 1));
+
+    // 64KB of CHR, bank 15 is bank 7
+    let mut color_dreams = board(11, &prg, &common::labelled(0x10000, 0x2000));
+    color_dreams.write_prg(0x8000, 0xf0);
+    assert_eq!((color_dreams.peek_chr(0x0000), color_dreams.peek_chr(0x1fff)), (7, 7));
+
+    // 8KB of CHR-RAM, every bank is the same one
+    let mut gxrom = board(66, &prg, &[]);
+    gxrom.write_prg(0x8000, 0x03);
+    gxrom.write_chr(0x1fff, 0x42);
+    gxrom.write_prg(0x8000, 0x00);
+    assert_eq!(gxrom.peek_chr(0x1fff), 0x42);
+}
+
+#[test]
+fn bus_conflicts_and_the_value_with_rom() {
+    let prg = common::labelled(0x20000, 0x4000);
+    // $8000 holds bank 0, so every write there selects bank 0
+    let mut mapper = board(2, &prg, &[]);
+    mapper.write_prg(0x8000, 5);
+    assert_eq!(mapper.peek_prg(0x8000), 0);
+    // $C000 holds the last bank, 7
+    mapper.write_prg(0xc000, 5);
+    assert_eq!(mapper.peek_prg(0x8000), 5);
+
+    let mut mapper = MapperUxROM::with_bus_conflicts(common::cartridge(2, 0, &prg, &[]), false);
+    mapper.write_prg(0x8000, 5);
+    assert_eq!(mapper.peek_prg(0x8000), 5);
+
+    // AxROM defaults to ANROM/AOROM, without conflicts; AMROM has them
+    let prg = vec![0x01; 0x20000];
+    let mut mapper = MapperAxROM::with_bus_conflicts(common::cartridge(7, 0, &prg, &[]), true);
+    mapper.write_prg(0x8000, 0x12);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::OneScreenLower);
+}
+
+#[test]
+fn nes2_submapper_picks_bus_conflicts() {
+    // Submapper 1 has no conflicts, 2 ANDs, anything else keeps the board default
+    let prg = common::labelled(0x20000, 0x4000);
+    for (submapper, bank) in [(0, 0), (1, 5), (2, 0)] {
+        let mut mapper = create_mapper(common::submapper_cartridge(2, submapper, &prg, &[])).unwrap();
+        mapper.write_prg(0x8000, 5);
+        assert_eq!(mapper.peek_prg(0x8000), bank, "UxROM submapper {}", submapper);
+    }
+
+    let chr = common::labelled(0x8000, 0x2000);
+    for (submapper, bank) in [(0, 0), (1, 3), (2, 0)] {
+        let mut mapper = create_mapper(common::submapper_cartridge(3, submapper, &[0; 0x8000], &chr)).unwrap();
+        mapper.write_prg(0x8000, 3);
+        assert_eq!(mapper.peek_chr(0x0000), bank, "CNROM submapper {}", submapper);
+    }
+
+    let prg = vec![0x01; 0x20000];
+    for (submapper, mirroring) in [
+        (0, NameTableMirroring::OneScreenHigher),
+        (1, NameTableMirroring::OneScreenHigher),
+        (2, NameTableMirroring::OneScreenLower),
+    ] {
+        let mut mapper = create_mapper(common::submapper_cartridge(7, submapper, &prg, &[])).unwrap();
+        mapper.write_prg(0x8000, 0x12);
+        assert_eq!(mapper.name_table_mirroring(), mirroring, "AxROM submapper {}", submapper);
+    }
+}
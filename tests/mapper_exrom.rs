@@ -0,0 +1,233 @@
+mod common;
+
+use nes::main_bus::MainBus;
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+
+/// 64KB of PRG-RAM.
+fn mmc5() -> Box<dyn Mapper> {
+    let prg = common::labelled(0x40000, 0x2000);
+    let chr = common::labelled(0x40000, 0x400);
+    create_mapper(common::nes2_cartridge(5, 0, 0x0a, &prg, &chr)).unwrap()
+}
+
+/// The PPU fetches of one rendered scanline: tiles 2-33, eight sprites, tiles 0-1 of
+/// the next line and the two dummy nametable fetches. Returns what the nametable and
+/// pattern fetches of each background tile read, in fetch order.
+fn scanline(mapper: &mut dyn Mapper, ciram: &[u8]) -> Vec<[u8; 3]> {
+    let tile = |mapper: &mut dyn Mapper, x: u16| {
+        let name = mapper.read_name_table(0x2000 + x, ciram);
+        let attribute = mapper.read_name_table(0x23c0 + x / 4, ciram);
+        let pattern = mapper.read_chr(0x0000);
+        mapper.read_chr(0x0008);
+        [name, attribute, pattern]
+    };
+    let mut tiles: Vec<[u8; 3]> = (2..34).map(|x| tile(mapper, x)).collect();
+    for _ in 0..8 {
+        mapper.read_name_table(0x2000, ciram);
+        mapper.read_name_table(0x2000, ciram);
+        mapper.read_chr(0x1000);
+        mapper.read_chr(0x1008);
+    }
+    tiles.extend((0..2).map(|x| tile(mapper, x)));
+    mapper.read_name_table(0x2002, ciram);
+    mapper.read_name_table(0x2002, ciram);
+    tiles
+}
+
+/// The end of the pre-render line, after which the first visible line is detected.
+fn start_frame(mapper: &mut dyn Mapper, ciram: &[u8]) {
+    mapper.read_name_table(0x2002, ciram);
+    mapper.read_name_table(0x2002, ciram);
+}
+
+fn end_frame(mapper: &mut dyn Mapper) {
+    for _ in 0..3 {
+        mapper.cpu_cycle();
+    }
+}
+
+#[test]
+fn prg_modes() {
+    let mut mapper = mmc5();
+    let banks = |mapper: &dyn Mapper| [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    // Mode 3 at power-on with $5117 = $FF
+    assert_eq!(banks(mapper.as_ref())[3], 31);
+
+    for (addr, bank) in [(0x5114, 0x83), (0x5115, 0x85), (0x5116, 0x87), (0x5117, 0x09)] {
+        mapper.write_expansion(addr, bank);
+    }
+    assert_eq!(banks(mapper.as_ref()), [3, 5, 7, 9]);
+    mapper.write_expansion(0x5100, 2);
+    assert_eq!(banks(mapper.as_ref()), [4, 5, 7, 9]);
+    mapper.write_expansion(0x5100, 1);
+    assert_eq!(banks(mapper.as_ref()), [4, 5, 8, 9]);
+    mapper.write_expansion(0x5100, 0);
+    assert_eq!(banks(mapper.as_ref()), [8, 9, 10, 11]);
+}
+
+#[test]
+fn prg_ram_banks_and_protection() {
+    let mut bus = MainBus::new();
+    bus.set_mapper(mmc5());
+    // Bank 2 at $6000, RAM bank 5 at $A000
+    bus.write(0x5113, 2);
+    bus.write(0x5115, 5);
+
+    bus.write(0x6000, 0x11);
+    assert_eq!(bus.read(0x6000), 0);
+    bus.write(0x5102, 2);
+    bus.write(0x5103, 1);
+    bus.write(0x6000, 0x11);
+    bus.write(0xa001, 0x22);
+    assert_eq!(bus.read(0x6000), 0x11);
+    assert_eq!(bus.read(0xa001), 0x22);
+    assert_eq!(bus.prg_ram()[2 * 0x2000], 0x11);
+    assert_eq!(bus.prg_ram()[5 * 0x2000 + 1], 0x22);
+
+    // Switching the window to ROM
+    bus.write(0x5115, 0x85);
+    assert_eq!(bus.read(0xa001), 5);
+}
+
+#[test]
+fn chr_modes_and_sprite_sets() {
+    let mut mapper = mmc5();
+    for (offset, bank) in (0..8).zip([10, 11, 12, 13, 14, 15, 16, 17]) {
+        mapper.write_expansion(0x5120 + offset, bank);
+    }
+    for (offset, bank) in (0..4).zip([40, 41, 42, 43]) {
+        mapper.write_expansion(0x5128 + offset, bank);
+    }
+    mapper.write_expansion(0x5101, 3);
+    let banks = |mapper: &dyn Mapper| [0x0000, 0x0400, 0x1000, 0x1c00].map(|addr| mapper.peek_chr(addr));
+    assert_eq!(banks(mapper.as_ref()), [10, 11, 14, 17]);
+    mapper.write_expansion(0x5101, 2);
+    assert_eq!(banks(mapper.as_ref()), [22, 23, 30, 35]);
+    mapper.write_expansion(0x5101, 0);
+    assert_eq!(banks(mapper.as_ref()), [136, 137, 140, 143]);
+
+    // With 8x16 sprites the background uses set B, sprites set A
+    mapper.write_expansion(0x5101, 3);
+    mapper.ppu_register_write(0x2000, 0x20);
+    mapper.ppu_register_write(0x2001, 0x18);
+    let ciram = [0u8; 0x800];
+    start_frame(mapper.as_mut(), &ciram);
+    let tiles = scanline(mapper.as_mut(), &ciram);
+    assert!(tiles.iter().all(|tile| tile[2] == 40));
+    // The first sprite fetch of the next line
+    mapper.read_name_table(0x2002, &ciram);
+    for _ in 1..128 {
+        mapper.read_chr(0x0000);
+    }
+    assert_eq!(mapper.read_chr(0x1400), 15);
+}
+
+#[test]
+fn multiplier() {
+    let mut mapper = mmc5();
+    mapper.write_expansion(0x5205, 200);
+    mapper.write_expansion(0x5206, 100);
+    assert_eq!(mapper.peek_expansion(0x5205), Some(0x20));
+    assert_eq!(mapper.peek_expansion(0x5206), Some(0x4e));
+}
+
+#[test]
+fn exram_cpu_access_depends_on_mode() {
+    let mut mapper = mmc5();
+    mapper.write_expansion(0x5c10, 0x42);
+    // Nametable and attribute modes are write-only for the CPU
+    assert_eq!(mapper.peek_expansion(0x5c10), None);
+    mapper.write_expansion(0x5104, 2);
+    assert_eq!(mapper.peek_expansion(0x5c10), Some(0x42));
+    mapper.write_expansion(0x5c10, 0x43);
+    mapper.write_expansion(0x5104, 3);
+    mapper.write_expansion(0x5c10, 0x44);
+    assert_eq!(mapper.peek_expansion(0x5c10), Some(0x43));
+}
+
+#[test]
+fn nametable_mapping_and_fill_mode() {
+    let mut mapper = mmc5();
+    let mut ciram = [0u8; 0x800];
+    // $2000 CIRAM page 1, $2400 ExRAM, $2800 fill, $2C00 CIRAM page 0
+    mapper.write_expansion(0x5105, 0b00_11_10_01);
+    mapper.write_expansion(0x5106, 0x77);
+    mapper.write_expansion(0x5107, 2);
+
+    mapper.write_name_table(0x2005, 1, &mut ciram);
+    mapper.write_name_table(0x2405, 2, &mut ciram);
+    mapper.write_name_table(0x2c06, 3, &mut ciram);
+    assert_eq!(ciram[0x405], 1);
+    assert_eq!(ciram[0x006], 3);
+    assert_eq!(mapper.peek_name_table(0x2405, &ciram), 2);
+    assert_eq!(mapper.peek_name_table(0x2805, &ciram), 0x77);
+    assert_eq!(mapper.peek_name_table(0x2bc0, &ciram), 0xaa);
+}
+
+#[test]
+fn scanline_irq_and_in_frame() {
+    let mut mapper = mmc5();
+    let ciram = [0u8; 0x800];
+    mapper.write_expansion(0x5203, 3);
+    mapper.write_expansion(0x5204, 0x80);
+    mapper.ppu_register_write(0x2001, 0x18);
+
+    start_frame(mapper.as_mut(), &ciram);
+    assert_eq!(mapper.peek_expansion(0x5204), Some(0x00));
+    scanline(mapper.as_mut(), &ciram);
+    assert_eq!(mapper.peek_expansion(0x5204), Some(0x40));
+    for _ in 0..2 {
+        scanline(mapper.as_mut(), &ciram);
+    }
+    assert!(!mapper.irq_line());
+    // Detected at the start of line 3
+    mapper.read_name_table(0x2002, &ciram);
+    assert!(mapper.irq_line());
+
+    // Reading the status acknowledges
+    assert_eq!(mapper.read_expansion(0x5204), Some(0xc0));
+    assert!(!mapper.irq_line());
+
+    end_frame(mapper.as_mut());
+    assert_eq!(mapper.peek_expansion(0x5204), Some(0x00));
+}
+
+#[test]
+fn extended_attributes() {
+    let mut mapper = mmc5();
+    let ciram = [0u8; 0x800];
+    mapper.write_expansion(0x5104, 1);
+    mapper.write_expansion(0x5130, 1);
+    // Tile 5: palette 3, 4KB bank 2 (of the upper 256KB)
+    mapper.write_expansion(0x5c05, 0xc2);
+    mapper.ppu_register_write(0x2001, 0x18);
+
+    start_frame(mapper.as_mut(), &ciram);
+    let tiles = scanline(mapper.as_mut(), &ciram);
+    assert_eq!(tiles[3][1], 0xff);
+    // 4KB bank $42 wraps to bank 2 in 256KB of CHR, starting at 1KB bank 8
+    assert_eq!(tiles[3][2], 8);
+    assert_eq!(tiles[4][1..], [0, 0]);
+}
+
+#[test]
+fn split_screen() {
+    let mut mapper = mmc5();
+    let ciram = [0x99u8; 0x800];
+    // Split on the left 4 tiles, scrolled 8 pixels down, CHR 4KB bank 3
+    mapper.write_expansion(0x5200, 0x84);
+    mapper.write_expansion(0x5201, 8);
+    mapper.write_expansion(0x5202, 3);
+    // Row 1 of the split
+    mapper.write_expansion(0x5c00 + 32 + 2, 0x21);
+    mapper.write_expansion(0x5fc0, 0x04);
+    mapper.ppu_register_write(0x2001, 0x18);
+
+    start_frame(mapper.as_mut(), &ciram);
+    let tiles = scanline(mapper.as_mut(), &ciram);
+    assert_eq!(tiles[0], [0x21, 0x55, 12]);
+    assert_eq!(tiles[1][0], 0);
+    // Outside the split, the normal nametable
+    assert_eq!(tiles[2], [0x99, 0x99, 0]);
+}
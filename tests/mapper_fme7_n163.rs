@@ -0,0 +1,240 @@
+mod common;
+
+use nes::main_bus::MainBus;
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::mapper::NameTableMirroring;
+
+/// Battery-backed PRG-RAM.
+fn board(mapper: u8) -> Box<dyn Mapper> {
+    let prg = common::labelled(0x40000, 0x2000);
+    let chr = common::labelled(0x40000, 0x400);
+    create_mapper(common::cartridge(mapper, 0x02, &prg, &chr)).unwrap()
+}
+
+fn run(mapper: &mut dyn Mapper, cycles: usize) {
+    for _ in 0..cycles {
+        mapper.cpu_cycle();
+    }
+}
+
+fn fme7_register(mapper: &mut dyn Mapper, command: u8, value: u8) {
+    mapper.write_prg(0x8000, command);
+    mapper.write_prg(0xa000, value);
+}
+
+#[test]
+fn fme7_banking() {
+    let mut mapper = board(69);
+    for (command, value) in [(0x9, 3), (0xa, 5), (0xb, 7), (0x6, 42)] {
+        fme7_register(mapper.as_mut(), command, value);
+    }
+    let banks = [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks, [3, 5, 7, 31]);
+    assert_eq!(mapper.peek_chr(0x1800), 42);
+    fme7_register(mapper.as_mut(), 0xc, 1);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::Horizontal);
+}
+
+#[test]
+fn fme7_rom_or_ram_at_6000() {
+    let mut bus = MainBus::new();
+    bus.set_mapper(board(69));
+    bus.write(0x8000, 0x8);
+    bus.write(0xa000, 0x04);
+    assert_eq!(bus.read(0x6000), 4);
+
+    bus.write(0xa000, 0xc0);
+    bus.write(0x6001, 0x55);
+    assert_eq!(bus.read(0x6001), 0x55);
+    assert_eq!(bus.prg_ram()[1], 0x55);
+    // RAM selected but disabled
+    bus.write(0xa000, 0x40);
+    bus.write(0x6001, 0x66);
+    assert_eq!(bus.prg_ram()[1], 0x55);
+}
+
+#[test]
+fn fme7_irq_counter() {
+    let mut mapper = board(69);
+    fme7_register(mapper.as_mut(), 0xe, 5);
+    fme7_register(mapper.as_mut(), 0xf, 0);
+    fme7_register(mapper.as_mut(), 0xd, 0x81);
+    run(mapper.as_mut(), 5);
+    assert!(!mapper.irq_line());
+    run(mapper.as_mut(), 1);
+    assert!(mapper.irq_line());
+    fme7_register(mapper.as_mut(), 0xd, 0x80);
+    assert!(!mapper.irq_line());
+
+    // Counting without IRQs
+    run(mapper.as_mut(), 0x10000);
+    assert!(!mapper.irq_line());
+}
+
+#[test]
+fn sunsoft5b_tone() {
+    let mut mapper = board(69);
+    let write = |mapper: &mut dyn Mapper, register: u8, value: u8| {
+        mapper.write_prg(0xc000, register);
+        mapper.write_prg(0xe000, value);
+    };
+    // Channel A: period 1, tone only, full volume
+    write(mapper.as_mut(), 0x0, 1);
+    write(mapper.as_mut(), 0x7, 0x3e);
+    write(mapper.as_mut(), 0x8, 0x0f);
+
+    let mut high = 0;
+    for _ in 0..64 {
+        mapper.cpu_cycle();
+        let channels = mapper.audio_channels();
+        high += (channels[0] == 1.0) as usize;
+        assert_eq!(channels[1..], [0.0, 0.0]);
+    }
+    assert_eq!(high, 32);
+
+    // Each volume step down is 3dB
+    write(mapper.as_mut(), 0x8, 0x0e);
+    run(mapper.as_mut(), 32);
+    let level = mapper.audio_channels()[0].max(0.0);
+    run(mapper.as_mut(), 16);
+    let level = level.max(mapper.audio_channels()[0]);
+    assert!((level - 10f32.powf(-3.0 / 20.0)).abs() < 1e-4, "level {}", level);
+}
+
+#[test]
+fn n163_banking_and_nametables() {
+    let mut mapper = board(19);
+    mapper.write_prg(0xe000, 3);
+    mapper.write_prg(0xe800, 5);
+    mapper.write_prg(0xf000, 7);
+    let banks = [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks, [3, 5, 7, 31]);
+    mapper.write_prg(0xb800, 42);
+    assert_eq!(mapper.peek_chr(0x1c00), 42);
+
+    let mut ciram = vec![0; 0x800];
+    ciram[0x400] = 0x99;
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::Vertical);
+    for addr in [0xc000, 0xd000] {
+        mapper.write_prg(addr, 0xe1);
+    }
+    assert_eq!(mapper.peek_name_table(0x2000, &ciram), 0x99);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::OneScreenHigher);
+
+    // Nametable from CHR-ROM, which ignores writes
+    mapper.write_prg(0xc800, 5);
+    mapper.write_name_table(0x2410, 0x11, &mut ciram);
+    assert_eq!(mapper.peek_name_table(0x2410, &ciram), 5);
+    assert_eq!(ciram[0x410], 0);
+}
+
+#[test]
+fn n163_ram_port() {
+    let mut mapper = board(19);
+    mapper.write_prg(0xf800, 0x90);
+    for value in [1, 2, 3] {
+        mapper.write_expansion(0x4800, value);
+    }
+    mapper.write_prg(0xf800, 0x90);
+    assert_eq!(mapper.peek_expansion(0x4800), Some(1));
+    let read: Vec<_> = (0..3).map(|_| mapper.read_expansion(0x4800)).collect();
+    assert_eq!(read, [Some(1), Some(2), Some(3)]);
+
+    // Without auto-increment
+    mapper.write_prg(0xf800, 0x11);
+    mapper.read_expansion(0x4800);
+    assert_eq!(mapper.read_expansion(0x4800), Some(2));
+}
+
+#[test]
+fn n163_irq_counter() {
+    let mut mapper = board(19);
+    mapper.write_expansion(0x5000, 0xfd);
+    mapper.write_expansion(0x5800, 0xff);
+    assert_eq!(mapper.peek_expansion(0x5800), Some(0xff));
+    run(mapper.as_mut(), 1);
+    assert!(!mapper.irq_line());
+    run(mapper.as_mut(), 1);
+    assert!(mapper.irq_line());
+    // Stops at $7FFF
+    run(mapper.as_mut(), 10);
+    assert_eq!(mapper.peek_expansion(0x5000), Some(0xff));
+    mapper.write_expansion(0x5000, 0);
+    assert!(!mapper.irq_line());
+}
+
+#[test]
+fn n163_wavetable_channel() {
+    let mut mapper = board(19);
+    let write = |mapper: &mut dyn Mapper, addr: u8, values: &[u8]| {
+        mapper.write_prg(0xf800, 0x80 | addr);
+        for &value in values {
+            mapper.write_expansion(0x4800, value);
+        }
+    };
+    // Samples 0 and 15, then channel 7 standing still on sample 0: 4-sample wave,
+    // full volume, one channel enabled
+    write(mapper.as_mut(), 0x00, &[0xf0]);
+    write(mapper.as_mut(), 0x78, &[0, 0, 0, 0, 0xfc, 0, 0, 0x0f]);
+    run(mapper.as_mut(), 15);
+    assert_eq!(mapper.audio_channels()[7], -1.0);
+
+    // Offset 1 plays sample 15
+    write(mapper.as_mut(), 0x7e, &[1]);
+    run(mapper.as_mut(), 15);
+    assert_eq!(mapper.audio_channels()[7], 0.875);
+    assert!(mapper.audio_channels()[..7].iter().all(|&sample| sample == 0.0));
+
+    // Sound disabled through $E000
+    mapper.write_prg(0xe000, 0x40);
+    write(mapper.as_mut(), 0x7e, &[0]);
+    run(mapper.as_mut(), 15);
+    assert_eq!(mapper.audio_channels()[7], 0.875);
+}
+
+#[test]
+fn n163_prg_ram_protection_and_battery() {
+    let mut bus = MainBus::new();
+    bus.set_mapper(board(19));
+    bus.write(0xf800, 0x41);
+    bus.write(0x6000, 0x11);
+    bus.write(0x6800, 0x22);
+    assert_eq!((bus.read(0x6000), bus.read(0x6800)), (0, 0x22));
+    assert!(bus.take_battery_dirty());
+
+    // Internal RAM is saved after PRG-RAM
+    bus.write(0xf800, 0x85);
+    bus.write(0x4800, 0xaa);
+    assert!(bus.take_battery_dirty());
+    assert!(!bus.take_battery_dirty());
+    let data = bus.battery_data();
+    assert_eq!(data.len(), 0x2000 + 0x80);
+    assert_eq!((data[0x800], data[0x2005]), (0x22, 0xaa));
+
+    let mut restored = MainBus::new();
+    restored.set_mapper(board(19));
+    restored.load_battery_data(&data);
+    assert!(!restored.take_battery_dirty());
+    restored.write(0xf800, 0x05);
+    assert_eq!((restored.read(0x6800), restored.read(0x4800)), (0x22, 0xaa));
+}
+
+#[test]
+fn state_round_trip() {
+    for number in [19, 69] {
+        let mut mapper = board(number);
+        mapper.write_prg(0x8000, 0x9);
+        mapper.write_prg(0xa000, 3);
+        mapper.write_prg(0xe000, 3);
+        mapper.write_prg(0xf800, 0x80);
+        run(mapper.as_mut(), 100);
+        let state = mapper.save_state();
+
+        let mut restored = board(number);
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.peek_prg(0x8000), 3);
+        assert_eq!(restored.save_state(), state);
+        assert!(restored.load_state(&state[1..]).is_err());
+    }
+}
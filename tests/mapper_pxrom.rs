@@ -0,0 +1,75 @@
+mod common;
+
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::mapper::NameTableMirroring;
+
+/// PRG labelled per 8KB bank, CHR per 4KB bank, with banks 1/2 for the lower half
+/// and 3/4 for the upper half in $FD/$FE order.
+fn board(mapper: u8) -> Box<dyn Mapper> {
+    let mut mapper = create_mapper(common::cartridge(mapper, 0, &common::labelled(0x20000, 0x2000), &common::labelled(0x20000, 0x1000))).unwrap();
+    for (addr, bank) in [(0xb000, 1), (0xc000, 2), (0xd000, 3), (0xe000, 4)] {
+        mapper.write_prg(addr, bank);
+    }
+    mapper
+}
+
+#[test]
+fn mmc2_prg_layout() {
+    let mut mapper = board(9);
+    mapper.write_prg(0xa000, 5);
+    let banks = [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks, [5, 13, 14, 15]);
+    mapper.write_prg(0xf000, 1);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::Horizontal);
+}
+
+#[test]
+fn mmc4_prg_layout() {
+    let mut mapper = board(10);
+    mapper.write_prg(0xa000, 2);
+    let banks = [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks, [4, 5, 14, 15]);
+}
+
+#[test]
+fn prg_smaller_than_the_fixed_window_wraps() {
+    // Two 8KB banks: the three fixed slots are the last three of a mirrored image
+    let cartridge = common::cartridge(9, 0, &common::labelled(0x4000, 0x2000), &[]);
+    let mut mapper = create_mapper(cartridge).unwrap();
+    mapper.write_prg(0xa000, 4);
+    let banks = [0x8000, 0xa000, 0xc000, 0xffff].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks, [0, 1, 0, 1]);
+}
+
+#[test]
+fn tile_fetches_flip_the_latches() {
+    let mut mapper = board(9);
+    // Both halves start on their $FE banks
+    assert_eq!((mapper.peek_chr(0x0000), mapper.peek_chr(0x1000)), (2, 4));
+
+    // The fetch that flips the latch still sees the old bank
+    assert_eq!(mapper.read_chr(0x0fd8), 2);
+    assert_eq!(mapper.peek_chr(0x0000), 1);
+    assert_eq!(mapper.peek_chr(0x1000), 4);
+
+    assert_eq!(mapper.read_chr(0x1fdd), 4);
+    assert_eq!(mapper.peek_chr(0x1000), 3);
+    mapper.read_chr(0x1fe8);
+    assert_eq!(mapper.peek_chr(0x1000), 4);
+
+    // Peeking never flips a latch
+    mapper.peek_chr(0x0fe8);
+    assert_eq!(mapper.peek_chr(0x0000), 1);
+}
+
+#[test]
+fn mmc2_lower_latch_only_triggers_on_exact_addresses() {
+    let mut mmc2 = board(9);
+    mmc2.read_chr(0x0fda);
+    assert_eq!(mmc2.peek_chr(0x0000), 2);
+
+    let mut mmc4 = board(10);
+    mmc4.read_chr(0x0fda);
+    assert_eq!(mmc4.peek_chr(0x0000), 1);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::mapper::NameTableMirroring;
+
+fn mmc1(prg_kb: usize, chr_kb: usize) -> Box<dyn Mapper> {
+    let prg = common::labelled(prg_kb * 1024, 0x4000);
+    let chr = common::labelled(chr_kb * 1024, 0x1000);
+    create_mapper(common::cartridge(1, 0, &prg, &chr)).unwrap()
+}
+
+/// Loads `value` into the register at `addr` one bit at a time, leaving cycles in between.
+fn write_register(mapper: &mut dyn Mapper, addr: u16, value: u8) {
+    for bit in 0..5 {
+        mapper.write_prg(addr, (value >> bit) & 1);
+        mapper.cpu_cycle();
+        mapper.cpu_cycle();
+    }
+}
+
+#[test]
+fn powers_up_with_the_last_bank_fixed() {
+    let mapper = mmc1(128, 32);
+    assert_eq!(mapper.peek_prg(0x8000), 0);
+    assert_eq!(mapper.peek_prg(0xffff), 7);
+}
+
+#[test]
+fn prg_bank_modes() {
+    let mut mapper = mmc1(256, 32);
+    write_register(mapper.as_mut(), 0xe000, 5);
+    // fix last
+    assert_eq!((mapper.peek_prg(0x8000), mapper.peek_prg(0xc000)), (5, 15));
+    // fix first
+    write_register(mapper.as_mut(), 0x8000, 0x08);
+    assert_eq!((mapper.peek_prg(0x8000), mapper.peek_prg(0xc000)), (0, 5));
+    // 32KB, ignoring the low bit
+    write_register(mapper.as_mut(), 0x8000, 0x00);
+    assert_eq!((mapper.peek_prg(0x8000), mapper.peek_prg(0xc000)), (4, 5));
+}
+
+#[test]
+fn chr_bank_modes_and_mirroring() {
+    let mut mapper = mmc1(128, 128);
+    write_register(mapper.as_mut(), 0xa000, 5);
+    write_register(mapper.as_mut(), 0xc000, 9);
+    // 8KB mode ignores the low bit and bank 1
+    assert_eq!((mapper.peek_chr(0x0000), mapper.peek_chr(0x1000)), (4, 5));
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::OneScreenLower);
+
+    write_register(mapper.as_mut(), 0x8000, 0x1e);
+    assert_eq!((mapper.peek_chr(0x0000), mapper.peek_chr(0x1000)), (5, 9));
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::Vertical);
+    write_register(mapper.as_mut(), 0x8000, 0x01);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::OneScreenHigher);
+}
+
+#[test]
+fn bit_7_resets_the_shift_register() {
+    let mut mapper = mmc1(128, 32);
+    write_register(mapper.as_mut(), 0x8000, 0x00);
+    mapper.write_prg(0xe000, 1);
+    mapper.cpu_cycle();
+    mapper.cpu_cycle();
+    mapper.write_prg(0x8000, 0x80);
+    mapper.cpu_cycle();
+    mapper.cpu_cycle();
+    // back in fix-last mode, and the pending bit was dropped
+    write_register(mapper.as_mut(), 0xe000, 2);
+    assert_eq!((mapper.peek_prg(0x8000), mapper.peek_prg(0xc000)), (2, 7));
+}
+
+#[test]
+fn writes_on_consecutive_cycles_are_ignored() {
+    let mut mapper = mmc1(128, 32);
+    // A read-modify-write instruction writes twice in a row, only the first counts
+    for bit in [1, 1, 0, 0, 0] {
+        mapper.write_prg(0xe000, bit);
+        mapper.cpu_cycle();
+        mapper.write_prg(0xe000, 0);
+        mapper.cpu_cycle();
+        mapper.cpu_cycle();
+    }
+    assert_eq!(mapper.peek_prg(0x8000), 3);
+}
+
+#[test]
+fn prg_ram_can_be_disabled() {
+    let mut mapper = mmc1(128, 32);
+    assert_eq!(mapper.prg_ram_address(0x6123, false), Some(0x123));
+    write_register(mapper.as_mut(), 0xe000, 0x10);
+    assert_eq!(mapper.prg_ram_address(0x6123, false), None);
+}
+
+#[test]
+fn surom_and_sxrom_banking() {
+    let prg = common::labelled(512 * 1024, 0x4000);
+    // 32KB of battery-backed PRG-RAM
+    let mut mapper = create_mapper(common::nes2_cartridge(1, 0x02, 0x90, &prg, &[])).unwrap();
+    assert_eq!(mapper.prg_ram_size(), 0x8000);
+    assert_eq!(mapper.peek_prg(0xc000), 15);
+
+    write_register(mapper.as_mut(), 0xa000, 0x1c);
+    write_register(mapper.as_mut(), 0xe000, 2);
+    assert_eq!((mapper.peek_prg(0x8000), mapper.peek_prg(0xc000)), (18, 31));
+    assert_eq!(mapper.prg_ram_address(0x6000, false), Some(3 * 0x2000));
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::mapper::NameTableMirroring;
+use nes::mapper_txrom::MapperTxROM;
+use nes::mapper_txrom::Mmc3Revision;
+
+fn mmc3() -> Box<dyn Mapper> {
+    let prg = common::labelled(0x20000, 0x2000);
+    let chr = common::labelled(0x20000, 0x400);
+    create_mapper(common::cartridge(4, 0, &prg, &chr)).unwrap()
+}
+
+/// One scanline's worth of A12 activity: low for the background, one rising edge
+/// for the sprite fetches.
+fn scanline(mapper: &mut dyn Mapper) {
+    mapper.ppu_address(0x0000);
+    for _ in 0..100 {
+        mapper.cpu_cycle();
+    }
+    mapper.ppu_address(0x1000);
+    mapper.cpu_cycle();
+}
+
+#[test]
+fn prg_banks_and_mode() {
+    let mut mapper = mmc3();
+    mapper.write_prg(0x8000, 6);
+    mapper.write_prg(0x8001, 3);
+    mapper.write_prg(0x8000, 7);
+    mapper.write_prg(0x8001, 9);
+    let banks = |mapper: &dyn Mapper| [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks(mapper.as_ref()), [3, 9, 14, 15]);
+    mapper.write_prg(0x8000, 0x40);
+    assert_eq!(banks(mapper.as_ref()), [14, 9, 3, 15]);
+}
+
+#[test]
+fn single_8kb_prg_bank_fills_every_slot() {
+    let prg: Vec<u8> = (0..0x2000).map(|i| i as u8).collect();
+    let mut mapper = create_mapper(common::prg_8kb_cartridge(4, &prg, &[0; 0x2000])).unwrap();
+    for mode in [0x00, 0x40] {
+        mapper.write_prg(0x8000, mode | 6);
+        mapper.write_prg(0x8001, 3);
+        let bytes = [0x8001, 0xa002, 0xc003, 0xe004].map(|addr| mapper.peek_prg(addr));
+        assert_eq!(bytes, [1, 2, 3, 4]);
+    }
+}
+
+#[test]
+fn chr_banks_and_inversion() {
+    let mut mapper = mmc3();
+    for (register, bank) in [(0, 9), (1, 20), (2, 40), (5, 70)] {
+        mapper.write_prg(0x8000, register);
+        mapper.write_prg(0x8001, bank);
+    }
+    let banks = |mapper: &dyn Mapper| [0x0000, 0x0400, 0x0800, 0x1000, 0x1c00].map(|addr| mapper.peek_chr(addr));
+    // 2KB banks ignore the low bit
+    assert_eq!(banks(mapper.as_ref()), [8, 9, 20, 40, 70]);
+    mapper.write_prg(0x8000, 0x80);
+    assert_eq!(mapper.peek_chr(0x1000), 8);
+    assert_eq!(mapper.peek_chr(0x0000), 40);
+    assert_eq!(mapper.peek_chr(0x0c00), 70);
+}
+
+#[test]
+fn mirroring_and_prg_ram_protect() {
+    let mut mapper = mmc3();
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::Vertical);
+    mapper.write_prg(0xa000, 1);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::Horizontal);
+
+    assert_eq!(mapper.prg_ram_address(0x6010, true), Some(0x10));
+    mapper.write_prg(0xa001, 0xc0);
+    assert_eq!(mapper.prg_ram_address(0x6010, false), Some(0x10));
+    assert_eq!(mapper.prg_ram_address(0x6010, true), None);
+    mapper.write_prg(0xa001, 0x00);
+    assert_eq!(mapper.prg_ram_address(0x6010, false), None);
+}
+
+#[test]
+fn irq_fires_after_latch_plus_one_scanlines() {
+    let mut mapper = mmc3();
+    mapper.write_prg(0xc000, 3);
+    mapper.write_prg(0xc001, 0);
+    mapper.write_prg(0xe001, 0);
+    for _ in 0..3 {
+        scanline(mapper.as_mut());
+        assert!(!mapper.irq_line());
+    }
+    scanline(mapper.as_mut());
+    assert!(mapper.irq_line());
+
+    mapper.write_prg(0xe000, 0);
+    assert!(!mapper.irq_line());
+}
+
+#[test]
+fn quick_a12_toggles_are_filtered() {
+    let mut mapper = mmc3();
+    mapper.write_prg(0xc000, 0);
+    mapper.write_prg(0xe001, 0);
+    scanline(mapper.as_mut());
+    mapper.write_prg(0xe000, 0);
+    mapper.write_prg(0xe001, 0);
+    for _ in 0..8 {
+        mapper.ppu_address(0x0000);
+        mapper.ppu_address(0x1000);
+    }
+    assert!(!mapper.irq_line());
+}
+
+#[test]
+fn revisions_differ_on_a_zero_latch() {
+    for (revision, fires_every_scanline) in [(Mmc3Revision::A, false), (Mmc3Revision::B, true)] {
+        let prg = vec![0; 0x8000];
+        let mut mapper = MapperTxROM::with_revision(common::cartridge(4, 0, &prg, &[0; 0x2000]), revision);
+        mapper.write_prg(0xc000, 0);
+        mapper.write_prg(0xc001, 0);
+        mapper.write_prg(0xe001, 0);
+        // Both fire on the reload with 0 right after the $C001 write
+        scanline(&mut mapper);
+        assert!(mapper.irq_line());
+        mapper.write_prg(0xe000, 0);
+        mapper.write_prg(0xe001, 0);
+        scanline(&mut mapper);
+        assert_eq!(mapper.irq_line(), fires_every_scanline, "{:?}", revision);
+    }
+}
+
+#[test]
+fn submapper_4_is_an_mmc3a() {
+    for (submapper, fires_every_scanline) in [(0, true), (4, false)] {
+        let cartridge = common::submapper_cartridge(4, submapper, &[0; 0x8000], &[0; 0x2000]);
+        let mut mapper = create_mapper(cartridge).unwrap();
+        mapper.write_prg(0xc000, 0);
+        mapper.write_prg(0xc001, 0);
+        mapper.write_prg(0xe001, 0);
+        scanline(mapper.as_mut());
+        mapper.write_prg(0xe000, 0);
+        mapper.write_prg(0xe001, 0);
+        scanline(mapper.as_mut());
+        assert_eq!(mapper.irq_line(), fires_every_scanline, "submapper {}", submapper);
+    }
+}
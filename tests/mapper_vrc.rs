@@ -0,0 +1,218 @@
+mod common;
+
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::mapper::NameTableMirroring;
+
+fn vrc(mapper: u8, submapper: u8) -> Box<dyn Mapper> {
+    let prg = common::labelled(0x40000, 0x2000);
+    let chr = common::labelled(0x40000, 0x400);
+    create_mapper(common::submapper_cartridge(mapper, submapper, &prg, &chr)).unwrap()
+}
+
+fn run(mapper: &mut dyn Mapper, cycles: usize) {
+    for _ in 0..cycles {
+        mapper.cpu_cycle();
+    }
+}
+
+#[test]
+fn vrc2_and_vrc4_address_wiring() {
+    // (mapper, submapper, high nibble of CHR bank 0, low nibble of CHR bank 1)
+    let boards = [
+        (21, 1, 0xb002, 0xb004),
+        (21, 2, 0xb040, 0xb080),
+        (21, 0, 0xb040, 0xb004),
+        (23, 1, 0xb001, 0xb002),
+        (23, 2, 0xb004, 0xb008),
+        (23, 0, 0xb004, 0xb002),
+        (25, 1, 0xb002, 0xb001),
+        (25, 2, 0xb008, 0xb004),
+        (25, 0, 0xb002, 0xb004),
+    ];
+    for (number, submapper, high, next) in boards {
+        let mut mapper = vrc(number, submapper);
+        mapper.write_prg(0xb000, 0x5);
+        mapper.write_prg(high, 0x1);
+        mapper.write_prg(next, 0x7);
+        assert_eq!((mapper.peek_chr(0x0000), mapper.peek_chr(0x0400)), (0x15, 0x07), "mapper {} submapper {}", number, submapper);
+    }
+
+    // VRC2a ignores the low bit of CHR banks
+    let mut mapper = vrc(22, 0);
+    mapper.write_prg(0xb000, 0x5);
+    mapper.write_prg(0xb002, 0x1);
+    assert_eq!(mapper.peek_chr(0x0000), 0x0a);
+}
+
+#[test]
+fn vrc4_prg_swap_and_mirroring() {
+    let mut mapper = vrc(23, 1);
+    mapper.write_prg(0x8000, 3);
+    mapper.write_prg(0xa000, 5);
+    let banks = |mapper: &dyn Mapper| [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks(mapper.as_ref()), [3, 5, 30, 31]);
+    mapper.write_prg(0x9002, 0x2);
+    assert_eq!(banks(mapper.as_ref()), [30, 5, 3, 31]);
+
+    mapper.write_prg(0x9000, 3);
+    assert_eq!(mapper.name_table_mirroring(), NameTableMirroring::OneScreenHigher);
+    // VRC2 only has the low bit
+    let mut vrc2 = vrc(23, 3);
+    vrc2.write_prg(0x9000, 3);
+    assert_eq!(vrc2.name_table_mirroring(), NameTableMirroring::Horizontal);
+}
+
+#[test]
+fn vrc4_single_8kb_prg_bank_fills_every_slot() {
+    let prg: Vec<u8> = (0..0x2000).map(|i| i as u8).collect();
+    let mut mapper = create_mapper(common::prg_8kb_cartridge(21, &prg, &[0; 0x2000])).unwrap();
+    mapper.write_prg(0x8000, 3);
+    let bytes = [0x8001, 0xa002, 0xc003, 0xe004].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(bytes, [1, 2, 3, 4]);
+}
+
+#[test]
+fn vrc_irq_cycle_and_scanline_modes() {
+    let mut mapper = vrc(23, 1);
+    // Latch $FE a nibble at a time, enable in cycle mode
+    mapper.write_prg(0xf000, 0xe);
+    mapper.write_prg(0xf001, 0xf);
+    mapper.write_prg(0xf002, 0x06);
+    run(mapper.as_mut(), 1);
+    assert!(!mapper.irq_line());
+    run(mapper.as_mut(), 1);
+    assert!(mapper.irq_line());
+    mapper.write_prg(0xf003, 0);
+    assert!(!mapper.irq_line());
+
+    // Scanline mode: one clock per 341 / 3 CPU cycles
+    mapper.write_prg(0xf000, 0xf);
+    mapper.write_prg(0xf002, 0x02);
+    run(mapper.as_mut(), 113);
+    assert!(!mapper.irq_line());
+    run(mapper.as_mut(), 1);
+    assert!(mapper.irq_line());
+}
+
+#[test]
+fn vrc6_banking_and_swapped_lines() {
+    let mut mapper = vrc(24, 0);
+    mapper.write_prg(0x8000, 2);
+    mapper.write_prg(0xc000, 7);
+    let banks = [0x8000, 0xa000, 0xc000, 0xe000].map(|addr| mapper.peek_prg(addr));
+    assert_eq!(banks, [4, 5, 7, 31]);
+
+    mapper.write_prg(0xd001, 9);
+    assert_eq!(mapper.peek_chr(0x0400), 9);
+    let mut swapped = vrc(26, 0);
+    swapped.write_prg(0xd001, 9);
+    assert_eq!(swapped.peek_chr(0x0800), 9);
+    swapped.write_prg(0xb003, 0x84);
+    assert_eq!(swapped.name_table_mirroring(), NameTableMirroring::Horizontal);
+}
+
+#[test]
+fn vrc6_audio_channels() {
+    let mut mapper = vrc(24, 0);
+    // Pulse 1 at 8/16 duty, full volume, period 0
+    mapper.write_prg(0x9000, 0x7f);
+    mapper.write_prg(0x9001, 0x00);
+    mapper.write_prg(0x9002, 0x80);
+    // Sawtooth adding 8, period 0
+    mapper.write_prg(0xb000, 0x08);
+    mapper.write_prg(0xb002, 0x80);
+
+    let mut high = 0;
+    let mut saw = Vec::new();
+    for _ in 0..16 {
+        mapper.cpu_cycle();
+        let channels = mapper.audio_channels();
+        high += (channels[0] == 1.0) as usize;
+        saw.push((channels[2] * 31.0).round() as u8);
+    }
+    assert_eq!(high, 8);
+    assert_eq!(saw[..14], [0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 0]);
+
+    // Halted channels hold their output
+    mapper.write_prg(0x9003, 0x1);
+    let held = mapper.audio_channels().to_vec();
+    run(mapper.as_mut(), 5);
+    assert_eq!(mapper.audio_channels(), held);
+
+    // A disabled sawtooth stays at 0
+    mapper.write_prg(0x9003, 0x0);
+    mapper.write_prg(0xb000, 0x08);
+    mapper.write_prg(0xb002, 0x00);
+    for _ in 0..16 {
+        mapper.cpu_cycle();
+        assert_eq!(mapper.audio_channels()[2], 0.0);
+    }
+
+    // A disabled pulse stays on step 0, so at 1/16 duty it is high on the 16th clock
+    mapper.write_prg(0x9002, 0x00);
+    run(mapper.as_mut(), 5);
+    mapper.write_prg(0x9000, 0x0f);
+    mapper.write_prg(0x9002, 0x80);
+    let pulse: Vec<bool> = (0..16)
+        .map(|_| {
+            mapper.cpu_cycle();
+            mapper.audio_channels()[0] == 1.0
+        })
+        .collect();
+    assert_eq!(pulse.iter().position(|&high| high), Some(15));
+}
+
+#[test]
+fn vrc7_register_select_lines() {
+    for (submapper, addr) in [(1, 0x8008), (2, 0x8010), (0, 0x8008), (0, 0x8010)] {
+        let mut mapper = vrc(85, submapper);
+        mapper.write_prg(addr, 6);
+        assert_eq!(mapper.peek_prg(0xa000), 6);
+    }
+    let mut mapper = vrc(85, 1);
+    mapper.write_prg(0xd008, 40);
+    assert_eq!(mapper.peek_chr(0x1c00), 40);
+}
+
+#[test]
+fn vrc7_fm_audio() {
+    let mut mapper = vrc(85, 0);
+    let write = |mapper: &mut dyn Mapper, register: u8, value: u8| {
+        mapper.write_prg(0x9010, register);
+        mapper.write_prg(0x9030, value);
+    };
+    // Channel 0: instrument 3 at full volume, F-number $AC, block 4, key on
+    write(mapper.as_mut(), 0x30, 0x30);
+    write(mapper.as_mut(), 0x10, 0xac);
+    write(mapper.as_mut(), 0x20, 0x18);
+
+    let mut peak: f32 = 0.0;
+    for _ in 0..36 * 2000 {
+        mapper.cpu_cycle();
+        peak = peak.max(mapper.audio_channels()[0].abs());
+    }
+    assert!(peak > 0.1, "peak {}", peak);
+    assert!(mapper.audio_channels()[1..].iter().all(|&sample| sample == 0.0));
+
+    // Silencing resets the chip
+    mapper.write_prg(0xe000, 0x40);
+    run(mapper.as_mut(), 36 * 10);
+    assert!(mapper.audio_channels().iter().all(|&sample| sample == 0.0));
+}
+
+#[test]
+fn vrc_state_round_trip() {
+    let mut mapper = vrc(24, 0);
+    mapper.write_prg(0x8000, 3);
+    mapper.write_prg(0xf000, 0x80);
+    mapper.write_prg(0xf001, 0x02);
+    mapper.write_prg(0x9002, 0x80);
+    let state = mapper.save_state();
+
+    let mut restored = vrc(24, 0);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.peek_prg(0x8000), 6);
+    assert_eq!(restored.save_state(), state);
+    assert!(restored.load_state(&state[1..]).is_err());
+}
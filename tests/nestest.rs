@@ -0,0 +1,51 @@
+mod common;
+
+use common::SharedBuffer;
+use common::TraceState;
+
+use nes::cartridge::Cartridge;
+use nes::bus::FlatBus;
+use nes::cpu::CPU;
+
+#[test]
+fn nestest_matches_reference_log() {
+    run_nestest(false);
+}
+
+#[test]
+fn nestest_matches_reference_log_cycle_accurate() {
+    run_nestest(true);
+}
+
+fn run_nestest(cycle_accurate: bool) {
+    let mut cartridge = Cartridge::new();
+    cartridge.load_from_file("tests/roms/nestest.nes").unwrap();
+    let mut bus = FlatBus::new();
+    // NROM-128: the single 16KB bank is mirrored at $8000 and $C000
+    bus.load(0x8000, cartridge.get_rom());
+    bus.load(0xc000, cartridge.get_rom());
+
+    let expected = std::fs::read_to_string("tests/roms/nestest.log").unwrap();
+    let trace = SharedBuffer::default();
+    let mut cpu = CPU::new(bus);
+    cpu.set_tracer(Some(Box::new(trace.clone())));
+    cpu.set_cycle_accurate(cycle_accurate);
+    // Automation mode, no PPU needed
+    cpu.reset_with_start_addr(0xc000);
+
+    for (number, expected_line) in expected.lines().enumerate() {
+        cpu.step_instruction().unwrap();
+        let actual_line = trace.take_lines().pop().unwrap();
+        assert_eq!(
+            TraceState::parse(&actual_line),
+            TraceState::parse(expected_line),
+            "mismatch on line {}\n  expected: {}\n  actual:   {}",
+            number + 1,
+            expected_line,
+            actual_line
+        );
+    }
+    // nestest reports the number of the first failing official/unofficial test here
+    assert_eq!(cpu.bus().memory[0x02], 0, "official opcode test failed");
+    assert_eq!(cpu.bus().memory[0x03], 0, "unofficial opcode test failed");
+}
@@ -0,0 +1,53 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nes::cpu::CPU;
+use nes::main_bus::IORegister;
+use nes::main_bus::MainBus;
+
+/// A bus with `STA $4014` at $0000, sprite data in page $02 and OAM collecting what
+/// gets written to OAMDATA.
+fn dma_bus() -> (MainBus, Rc<RefCell<Vec<u8>>>) {
+    let mut bus = MainBus::new();
+    for (addr, value) in [0x8d, 0x14, 0x40].iter().enumerate() {
+        bus.write(addr as u16, *value);
+    }
+    for offset in 0..0x100 {
+        bus.write(0x0200 + offset, offset as u8 ^ 0xff);
+    }
+    let oam = Rc::new(RefCell::new(Vec::new()));
+    let sink = oam.clone();
+    bus.set_write_callback(IORegister::OAMDATA, Box::new(move |value| sink.borrow_mut().push(value)));
+    (bus, oam)
+}
+
+fn run_dma(cycle_accurate: bool, start_cycle: u64) -> (u64, Vec<u8>) {
+    let (bus, oam) = dma_bus();
+    let mut cpu = CPU::new(bus);
+    cpu.set_cycle_accurate(cycle_accurate);
+    cpu.reset_with_start_addr(0x0000);
+    cpu.r_a = 0x02;
+    cpu.m_cycles = start_cycle;
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0x0003);
+    let oam = oam.borrow().clone();
+    (cpu.m_cycles - start_cycle, oam)
+}
+
+#[test]
+fn dma_copies_the_page_into_oam() {
+    let (_, oam) = run_dma(false, 0);
+    let expected: Vec<u8> = (0..=0xff).map(|offset: u8| offset ^ 0xff).collect();
+    assert_eq!(oam, expected);
+}
+
+#[test]
+fn dma_stalls_for_513_or_514_cycles() {
+    for cycle_accurate in [false, true] {
+        // STA abs is 4 cycles, its write lands on the last of them: cycle 4, then 5
+        assert_eq!(run_dma(cycle_accurate, 0).0, 4 + 513);
+        assert_eq!(run_dma(cycle_accurate, 1).0, 4 + 514);
+        // Past the 32-bit range, about 40 minutes in
+        assert_eq!(run_dma(cycle_accurate, u32::MAX as u64 + 1).0, 4 + 513);
+    }
+}
@@ -0,0 +1,38 @@
+use nes::cpu_opcodes::AddressingMode;
+use nes::cpu_opcodes::MemoryAccess;
+use nes::cpu_opcodes::Mnemonic;
+use nes::cpu_opcodes::INSTRUCTIONS;
+
+#[test]
+fn has_the_151_official_opcodes() {
+    assert_eq!(INSTRUCTIONS.iter().filter(|info| info.official).count(), 151);
+}
+
+#[test]
+fn describes_timing_and_memory_access() {
+    let lda_abs_x = &INSTRUCTIONS[0xbd];
+    assert_eq!(lda_abs_x.mnemonic, Mnemonic::LDA);
+    assert_eq!(lda_abs_x.mode, AddressingMode::AbsoluteX);
+    assert_eq!((lda_abs_x.cycles, lda_abs_x.length), (4, 3));
+    assert!(lda_abs_x.page_penalty);
+    assert_eq!(lda_abs_x.access, MemoryAccess::Read);
+
+    // Stores and read-modify-writes always take the indexing cycle
+    let sta_abs_x = &INSTRUCTIONS[0x9d];
+    assert_eq!(sta_abs_x.cycles, 5);
+    assert!(!sta_abs_x.page_penalty);
+    assert_eq!(sta_abs_x.access, MemoryAccess::Write);
+    let inc_abs_x = &INSTRUCTIONS[0xfe];
+    assert_eq!(inc_abs_x.cycles, 7);
+    assert!(!inc_abs_x.page_penalty);
+    assert_eq!(inc_abs_x.access, MemoryAccess::ReadModifyWrite);
+
+    let asl_a = &INSTRUCTIONS[0x0a];
+    assert_eq!(asl_a.mode, AddressingMode::Accumulator);
+    assert_eq!(asl_a.access, MemoryAccess::None);
+
+    let lax_zp_y = &INSTRUCTIONS[0xb7];
+    assert_eq!(lax_zp_y.mnemonic, Mnemonic::LAX);
+    assert_eq!(lax_zp_y.mode, AddressingMode::ZeroPageY);
+    assert!(!lax_zp_y.official);
+}
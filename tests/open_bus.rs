@@ -0,0 +1,74 @@
+use nes::bus::Bus;
+use nes::cartridge::Cartridge;
+use nes::cpu::CPU;
+use nes::main_bus::IORegister;
+use nes::main_bus::MainBus;
+use nes::mapper::create_mapper;
+
+fn nestest_bus() -> MainBus {
+    let mut cartridge = Cartridge::new();
+    cartridge.load_from_file("tests/roms/nestest.nes").unwrap();
+    let mut bus = MainBus::new();
+    bus.set_mapper(create_mapper(cartridge).unwrap());
+    bus
+}
+
+#[test]
+fn unmapped_reads_return_the_last_bus_value() {
+    let mut bus = nestest_bus();
+    bus.write(0x0000, 0x5a);
+    assert_eq!(bus.read(0x5000), 0x5a);
+    // nestest has no PRG-RAM
+    assert_eq!(bus.read(0x6000), 0x5a);
+    assert_eq!(bus.read(0x0000), 0x5a);
+    bus.write(0x0001, 0x17);
+    assert_eq!(bus.read(0x4018), 0x17);
+    // no one answers $4000-$4013 reads
+    assert_eq!(bus.read(0x4000), 0x17);
+    assert_eq!(bus.peek(0x5000), 0x17);
+}
+
+#[test]
+fn controller_reads_keep_the_upper_bits_open() {
+    let mut bus = nestest_bus();
+    bus.set_read_callback(IORegister::JOY1, Box::new(|| 0xff));
+    // LDA $4016 leaves the operand's high byte on the bus
+    bus.write(0x0000, 0x40);
+    bus.read(0x0000);
+    assert_eq!(bus.read(0x4016), 0x5f);
+}
+
+#[test]
+fn lda_from_unmapped_address_reads_operand_high_byte() {
+    for cycle_accurate in [false, true] {
+        let mut bus = nestest_bus();
+        // LDA $5123 from RAM
+        bus.write(0x0000, 0xad);
+        bus.write(0x0001, 0x23);
+        bus.write(0x0002, 0x51);
+        let mut cpu = CPU::new(bus);
+        cpu.set_cycle_accurate(cycle_accurate);
+        cpu.reset_with_start_addr(0x0000);
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_a, 0x51);
+    }
+}
+
+#[test]
+fn ppu_latch_is_separate_and_decays() {
+    let mut bus = nestest_bus();
+    bus.set_read_callback(IORegister::PPUSTATUS, Box::new(|| 0x80));
+    bus.write(0x2000, 0xab);
+    bus.write(0x0000, 0x00);
+    // write-only registers return the PPU latch, not the CPU bus
+    assert_eq!(bus.read(0x2005), 0xab);
+    // PPUSTATUS drives only bits 7-5
+    assert_eq!(bus.read(0x2002), 0x8b);
+
+    // about 600ms later everything not refreshed has decayed
+    for _ in 0..1_100_000 {
+        bus.tick();
+    }
+    assert_eq!(bus.read(0x2006), 0x00);
+    assert_eq!(bus.read(0x2002), 0x80);
+}
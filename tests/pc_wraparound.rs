@@ -0,0 +1,41 @@
+use nes::bus::FlatBus;
+use nes::cpu::CPU;
+
+fn cpu_at(start: u16, program: &[(u16, &[u8])], cycle_accurate: bool) -> CPU<FlatBus> {
+    let mut bus = FlatBus::new();
+    for (addr, bytes) in program {
+        bus.load(*addr, bytes);
+    }
+    let mut cpu = CPU::new(bus);
+    cpu.set_cycle_accurate(cycle_accurate);
+    cpu.reset_with_start_addr(start);
+    cpu
+}
+
+#[test]
+fn operands_wrap_from_ffff_to_0000() {
+    for cycle_accurate in [false, true] {
+        // LDA #$42 with the operand at $0000
+        let mut cpu = cpu_at(0xffff, &[(0xffff, &[0xa9]), (0x0000, &[0x42])], cycle_accurate);
+        cpu.step_instruction().unwrap();
+        assert_eq!((cpu.r_pc, cpu.r_a), (0x0001, 0x42));
+
+        // LDA $0200 with the high address byte at $0000
+        let mut cpu = cpu_at(0xfffe, &[(0xfffe, &[0xad, 0x00]), (0x0000, &[0x02]), (0x0200, &[0x77])], cycle_accurate);
+        cpu.step_instruction().unwrap();
+        assert_eq!((cpu.r_pc, cpu.r_a), (0x0001, 0x77));
+    }
+}
+
+#[test]
+fn jsr_and_rts_wrap_the_return_address() {
+    for cycle_accurate in [false, true] {
+        // JSR $0300 ending on $FFFF pushes $FFFF, RTS comes back to $0000
+        let mut cpu = cpu_at(0xfffd, &[(0xfffd, &[0x20, 0x00, 0x03]), (0x0300, &[0x60])], cycle_accurate);
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x0300);
+        assert_eq!((cpu.bus().memory[0x01fd], cpu.bus().memory[0x01fc]), (0xff, 0xff));
+        cpu.step_instruction().unwrap();
+        assert_eq!(cpu.r_pc, 0x0000);
+    }
+}
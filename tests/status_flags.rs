@@ -0,0 +1,66 @@
+use nes::bus::FlatBus;
+use nes::cpu::CPU;
+use nes::cpu::IrqSource;
+use nes::status_flags::StatusFlags;
+
+fn cpu_with_program(program: &[u8]) -> CPU<FlatBus> {
+    let mut bus = FlatBus::new();
+    bus.load(0x8000, program);
+    // IRQ/BRK handler at $9000
+    bus.load(0xfffe, &[0x00, 0x90]);
+    let mut cpu = CPU::new(bus);
+    cpu.reset_with_start_addr(0x8000);
+    cpu
+}
+
+#[test]
+fn stack_byte_conversions() {
+    let flags = StatusFlags::CARRY | StatusFlags::NEGATIVE;
+    assert_eq!(flags.to_stack_byte(true), 0xb1);
+    assert_eq!(flags.to_stack_byte(false), 0xa1);
+    assert_eq!(StatusFlags::from_stack_byte(0xff).bits(), 0xcf);
+    assert_eq!(format!("{:?}", flags), "Nv-bdizC");
+}
+
+#[test]
+fn php_and_brk_push_b_set_irq_pushes_it_clear() {
+    // PHP
+    let mut cpu = cpu_with_program(&[0x08]);
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.bus().memory[0x01fd], 0x34);
+
+    // BRK
+    let mut cpu = cpu_with_program(&[0x00]);
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.bus().memory[0x01fb], 0x34);
+    assert_eq!(cpu.r_pc, 0x9000);
+
+    // CLI, then the IRQ is taken
+    let mut cpu = cpu_with_program(&[0x58, 0xea]);
+    cpu.set_irq(IrqSource::External, true);
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.r_pc, 0x9000);
+    assert_eq!(cpu.bus().memory[0x01fb], 0x20);
+}
+
+#[test]
+fn plp_ignores_b_and_bit_5() {
+    // LDA #$FF; PHA; PLP
+    let mut cpu = cpu_with_program(&[0xa9, 0xff, 0x48, 0x28]);
+    for _ in 0..3 {
+        cpu.step_instruction().unwrap();
+    }
+    assert_eq!(cpu.status().bits(), 0xcf);
+    assert!(!cpu.status().contains(StatusFlags::BREAK));
+}
+
+#[test]
+fn set_status_round_trips() {
+    let mut cpu = cpu_with_program(&[]);
+    let status = StatusFlags::ZERO | StatusFlags::DECIMAL | StatusFlags::OVERFLOW;
+    cpu.set_status(status | StatusFlags::BREAK);
+    assert_eq!(cpu.status(), status);
+    assert!(cpu.f_z && cpu.f_d && cpu.f_v && !cpu.f_i);
+}